@@ -0,0 +1,417 @@
+//! Derive macros for [gm-ffi](https://docs.rs/gm-ffi). Use them through `gm_ffi`, which
+//! re-exports each one next to the trait it implements.
+
+#![deny(rust_2018_idioms)]
+#![deny(missing_docs)]
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, Ident, Token};
+
+/// Derives `gm_ffi::layout::GmBufferSerialize` for a struct with named fields, writing its
+/// fields in order, with no padding, exactly as GML's `buffer_write` would. The struct also
+/// gets a `gml_functions()` function returning the matching GML. See `gm_ffi::layout`.
+#[proc_macro_derive(GmBufferSerialize)]
+pub fn derive_gm_buffer_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    gm_buffer_serialize(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn gm_buffer_serialize(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            Fields::Unit => {
+                return Err(Error::new_spanned(&input.ident, "there's nothing to write"))
+            }
+            Fields::Unnamed(fields) => {
+                return Err(Error::new_spanned(
+                    fields,
+                    "GML structs need field names, so tuple structs aren't supported",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "GmBufferSerialize can only be derived for structs",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let name_str = name.unraw().to_string();
+    let idents: Vec<_> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let names: Vec<_> = idents.iter().map(|i| i.unraw().to_string()).collect();
+    let values: Vec<_> = names.iter().map(|n| format!("_value.{}", n)).collect();
+    let types: Vec<_> = fields.iter().map(|f| &f.ty).collect();
+
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for ty in &types {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#ty: ::gm_ffi::layout::GmBufferSerialize));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Returns GML functions which write and read this struct, in the same layout.
+            pub fn gml_functions() -> ::std::string::String {
+                ::gm_ffi::layout::gml_struct_functions(
+                    #name_str,
+                    &[#((
+                        #names,
+                        <#types as ::gm_ffi::layout::GmBufferSerialize>::gml_write("_buffer", #values),
+                        <#types as ::gm_ffi::layout::GmBufferSerialize>::gml_read("_buffer"),
+                    )),*],
+                )
+            }
+        }
+
+        impl #impl_generics ::gm_ffi::layout::GmBufferSerialize for #name #ty_generics #where_clause {
+            fn write_gm(&self, output: &mut ::std::vec::Vec<u8>) {
+                #(::gm_ffi::layout::GmBufferSerialize::write_gm(&self.#idents, output);)*
+            }
+
+            fn read_gm(cursor: &mut ::gm_ffi::BufferCursor<'_>) -> ::core::option::Option<Self> {
+                let start = cursor.position();
+                let mut read = || {
+                    ::core::option::Option::Some(Self {
+                        #(#idents: ::gm_ffi::layout::GmBufferSerialize::read_gm(cursor)?),*
+                    })
+                };
+
+                let output = read();
+                if output.is_none() {
+                    cursor.set_position(start);
+                }
+                output
+            }
+
+            fn gml_write(buffer: &str, value: &str) -> ::std::string::String {
+                ::std::format!(
+                    "{}_write({}, {});",
+                    ::gm_ffi::layout::gml_function_prefix(#name_str),
+                    buffer,
+                    value
+                )
+            }
+
+            fn gml_read(buffer: &str) -> ::std::string::String {
+                ::std::format!(
+                    "{}_read({})",
+                    ::gm_ffi::layout::gml_function_prefix(#name_str),
+                    buffer
+                )
+            }
+        }
+    })
+}
+
+/// Derives conversions between a fieldless enum and reals, numbering its variants from 0 in
+/// order, and gives it a `VARIANTS` constant and a `gml_macros()` function returning the
+/// matching GML `#macro` block. See `gm_ffi::enums`.
+#[proc_macro_derive(GmEnum)]
+pub fn derive_gm_enum(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    gm_enum(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn gm_enum(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let Data::Enum(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "GmEnum can only be derived for enums",
+        ));
+    };
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "GmEnum can't be derived for generic enums",
+        ));
+    }
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(Error::new_spanned(
+                &variant.fields,
+                "GmEnum variants can't have fields",
+            ));
+        }
+        if let Some((_, discriminant)) = &variant.discriminant {
+            return Err(Error::new_spanned(
+                discriminant,
+                "GmEnum variants are numbered from 0 in order, so they can't set their own",
+            ));
+        }
+    }
+
+    let name = &input.ident;
+    let name_str = name.unraw().to_string();
+    let variants: Vec<_> = data.variants.iter().map(|v| &v.ident).collect();
+    let variant_strs: Vec<_> = variants.iter().map(|v| v.unraw().to_string()).collect();
+    let indices = 0..variants.len();
+
+    Ok(quote! {
+        impl #name {
+            /// Every variant, in order, so `VARIANTS[n]` is the variant for the real `n`.
+            pub const VARIANTS: &'static [#name] = &[#(#name::#variants),*];
+
+            /// Returns a GML `#macro` block with a constant for each variant.
+            pub fn gml_macros() -> ::std::string::String {
+                let mut output = ::std::string::String::new();
+                #(
+                    output.push_str(&::std::format!(
+                        "#macro {} {}\n",
+                        ::gm_ffi::enums::gml_constant_name(#name_str, #variant_strs),
+                        #name::#variants as u32
+                    ));
+                )*
+
+                output
+            }
+        }
+
+        impl ::core::convert::TryFrom<f64> for #name {
+            type Error = ::gm_ffi::enums::InvalidEnumValue;
+
+            fn try_from(value: f64) -> ::core::result::Result<Self, Self::Error> {
+                if value >= 0.0 && value.fract() == 0.0 {
+                    match value as usize {
+                        #(#indices => return ::core::result::Result::Ok(#name::#variants),)*
+                        _ => {}
+                    }
+                }
+
+                ::core::result::Result::Err(::gm_ffi::enums::InvalidEnumValue {
+                    name: #name_str,
+                    value,
+                })
+            }
+        }
+
+        impl ::core::convert::TryFrom<::gm_ffi::GmReal> for #name {
+            type Error = ::gm_ffi::enums::InvalidEnumValue;
+
+            fn try_from(value: ::gm_ffi::GmReal) -> ::core::result::Result<Self, Self::Error> {
+                Self::try_from(value.0)
+            }
+        }
+
+        impl ::core::convert::From<#name> for f64 {
+            fn from(o: #name) -> Self {
+                o as u32 as f64
+            }
+        }
+
+        impl ::core::convert::From<#name> for ::gm_ffi::GmReal {
+            fn from(o: #name) -> Self {
+                ::gm_ffi::GmReal(o.into())
+            }
+        }
+    })
+}
+
+/// Derives a set of flags packed into the bits of a real for a `struct Name(u64);`. The flags
+/// are listed in a `#[gm_flags(NAME = bit, ...)]` attribute, and each becomes an associated
+/// constant. The struct also gets set operations, conversions to and from reals, and a
+/// `gml_macros()` function returning the matching GML `#macro` block. See `gm_ffi::flags`.
+#[proc_macro_derive(GmFlags, attributes(gm_flags))]
+pub fn derive_gm_flags(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    gm_flags(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Flag {
+    attrs: Vec<Attribute>,
+    name: Ident,
+    bit: Expr,
+}
+
+impl Parse for Flag {
+    fn parse(input: ParseStream<'_>) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let bit = input.parse()?;
+
+        Ok(Self { attrs, name, bit })
+    }
+}
+
+fn gm_flags(input: DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let is_newtype = match &input.data {
+        Data::Struct(data) => matches!(&data.fields, Fields::Unnamed(f) if f.unnamed.len() == 1),
+        _ => false,
+    };
+    if !is_newtype {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "GmFlags can only be derived for a struct like `struct Name(u64);`",
+        ));
+    }
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "GmFlags can't be derived for generic structs",
+        ));
+    }
+
+    let mut flags = Vec::new();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("gm_flags")) {
+        flags.extend(attr.parse_args_with(Punctuated::<Flag, Token![,]>::parse_terminated)?);
+    }
+
+    let name = &input.ident;
+    let name_str = name.unraw().to_string();
+    let attrs: Vec<_> = flags.iter().map(|f| &f.attrs).collect();
+    let flag_names: Vec<_> = flags.iter().map(|f| &f.name).collect();
+    let flag_strs: Vec<_> = flag_names.iter().map(|f| f.unraw().to_string()).collect();
+    let bits: Vec<_> = flags.iter().map(|f| &f.bit).collect();
+
+    Ok(quote! {
+        const _: () = {
+            #(::core::assert!((#bits as u32) < ::gm_ffi::flags::MAX_FLAGS, "flags must fit in a real");)*
+        };
+
+        #[allow(dead_code)]
+        impl #name {
+            #(
+                #(#attrs)*
+                pub const #flag_names: #name = #name(1 << #bits);
+            )*
+
+            /// No flags set.
+            pub const EMPTY: #name = #name(0);
+
+            /// Every flag set.
+            pub const ALL: #name = #name(0 #(| (1 << #bits))*);
+
+            /// Returns the raw bits.
+            pub const fn bits(self) -> u64 {
+                self.0
+            }
+
+            /// Creates a set from raw bits, returning `None` if any bit isn't a flag.
+            pub const fn from_bits(bits: u64) -> ::core::option::Option<Self> {
+                if bits & !Self::ALL.0 == 0 {
+                    ::core::option::Option::Some(Self(bits))
+                } else {
+                    ::core::option::Option::None
+                }
+            }
+
+            /// Returns if every flag in `other` is set.
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            /// Returns if no flags are set.
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            /// Sets every flag in `other`.
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            /// Clears every flag in `other`.
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            /// Sets or clears every flag in `other`.
+            pub fn set(&mut self, other: Self, value: bool) {
+                if value {
+                    self.insert(other);
+                } else {
+                    self.remove(other);
+                }
+            }
+
+            /// Returns a GML `#macro` block with a constant for each flag.
+            pub fn gml_macros() -> ::std::string::String {
+                let mut output = ::std::string::String::new();
+                #(
+                    output.push_str(&::std::format!(
+                        "#macro {} {}\n",
+                        ::gm_ffi::enums::gml_constant_name(#name_str, #flag_strs),
+                        #name::#flag_names.0
+                    ));
+                )*
+
+                output
+            }
+        }
+
+        impl ::core::ops::BitOr for #name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl ::core::ops::BitAnd for #name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl ::core::convert::TryFrom<f64> for #name {
+            type Error = ::gm_ffi::flags::InvalidFlags;
+
+            fn try_from(value: f64) -> ::core::result::Result<Self, Self::Error> {
+                let err = ::gm_ffi::flags::InvalidFlags {
+                    name: #name_str,
+                    value,
+                };
+
+                if value < 0.0
+                    || value.fract() != 0.0
+                    || value >= (1u64 << ::gm_ffi::flags::MAX_FLAGS) as f64
+                {
+                    return ::core::result::Result::Err(err);
+                }
+
+                Self::from_bits(value as u64).ok_or(err)
+            }
+        }
+
+        impl ::core::convert::TryFrom<::gm_ffi::GmReal> for #name {
+            type Error = ::gm_ffi::flags::InvalidFlags;
+
+            fn try_from(value: ::gm_ffi::GmReal) -> ::core::result::Result<Self, Self::Error> {
+                Self::try_from(value.0)
+            }
+        }
+
+        impl ::core::convert::From<#name> for f64 {
+            fn from(o: #name) -> Self {
+                o.0 as f64
+            }
+        }
+
+        impl ::core::convert::From<#name> for ::gm_ffi::GmReal {
+            fn from(o: #name) -> Self {
+                ::gm_ffi::GmReal(o.into())
+            }
+        }
+    })
+}
@@ -0,0 +1,129 @@
+//! A bump allocator over a single GM buffer, so lots of small blobs can share one buffer.
+
+use crate::{GmBuffer, GmId, GmReal};
+
+/// A bump allocator which sub-allocates regions of one big `GmBuffer<u8>`.
+///
+/// Every allocation returns its byte offset into the buffer as a [GmReal], which GML
+/// can use directly with `buffer_peek`. For example, after `alloc_str("hello")` returns
+/// `offset`, GML reads the string with `buffer_peek(arena, offset, buffer_string)`.
+///
+/// Individual allocations are never freed. Instead, the whole arena is [reset](GmArena::reset)
+/// at once, usually at the start of each frame.
+#[derive(Debug)]
+pub struct GmArena {
+    buffer: GmBuffer<u8>,
+    head: usize,
+}
+
+impl GmArena {
+    /// Creates a new, empty arena over `buffer`.
+    pub fn new(buffer: GmBuffer<u8>) -> Self {
+        Self { buffer, head: 0 }
+    }
+
+    /// Reserves `len` bytes, aligned to `align` bytes from the start of the buffer, returning
+    /// the offset. Returns `None` if there isn't room, or `align` isn't a power of two.
+    pub fn alloc(&mut self, len: usize, align: usize) -> Option<GmReal> {
+        if !align.is_power_of_two() {
+            return None;
+        }
+
+        let start = self.head.checked_next_multiple_of(align)?;
+        let end = start.checked_add(len)?;
+        if end > self.buffer.byte_len() {
+            return None;
+        }
+        self.head = end;
+
+        Some(GmReal::new(start as f64))
+    }
+
+    /// Copies `bytes` into the arena, returning the offset.
+    pub fn alloc_bytes(&mut self, bytes: &[u8]) -> Option<GmReal> {
+        let offset = self.alloc(bytes.len(), 1)?;
+        let start = offset.as_usize();
+        self.buffer.as_bytes_mut()[start..start + bytes.len()].copy_from_slice(bytes);
+
+        Some(offset)
+    }
+
+    /// Copies `value` into the arena as a NUL terminated string, readable in GML with `buffer_string`.
+    pub fn alloc_str(&mut self, value: &str) -> Option<GmReal> {
+        let offset = self.alloc(value.len() + 1, 1)?;
+        let start = offset.as_usize();
+        let dest = &mut self.buffer.as_bytes_mut()[start..=start + value.len()];
+        dest[..value.len()].copy_from_slice(value.as_bytes());
+        dest[value.len()] = 0;
+
+        Some(offset)
+    }
+
+    /// Copies `value` into the arena, aligned for `T`, returning the offset.
+    pub fn alloc_value<T: Copy>(&mut self, value: T) -> Option<GmReal> {
+        let offset = self.alloc(core::mem::size_of::<T>(), core::mem::align_of::<T>())?;
+        unsafe {
+            let dest = self
+                .buffer
+                .as_bytes_mut()
+                .as_mut_ptr()
+                .add(offset.as_usize());
+            (dest as *mut T).write_unaligned(value);
+        }
+
+        Some(offset)
+    }
+
+    /// Returns the `len` bytes at `offset`, if they've been allocated.
+    pub fn get_mut(&mut self, offset: GmReal, len: usize) -> Option<&mut [u8]> {
+        let start = offset.as_usize();
+        let end = start.checked_add(len)?;
+        if end > self.head {
+            return None;
+        }
+
+        Some(&mut self.buffer.as_bytes_mut()[start..end])
+    }
+
+    /// The number of bytes allocated so far, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.head
+    }
+
+    /// The number of bytes left in the arena.
+    pub fn remaining(&self) -> usize {
+        self.buffer.byte_len() - self.head
+    }
+
+    /// Frees every allocation at once. Offsets handed out before this are no longer valid.
+    pub fn reset(&mut self) {
+        self.head = 0;
+    }
+
+    /// Gives up the arena, returning the id of the underlying buffer.
+    pub fn id(self) -> GmId {
+        self.buffer.id()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn bump() {
+        let mut backing = vec![0u64; 2];
+        let ptr = GmPtr::new(backing.as_mut_ptr() as *const _);
+        let mut arena = GmArena::new(unsafe { GmBuffer::new(GmId::new(0.0), ptr, 16) });
+
+        assert_eq!(arena.alloc_str("hi"), Some(GmReal::new(0.0)));
+        assert_eq!(arena.alloc_value(7u32), Some(GmReal::new(4.0)));
+        assert_eq!(arena.remaining(), 8);
+        assert!(arena.alloc_bytes(&[1; 9]).is_none());
+        assert_eq!(arena.get_mut(GmReal::new(0.0), 3).unwrap(), b"hi\0");
+
+        arena.reset();
+        assert_eq!(arena.alloc_bytes(&[1; 16]), Some(GmReal::new(0.0)));
+    }
+}
@@ -0,0 +1,180 @@
+//! Firing GM async events from Rust, so background work can tell GML it's done instead of
+//! being polled every step.
+//!
+//! This needs the runner's callbacks, so the DLL has to include
+//! [export_register_callbacks](crate::export_register_callbacks) (or
+//! [export_runner_init](crate::export_runner_init)).
+//! ```no_run
+//! use gm_ffi::AsyncEvent;
+//!
+//! std::thread::spawn(|| {
+//!     let body = "..."; // some slow download
+//!     AsyncEvent::social()
+//!         .with("type", "download_finished")
+//!         .with("size", body.len() as f64)
+//!         .dispatch()
+//!         .unwrap();
+//! });
+//! ```
+//! ```gml
+//! // in the Async - Social event
+//! if (async_load[? "type"] == "download_finished") {
+//!     show_debug_message("got " + string(async_load[? "size"]) + " bytes");
+//! }
+//! ```
+
+use crate::runner;
+use crate::GmReal;
+use std::ffi::CString;
+use std::fmt;
+
+/// A value in an [AsyncEvent]'s `async_load` map.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsyncValue {
+    /// A real.
+    Real(f64),
+    /// A string.
+    String(String),
+}
+
+impl From<f64> for AsyncValue {
+    fn from(o: f64) -> Self {
+        AsyncValue::Real(o)
+    }
+}
+
+impl From<i32> for AsyncValue {
+    fn from(o: i32) -> Self {
+        AsyncValue::Real(o.into())
+    }
+}
+
+impl From<u32> for AsyncValue {
+    fn from(o: u32) -> Self {
+        AsyncValue::Real(o.into())
+    }
+}
+
+impl From<bool> for AsyncValue {
+    fn from(o: bool) -> Self {
+        AsyncValue::Real(if o { 1.0 } else { 0.0 })
+    }
+}
+
+impl From<GmReal> for AsyncValue {
+    fn from(o: GmReal) -> Self {
+        AsyncValue::Real(o.0)
+    }
+}
+
+impl From<String> for AsyncValue {
+    fn from(o: String) -> Self {
+        AsyncValue::String(o)
+    }
+}
+
+impl From<&str> for AsyncValue {
+    fn from(o: &str) -> Self {
+        AsyncValue::String(o.to_string())
+    }
+}
+
+/// An async event, with the entries GML will see in `async_load`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AsyncEvent {
+    event: i32,
+    entries: Vec<(String, AsyncValue)>,
+}
+
+impl AsyncEvent {
+    /// Creates an event of the given kind. Extensions can only really fire
+    /// [Async - Social](runner::EVENT_OTHER_SOCIAL) events, so [social](AsyncEvent::social)
+    /// is usually what's wanted.
+    pub fn new(event: i32) -> Self {
+        Self {
+            event,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Creates an Async - Social event.
+    pub fn social() -> Self {
+        Self::new(runner::EVENT_OTHER_SOCIAL)
+    }
+
+    /// Adds an entry to `async_load`.
+    pub fn with(mut self, key: &str, value: impl Into<AsyncValue>) -> Self {
+        self.entries.push((key.to_string(), value.into()));
+        self
+    }
+
+    /// Returns the entries added so far.
+    pub fn entries(&self) -> &[(String, AsyncValue)] {
+        &self.entries
+    }
+
+    /// Creates the `ds_map` and fires the event. GM runs it in the next async step, on its own
+    /// thread, so this can be called from anywhere.
+    pub fn dispatch(self) -> Result<(), RunnerNotReady> {
+        let callbacks = runner::callbacks().ok_or(RunnerNotReady)?;
+
+        unsafe {
+            let map = (callbacks.create_ds_map)(0);
+            for (key, value) in &self.entries {
+                let key = c_string(key);
+                match value {
+                    AsyncValue::Real(value) => {
+                        (callbacks.ds_map_add_double)(map, key.as_ptr(), *value);
+                    }
+                    AsyncValue::String(value) => {
+                        let value = c_string(value);
+                        (callbacks.ds_map_add_string)(map, key.as_ptr(), value.as_ptr());
+                    }
+                }
+            }
+            (callbacks.create_async_event_with_ds_map)(map, self.event);
+        }
+
+        Ok(())
+    }
+}
+
+// GM strings can't hold a NUL, so drop any
+fn c_string(input: &str) -> CString {
+    CString::new(input.replace('\0', "")).unwrap_or_default()
+}
+
+/// Returned when an [AsyncEvent] is dispatched before the runner has given us its callbacks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunnerNotReady;
+
+impl fmt::Display for RunnerNotReady {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the runner hasn't called `RegisterCallbacks` or `YYExtensionInitialise` yet")
+    }
+}
+
+impl std::error::Error for RunnerNotReady {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_entries() {
+        let event = AsyncEvent::social()
+            .with("type", "loaded")
+            .with("ok", true)
+            .with("count", 3);
+
+        assert_eq!(
+            event.entries(),
+            &[
+                ("type".to_string(), AsyncValue::String("loaded".to_string())),
+                ("ok".to_string(), AsyncValue::Real(1.0)),
+                ("count".to_string(), AsyncValue::Real(3.0)),
+            ]
+        );
+        assert_eq!(event.dispatch(), Err(RunnerNotReady));
+    }
+}
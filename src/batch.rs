@@ -0,0 +1,530 @@
+//! Running many small commands in a single extern call, to cut down on the cost of
+//! crossing the FFI boundary.
+//!
+//! GML packs each command into a buffer as:
+//! ```gml
+//! buffer_write(commands, buffer_u32, OP_SPAWN); // the opcode
+//! buffer_write(commands, buffer_u32, 2);        // the number of arguments
+//! buffer_write(commands, buffer_f64, _x);       // the arguments
+//! buffer_write(commands, buffer_f64, _y);
+//! ```
+//! and then hands the whole thing to one export, generated by
+//! [export_run_batch](crate::export_run_batch), which calls [Batch::run]. Each command's
+//! [OutputCode] is written into a results buffer of `buffer_f64`s, one per command.
+//!
+//! For commands with arguments other than reals, or which don't need results, there's
+//! [CommandBuffer], whose handlers read their own arguments.
+
+use crate::{BufferCursor, GmError, OutputCode};
+use std::collections::HashMap;
+use std::fmt;
+
+type Handler = Box<dyn FnMut(&[f64]) -> OutputCode + Send>;
+
+/// A table of opcode handlers, which can run a packed buffer of commands.
+///
+/// ```
+/// # use gm_ffi::{batch::Batch, OutputCode};
+/// use once_cell::sync::Lazy;
+/// use std::sync::Mutex;
+///
+/// const OP_LOG: u32 = 0;
+///
+/// static BATCH: Lazy<Mutex<Batch>> = Lazy::new(|| {
+///     let mut batch = Batch::new();
+///     batch.register(OP_LOG, |args| {
+///         println!("{:?}", args);
+///         OutputCode::SUCCESS
+///     });
+///     Mutex::new(batch)
+/// });
+///
+/// gm_ffi::export_run_batch!(BATCH);
+/// ```
+#[derive(Default)]
+pub struct Batch {
+    handlers: Handlers<Handler>,
+    args: Vec<f64>,
+}
+
+/// An error which stopped a [Batch] part way through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchError {
+    /// The command starting at this byte offset was cut off.
+    Truncated {
+        /// The byte offset of the broken command.
+        offset: usize,
+        /// The number of commands which ran before it.
+        executed: usize,
+    },
+    /// There were more commands than room in the results buffer. Only the commands which
+    /// had room were run.
+    ResultsFull {
+        /// The number of commands which ran.
+        executed: usize,
+    },
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Truncated { offset, executed } => write!(
+                f,
+                "command at byte {} was truncated after running {} commands",
+                offset, executed
+            ),
+            BatchError::ResultsFull { executed } => {
+                write!(f, "results buffer was full after {} commands", executed)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl Batch {
+    /// Creates a new batch with no handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for `opcode`, replacing any existing one.
+    pub fn register(
+        &mut self,
+        opcode: u32,
+        handler: impl FnMut(&[f64]) -> OutputCode + Send + 'static,
+    ) {
+        self.handlers.0.insert(opcode, Box::new(handler));
+    }
+
+    /// Runs every command in `commands`, writing each command's result into `results`. Commands
+    /// with no registered handler get [OutputCode::FAILURE]. Returns the number of commands run.
+    ///
+    /// `commands` is read until it runs out. A command cut off part way is an error, but every
+    /// command before it has still run.
+    pub fn run(&mut self, commands: &[u8], results: &mut [f64]) -> Result<usize, BatchError> {
+        let args = &mut self.args;
+
+        self.handlers.walk(commands, 8, |command, handler| {
+            let Some(result) = results.get_mut(command.index) else {
+                return Err(BatchError::ResultsFull {
+                    executed: command.index,
+                });
+            };
+
+            let mut cursor = BufferCursor::new(command.args);
+            args.clear();
+            args.extend(core::iter::from_fn(|| cursor.read_f64()));
+
+            *result = match handler {
+                Some(handler) => handler(args).as_f64(),
+                None => OutputCode::FAILURE.as_f64(),
+            };
+            Ok(())
+        })
+    }
+}
+
+impl fmt::Debug for Batch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Batch")
+            .field("opcodes", &self.handlers.opcodes())
+            .finish()
+    }
+}
+
+type CommandHandler = Box<dyn FnMut(&mut BufferCursor<'_>) -> Result<(), GmError> + Send>;
+
+/// A table of opcode handlers, which can execute a whole frame's worth of commands written by
+/// GML into one buffer, in one call.
+///
+/// GML writes each command as a u32 opcode, a u32 length in bytes, and then whatever arguments
+/// that opcode takes, with `buffer_write`. Each handler gets a [BufferCursor] over just its own
+/// arguments, so a handler which reads too little or too much can't throw off the commands
+/// after it. [GML_HELPERS](CommandBuffer::GML_HELPERS) fills in the lengths.
+/// ```
+/// use gm_ffi::batch::CommandBuffer;
+/// use gm_ffi::GmError;
+/// use once_cell::sync::Lazy;
+/// use std::sync::Mutex;
+///
+/// const OP_MOVE: u32 = 0;
+///
+/// static COMMANDS: Lazy<Mutex<CommandBuffer>> = Lazy::new(|| {
+///     let mut commands = CommandBuffer::new();
+///     commands.register(OP_MOVE, |args| {
+///         let id = args.read_u32().ok_or("missing id")?;
+///         let x = args.read_f32().ok_or("missing x")?;
+///         let y = args.read_f32().ok_or("missing y")?;
+///         # let _ = (id, x, y);
+///         Ok(())
+///     });
+///     Mutex::new(commands)
+/// });
+///
+/// gm_ffi::export_execute_commands!(COMMANDS);
+/// ```
+/// ```gml
+/// var _at = gm_command_begin(global.commands, OP_MOVE);
+/// buffer_write(global.commands, buffer_u32, _id);
+/// buffer_write(global.commands, buffer_f32, x);
+/// buffer_write(global.commands, buffer_f32, y);
+/// gm_command_end(global.commands, _at);
+///
+/// // once, at the end of the step
+/// gm_command_flush(global.commands);
+/// ```
+#[derive(Default)]
+pub struct CommandBuffer {
+    handlers: Handlers<CommandHandler>,
+}
+
+impl CommandBuffer {
+    /// Creates a new command buffer with no handlers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the handler for `opcode`, replacing any existing one.
+    pub fn register(
+        &mut self,
+        opcode: u32,
+        handler: impl FnMut(&mut BufferCursor<'_>) -> Result<(), GmError> + Send + 'static,
+    ) {
+        self.handlers.0.insert(opcode, Box::new(handler));
+    }
+
+    /// Executes every command in `commands`, in order, returning the number executed.
+    ///
+    /// A command with no handler, or whose handler fails, is reported with
+    /// [push_gm_error](crate::warnings::push_gm_error), and the rest still run. A command cut
+    /// off part way is an error, but every command before it has still run.
+    pub fn execute(&mut self, commands: &[u8]) -> Result<usize, BatchError> {
+        self.handlers.walk(commands, 1, |command, handler| {
+            let result = match handler {
+                Some(handler) => handler(&mut BufferCursor::new(command.args)),
+                None => Err(GmError::custom("there's no handler for it")),
+            };
+            if let Err(e) = result {
+                crate::warnings::push_gm_error(format_args!(
+                    "command {} at byte {} failed: {}",
+                    command.opcode, command.offset, e
+                ));
+            }
+            Ok(())
+        })
+    }
+
+    /// GML functions for writing commands, and sending them with the default export name from
+    /// [export_execute_commands](crate::export_execute_commands). Paste these into a script in
+    /// the project.
+    pub const GML_HELPERS: &'static str = r#"/// @param {Id.Buffer} _buffer
+/// @param {Real} _opcode
+function gm_command_begin(_buffer, _opcode) {
+    buffer_write(_buffer, buffer_u32, _opcode);
+    var _at = buffer_tell(_buffer);
+    buffer_write(_buffer, buffer_u32, 0);
+    return _at;
+}
+
+/// @param {Id.Buffer} _buffer
+/// @param {Real} _at what gm_command_begin returned
+function gm_command_end(_buffer, _at) {
+    buffer_poke(_buffer, _at, buffer_u32, buffer_tell(_buffer) - _at - 4);
+}
+
+/// @param {Id.Buffer} _buffer
+function gm_command_flush(_buffer) {
+    var _count = gm_execute_commands(buffer_get_address(_buffer), buffer_tell(_buffer));
+    buffer_seek(_buffer, buffer_seek_start, 0);
+    return _count;
+}
+"#;
+}
+
+impl fmt::Debug for CommandBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandBuffer")
+            .field("opcodes", &self.handlers.opcodes())
+            .finish()
+    }
+}
+
+/// The opcode table behind [Batch] and [CommandBuffer], which also walks their commands.
+struct Handlers<H>(HashMap<u32, H>);
+
+/// One command found by [Handlers::walk].
+struct Command<'a> {
+    opcode: u32,
+    /// The byte offset of the command.
+    offset: usize,
+    /// The number of commands before this one.
+    index: usize,
+    args: &'a [u8],
+}
+
+impl<H> Handlers<H> {
+    fn opcodes(&self) -> Vec<&u32> {
+        self.0.keys().collect()
+    }
+
+    /// Calls `run` with every command in `commands`, and its handler, if it has one. Each
+    /// command is a u32 opcode, a u32 count of `unit` byte arguments, and then the arguments.
+    /// Returns the number of commands run.
+    fn walk(
+        &mut self,
+        commands: &[u8],
+        unit: usize,
+        mut run: impl FnMut(Command<'_>, Option<&mut H>) -> Result<(), BatchError>,
+    ) -> Result<usize, BatchError> {
+        let mut cursor = BufferCursor::new(commands);
+        let mut executed = 0;
+
+        while cursor.remaining() > 0 {
+            let offset = cursor.position();
+            let truncated = BatchError::Truncated { offset, executed };
+
+            let opcode = cursor.read_u32().ok_or(truncated)?;
+            let count = cursor.read_u32().ok_or(truncated)?;
+            let len = (count as usize).checked_mul(unit).ok_or(truncated)?;
+            let args = cursor.read_bytes(len).ok_or(truncated)?;
+
+            let command = Command {
+                opcode,
+                offset,
+                index: executed,
+                args,
+            };
+            run(command, self.0.get_mut(&opcode))?;
+            executed += 1;
+        }
+
+        Ok(executed)
+    }
+}
+
+impl<H> Default for Handlers<H> {
+    fn default() -> Self {
+        Self(HashMap::new())
+    }
+}
+
+/// Generates an export, called `gm_execute_commands` by default, which executes a buffer of
+/// commands with a [CommandBuffer](crate::batch::CommandBuffer), returning how many were
+/// executed. See [Naming exports](crate#naming-exports) for renaming it.
+///
+/// The command buffer must be a `static` `Mutex<CommandBuffer>` (usually inside a `once_cell`
+/// `Lazy`).
+/// In the extension editor, declare the export as taking a buffer address and the number of
+/// bytes written, and returning a real. It returns
+/// [OutputCode::FAILURE](crate::OutputCode::FAILURE), and sets the
+/// [last error](crate::error::last_error), if a command was cut off.
+/// ```
+/// # use gm_ffi::batch::CommandBuffer;
+/// # use once_cell::sync::Lazy;
+/// # use std::sync::Mutex;
+/// static COMMANDS: Lazy<Mutex<CommandBuffer>> = Lazy::new(Default::default);
+/// gm_ffi::export_execute_commands!(COMMANDS => my_extension_execute_commands);
+/// ```
+#[macro_export]
+macro_rules! export_execute_commands {
+    ($commands:path) => {
+        $crate::export_execute_commands!($commands => gm_execute_commands);
+    };
+    ($commands:path => $name:ident) => {
+        /// Executes a buffer of commands.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(ptr: $crate::GmPtr, len: f64) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let commands = unsafe { ptr.to_bytes(len as usize) };
+                let result = $commands
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .execute(commands);
+
+                match result {
+                    Ok(executed) => $crate::OutputCode::custom(executed as f64),
+                    Err(e) => $crate::error::set_last_error(e),
+                }
+            })
+        }
+    };
+}
+
+/// Generates an export, called `run_batch` by default, which runs a buffer of commands with a
+/// [Batch](crate::batch::Batch), writing each command's result into a second buffer, and
+/// returns how many ran. See [Naming exports](crate#naming-exports) for renaming it.
+///
+/// The batch must be a `static` `Mutex<Batch>` (usually inside a `once_cell` `Lazy`). In the
+/// extension editor, declare the export as taking the commands buffer's address and the number
+/// of bytes written, then the results buffer's address and size in bytes, and returning a real.
+/// It returns [OutputCode::FAILURE](crate::OutputCode::FAILURE), and sets the
+/// [last error](crate::error::last_error), if a command was cut off or the results buffer
+/// filled up.
+/// ```
+/// # use gm_ffi::batch::Batch;
+/// # use once_cell::sync::Lazy;
+/// # use std::sync::Mutex;
+/// static BATCH: Lazy<Mutex<Batch>> = Lazy::new(Default::default);
+/// gm_ffi::export_run_batch!(BATCH => my_extension_run_batch);
+///
+/// // one command, opcode 7, with no arguments or handler
+/// let commands = [7, 0, 0, 0, 0, 0, 0, 0];
+/// let mut results = [1.0];
+/// let count = unsafe {
+///     my_extension_run_batch(
+///         gm_ffi::GmPtr::new(commands.as_ptr() as *const _),
+///         8.0,
+///         gm_ffi::GmPtr::new(results.as_mut_ptr() as *const _),
+///         8.0,
+///     )
+/// };
+/// assert_eq!((count.as_f64(), results), (1.0, [0.0]));
+/// ```
+/// ```gml
+/// var _count = run_batch(
+///     buffer_get_address(global.commands), buffer_tell(global.commands),
+///     buffer_get_address(global.results), buffer_get_size(global.results)
+/// );
+/// buffer_seek(global.commands, buffer_seek_start, 0);
+/// ```
+#[macro_export]
+macro_rules! export_run_batch {
+    ($batch:path) => {
+        $crate::export_run_batch!($batch => run_batch);
+    };
+    ($batch:path => $name:ident) => {
+        /// Runs a buffer of commands, writing their results into another buffer.
+        ///
+        /// # Safety
+        /// `commands` must be the address of a GM buffer at least `commands_len` bytes long,
+        /// and `results` the address of a different GM buffer at least `results_len` bytes
+        /// long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(
+            commands: $crate::GmPtr,
+            commands_len: f64,
+            results: $crate::GmPtr,
+            results_len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let commands = unsafe { commands.to_bytes(commands_len as usize) };
+                let results: &mut [f64] = match results_len as usize / 8 {
+                    0 => &mut [],
+                    len => unsafe { ::core::slice::from_raw_parts_mut(results.inner() as *mut f64, len) },
+                };
+                let result = $batch
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .run(commands, results);
+
+                match result {
+                    Ok(executed) => $crate::OutputCode::custom(executed as f64),
+                    Err(e) => $crate::error::set_last_error(e),
+                }
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(out: &mut Vec<u8>, opcode: u32, args: &[f64]) {
+        out.extend(opcode.to_le_bytes());
+        out.extend((args.len() as u32).to_le_bytes());
+        for a in args {
+            out.extend(a.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut batch = Batch::new();
+        batch.register(1, |args| OutputCode::custom(args.iter().sum()));
+
+        let mut commands = vec![];
+        command(&mut commands, 1, &[2.0, 3.0]);
+        command(&mut commands, 7, &[]);
+        command(&mut commands, 1, &[]);
+
+        let mut results = [0.0; 3];
+        assert_eq!(batch.run(&commands, &mut results), Ok(3));
+        assert_eq!(results, [5.0, 0.0, 0.0]);
+
+        assert_eq!(
+            batch.run(&commands, &mut results[..1]),
+            Err(BatchError::ResultsFull { executed: 1 })
+        );
+        assert_eq!(
+            batch.run(&commands[..10], &mut results),
+            Err(BatchError::Truncated {
+                offset: 0,
+                executed: 0
+            })
+        );
+    }
+
+    #[test]
+    fn executes_commands() {
+        let total = std::sync::Arc::new(std::sync::Mutex::new(0.0));
+        let mut commands = CommandBuffer::new();
+        let sum = total.clone();
+        commands.register(3, move |args| {
+            let a = args.read_f32().ok_or("missing a")?;
+            let b = args.read_u8().ok_or("missing b")?;
+            *sum.lock().unwrap() += a as f64 + b as f64;
+            Ok(())
+        });
+
+        let mut buffer = vec![];
+        for args in [&[0, 0, 0x80, 0x3f, 2][..], &[0, 0, 0x80, 0x3f, 2, 9]] {
+            buffer.extend(3u32.to_le_bytes());
+            buffer.extend((args.len() as u32).to_le_bytes());
+            buffer.extend(args);
+        }
+
+        assert_eq!(commands.execute(&buffer), Ok(2));
+        assert_eq!(*total.lock().unwrap(), 6.0);
+        assert_eq!(
+            commands.execute(&buffer[..12]),
+            Err(BatchError::Truncated {
+                offset: 0,
+                executed: 0
+            })
+        );
+    }
+
+    #[test]
+    fn exports_catch_handler_panics() {
+        static BATCH: once_cell::sync::Lazy<std::sync::Mutex<Batch>> =
+            once_cell::sync::Lazy::new(|| {
+                let mut batch = Batch::new();
+                batch.register(1, |_| panic!("handler panicked"));
+                std::sync::Mutex::new(batch)
+            });
+        crate::export_run_batch!(BATCH => test_run_batch_panics);
+
+        let mut commands = vec![];
+        command(&mut commands, 1, &[]);
+        let mut results = [0.0];
+        let code = unsafe {
+            test_run_batch_panics(
+                crate::GmPtr::new(commands.as_ptr() as *const _),
+                commands.len() as f64,
+                crate::GmPtr::new(results.as_mut_ptr() as *const _),
+                8.0,
+            )
+        };
+
+        assert!(code.is_failure());
+        assert_eq!(
+            crate::error::last_error().as_deref(),
+            Some("panicked: handler panicked")
+        );
+    }
+}
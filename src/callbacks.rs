@@ -0,0 +1,308 @@
+//! GML callbacks which Rust can invoke, so GML can react to things when they happen instead of
+//! polling flags.
+//!
+//! GML registers a function under a name, Rust queues invocations of that name with
+//! [invoke], and once a step GML calls the dispatch export, which runs everything queued:
+//! ```
+//! # use gm_ffi::{callbacks, async_event::AsyncValue};
+//! gm_ffi::export_callbacks!();
+//!
+//! // from anywhere, on any thread
+//! callbacks::invoke("on_save_loaded", [AsyncValue::from("slot_1"), AsyncValue::from(3)]);
+//! ```
+//! ```gml
+//! gm_callback_register("on_save_loaded", function(_slot, _level) {
+//!     show_debug_message(_slot + " is on level " + string(_level));
+//! });
+//!
+//! // in a step event
+//! gm_callback_dispatch(global.callback_buffer);
+//! ```
+//! [GML_HELPERS] has `gm_callback_register` and `gm_callback_dispatch`.
+
+use crate::async_event::AsyncValue;
+use crate::{Bridge, BridgeWriter};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// The most invocations kept at once. Past this, the oldest are dropped.
+pub const MAX_QUEUED: usize = 1024;
+
+struct Registry {
+    names: Vec<String>,
+    queue: VecDeque<(String, Vec<AsyncValue>)>,
+}
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    names: Vec::new(),
+    queue: VecDeque::new(),
+});
+
+/// Registers a callback name, returning its id. Registering the same name again returns the
+/// same id. This is what the export generated by [export_callbacks](crate::export_callbacks)
+/// calls.
+pub fn register(name: &str) -> u32 {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let id = match registry.names.iter().position(|n| n == name) {
+        Some(id) => id,
+        None => {
+            registry.names.push(name.to_string());
+            registry.names.len() - 1
+        }
+    };
+
+    id as u32
+}
+
+/// Returns if a callback has been registered under `name`.
+pub fn is_registered(name: &str) -> bool {
+    let registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.names.iter().any(|n| n == name)
+}
+
+/// Queues a call to the callback registered as `name`, which runs the next time GML
+/// dispatches. This can be called from any thread.
+///
+/// The callback doesn't need to be registered yet, but if it still isn't when GML dispatches,
+/// the call is dropped with a [warning](crate::warnings::push_gm_warning).
+pub fn invoke(name: &str, args: impl IntoIterator<Item = AsyncValue>) {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    if registry.queue.len() == MAX_QUEUED {
+        registry.queue.pop_front();
+    }
+    registry
+        .queue
+        .push_back((name.to_string(), args.into_iter().collect()));
+}
+
+/// The number of calls waiting to be dispatched.
+pub fn queued() -> usize {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .queue
+        .len()
+}
+
+/// Writes as many queued calls as fit into `bridge`, oldest first, and returns how many were
+/// written. Anything which doesn't fit stays queued for next time.
+///
+/// The layout is a u32 count, then for each call a u32 callback id and a u32 argument count.
+/// Each argument is a u32 tag: 0 for a real, followed by an f64, or 1 for a string, followed
+/// by a u32 length in bytes and the string, NUL-terminated and padded to a multiple of 4 bytes.
+pub fn dispatch_into(bridge: &mut Bridge) -> usize {
+    let mut writer = bridge.writer();
+    // reserve the count
+    writer.write_u32(0);
+
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let mut count = 0;
+    while let Some((name, args)) = registry.queue.front() {
+        let Some(id) = registry.names.iter().position(|n| n == name) else {
+            crate::warnings::push_gm_warning(format_args!(
+                "dropped a call to `{}`, which isn't a registered callback",
+                name
+            ));
+            registry.queue.pop_front();
+            continue;
+        };
+
+        // the id and argument count, then the arguments
+        let units = 2 + args.iter().map(units_for).sum::<usize>();
+        if writer.remaining() < units {
+            if count == 0 {
+                // a call too big for an empty bridge would block the queue forever
+                crate::warnings::push_gm_warning(format_args!(
+                    "dropped a call to `{}`, which was too big for the bridge",
+                    name
+                ));
+                registry.queue.pop_front();
+                continue;
+            }
+            break;
+        }
+
+        writer.write_u32(id as u32);
+        writer.write_u32(args.len() as u32);
+        for arg in args {
+            match arg {
+                AsyncValue::Real(value) => {
+                    writer.write_u32(0);
+                    writer.write_f64(*value);
+                }
+                AsyncValue::String(value) => {
+                    writer.write_u32(1);
+                    writer.write_u32(value.len() as u32);
+                    writer.write_padded_str(value);
+                }
+            }
+        }
+
+        registry.queue.pop_front();
+        count += 1;
+    }
+    drop(registry);
+
+    bridge.writer().write_u32(count as u32);
+
+    count
+}
+
+// the tag, then the value, with a length before strings
+fn units_for(arg: &AsyncValue) -> usize {
+    match arg {
+        AsyncValue::Real(_) => 1 + 2,
+        AsyncValue::String(value) => 1 + 1 + BridgeWriter::padded_str_len(value),
+    }
+}
+
+/// GML functions for registering and dispatching callbacks, using the default export names
+/// from [export_callbacks](crate::export_callbacks). Paste these into a script in the project.
+pub const GML_HELPERS: &str = r#"/// @param {String} _name
+/// @param {Function} _callback
+function gm_callback_register(_name, _callback) {
+    if (!variable_global_exists("__gm_callbacks")) {
+        global.__gm_callbacks = [];
+    }
+    global.__gm_callbacks[gm_register_callback(_name)] = _callback;
+}
+
+/// @param {Id.Buffer} _buffer
+function gm_callback_dispatch(_buffer) {
+    var _count = gm_dispatch_callbacks(_buffer, buffer_get_address(_buffer), buffer_get_size(_buffer));
+    buffer_seek(_buffer, buffer_seek_start, 4);
+    repeat (_count) {
+        var _id = buffer_read(_buffer, buffer_u32);
+        var _argc = buffer_read(_buffer, buffer_u32);
+        var _args = array_create(_argc);
+        for (var _i = 0; _i < _argc; _i++) {
+            if (buffer_read(_buffer, buffer_u32) == 0) {
+                _args[_i] = buffer_read(_buffer, buffer_f64);
+            } else {
+                var _len = buffer_read(_buffer, buffer_u32);
+                var _start = buffer_tell(_buffer);
+                _args[_i] = buffer_read(_buffer, buffer_string);
+                buffer_seek(_buffer, buffer_seek_start, _start + (_len div 4 + 1) * 4);
+            }
+        }
+        script_execute_ext(global.__gm_callbacks[_id], _args);
+    }
+    return _count;
+}
+"#;
+
+/// Generates the exports for [callbacks](crate::callbacks): one which registers a name and
+/// returns its id, called `gm_register_callback` by default, and one which dispatches queued
+/// calls into a GM buffer, called `gm_dispatch_callbacks` by default. See
+/// [Naming exports](crate#naming-exports) for renaming them, and rename them in [GML_HELPERS] to match.
+///
+/// In the extension editor, declare the first as taking a string and returning a real, and the
+/// second as taking a buffer id, a buffer address, and the buffer's size in bytes. The buffer
+/// must be at least 1KiB.
+/// ```
+/// gm_ffi::export_callbacks!(my_extension_register_callback, my_extension_dispatch_callbacks);
+/// ```
+#[macro_export]
+macro_rules! export_callbacks {
+    () => {
+        $crate::export_callbacks!(gm_register_callback, gm_dispatch_callbacks);
+    };
+    ($register:ident, $dispatch:ident) => {
+        /// Registers a callback name, returning its id.
+        #[no_mangle]
+        pub extern "C" fn $register(name: $crate::GmPtr) -> f64 {
+            $crate::catch_ffi!(-1.0 => {
+                $crate::callbacks::register(&name.to_str_lossy()) as f64
+            })
+        }
+
+        /// Dispatches queued callback calls into a GM buffer.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $dispatch(
+            id: $crate::GmId,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let buffer = unsafe { $crate::GmBuffer::new(id, ptr, len as usize / 4) };
+                let mut bridge = match $crate::Bridge::try_new(buffer) {
+                    Ok(bridge) => bridge,
+                    Err(e) => return e.into(),
+                };
+                let count = $crate::callbacks::dispatch_into(&mut bridge);
+
+                $crate::OutputCode::custom(count as f64)
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_bridge;
+
+    #[test]
+    fn dispatches_registered_calls() {
+        let _lock = crate::lock_queues();
+        let (backing, mut bridge) = test_bridge();
+
+        let id = register("test_loaded");
+        assert_eq!(register("test_loaded"), id);
+        assert!(is_registered("test_loaded"));
+
+        invoke("test_loaded", [AsyncValue::from(2), AsyncValue::from("ab")]);
+        assert_eq!(dispatch_into(&mut bridge), 1);
+        assert_eq!(
+            backing[..9],
+            [
+                1,
+                id,
+                2,
+                0,
+                0,
+                0x4000_0000,
+                1,
+                2,
+                u32::from_le_bytes(*b"ab\0\0")
+            ]
+        );
+        assert_eq!(queued(), 0);
+    }
+
+    #[test]
+    fn drops_unregistered_calls() {
+        let _lock = crate::lock_queues();
+        let (_backing, mut bridge) = test_bridge();
+        let warnings = crate::warnings::queued();
+
+        invoke("test_unregistered", [AsyncValue::from(1)]);
+        assert_eq!(dispatch_into(&mut bridge), 0);
+        assert_eq!(queued(), 0);
+        assert_eq!(crate::warnings::queued(), warnings + 1);
+
+        crate::warnings::drain_into(&mut bridge);
+    }
+
+    #[test]
+    fn drops_calls_too_big_for_the_bridge() {
+        let _lock = crate::lock_queues();
+        let (_backing, mut bridge) = test_bridge();
+        let warnings = crate::warnings::queued();
+
+        // the count, id, argument count, tag, and length leave 251 u32s, or 1003 bytes and a NUL
+        register("test_big");
+        invoke("test_big", [AsyncValue::from("x".repeat(1003))]);
+        invoke("test_big", [AsyncValue::from("x".repeat(1004))]);
+        assert_eq!(dispatch_into(&mut bridge), 1);
+        assert_eq!(queued(), 1);
+        assert_eq!(dispatch_into(&mut bridge), 0);
+        assert_eq!(queued(), 0);
+        assert_eq!(crate::warnings::queued(), warnings + 1);
+
+        crate::warnings::drain_into(&mut bridge);
+    }
+}
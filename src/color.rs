@@ -0,0 +1,119 @@
+//! Colors, and converting them to and from GameMaker's BGR-packed reals.
+
+/// An 8-bit-per-channel color with alpha.
+///
+/// GameMaker passes colors around as reals packed as `0xBBGGRR` (so `c_red` is `255`),
+/// with alpha kept separately as a `0.0..=1.0` real. Use [from_gm](GmColor::from_gm) and
+/// [to_gm](GmColor::to_gm) to cross that boundary, rather than shifting bits by hand.
+///
+/// In memory, this is laid out as RGBA bytes, so it can be used directly as the element
+/// type of a `GmBuffer` of RGBA pixels.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GmColor {
+    /// Red.
+    pub r: u8,
+    /// Green.
+    pub g: u8,
+    /// Blue.
+    pub b: u8,
+    /// Alpha. 255 is opaque.
+    pub a: u8,
+}
+
+impl GmColor {
+    /// Opaque black.
+    pub const BLACK: GmColor = GmColor::rgb(0, 0, 0);
+    /// Opaque white.
+    pub const WHITE: GmColor = GmColor::rgb(255, 255, 255);
+    /// Fully transparent black.
+    pub const TRANSPARENT: GmColor = GmColor::rgba(0, 0, 0, 0);
+
+    /// Creates an opaque color.
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    /// Creates a color with alpha.
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Converts from a GM color real, like `c_red` or the output of `make_color_rgb`. The
+    /// result is opaque.
+    pub fn from_gm(color: f64) -> Self {
+        let [r, g, b, _] = (color as u32).to_le_bytes();
+
+        Self::rgb(r, g, b)
+    }
+
+    /// Converts from a GM color real and a GM alpha in `0.0..=1.0`.
+    pub fn from_gm_with_alpha(color: f64, alpha: f64) -> Self {
+        Self::from_gm(color).with_alpha(alpha)
+    }
+
+    /// Converts into a GM color real. Alpha is dropped, since GM keeps it separately;
+    /// see [alpha_f64](GmColor::alpha_f64).
+    pub fn to_gm(self) -> f64 {
+        u32::from_le_bytes([self.r, self.g, self.b, 0]) as f64
+    }
+
+    /// Returns this color with its alpha set from a GM alpha in `0.0..=1.0`.
+    pub fn with_alpha(self, alpha: f64) -> Self {
+        Self {
+            a: (alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+            ..self
+        }
+    }
+
+    /// Returns the alpha as a GM alpha, in `0.0..=1.0`.
+    pub fn alpha_f64(self) -> f64 {
+        self.a as f64 / 255.0
+    }
+
+    /// Creates a color from RGBA bytes.
+    pub const fn from_rgba8(bytes: [u8; 4]) -> Self {
+        Self::rgba(bytes[0], bytes[1], bytes[2], bytes[3])
+    }
+
+    /// Returns the color as RGBA bytes.
+    pub const fn to_rgba8(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// Creates a color from BGRA bytes.
+    pub const fn from_bgra8(bytes: [u8; 4]) -> Self {
+        Self::rgba(bytes[2], bytes[1], bytes[0], bytes[3])
+    }
+
+    /// Returns the color as BGRA bytes.
+    pub const fn to_bgra8(self) -> [u8; 4] {
+        [self.b, self.g, self.r, self.a]
+    }
+
+    /// Returns this color with its RGB channels multiplied by its alpha.
+    pub fn premultiplied(self) -> Self {
+        let mul = |c: u8| ((c as u16 * self.a as u16 + 127) / 255) as u8;
+
+        Self::rgba(mul(self.r), mul(self.g), mul(self.b), self.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gm_conversions() {
+        // c_orange is make_color_rgb(255, 160, 64)
+        let orange = GmColor::from_gm(4235519.0);
+        assert_eq!(orange, GmColor::rgb(255, 160, 64));
+        assert_eq!(orange.to_gm(), 4235519.0);
+
+        let faded = GmColor::from_gm_with_alpha(255.0, 0.5);
+        assert_eq!(faded.to_rgba8(), [255, 0, 0, 128]);
+        assert_eq!(faded.to_bgra8(), [0, 0, 255, 128]);
+        assert_eq!(faded.premultiplied(), GmColor::rgba(128, 0, 0, 128));
+    }
+}
@@ -0,0 +1,156 @@
+//! A read cursor over raw bytes, usually the contents of a `GmBuffer<u8>`.
+
+/// The byte order used to read multi-byte values.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Endian {
+    /// Least significant byte first. This is what GameMaker's `buffer_read` and
+    /// `buffer_write` use, so it's the default.
+    #[default]
+    Little,
+    /// Most significant byte first. Network byte order.
+    Big,
+}
+
+impl Endian {
+    /// The byte order of the machine we're running on.
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Endian = Endian::Little;
+
+    /// The byte order of the machine we're running on.
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Endian = Endian::Big;
+}
+
+/// A cursor which reads typed values out of a byte slice, advancing as it goes.
+///
+/// Every `read_*` method uses the cursor's default [Endian], which you can change
+/// with [set_endian](BufferCursor::set_endian). If you need a specific byte order for
+/// one value, use the `_le` and `_be` variants.
+///
+/// All reads return `None` if there aren't enough bytes left, and don't advance.
+#[derive(Debug, Clone)]
+pub struct BufferCursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    endian: Endian,
+}
+
+macro_rules! read_impls {
+    ($($ty:ty => $read:ident, $read_le:ident, $read_be:ident;)*) => {
+        $(
+            #[doc = concat!("Reads a `", stringify!($ty), "` using the cursor's default endianness.")]
+            pub fn $read(&mut self) -> Option<$ty> {
+                match self.endian {
+                    Endian::Little => self.$read_le(),
+                    Endian::Big => self.$read_be(),
+                }
+            }
+
+            #[doc = concat!("Reads a little endian `", stringify!($ty), "`.")]
+            pub fn $read_le(&mut self) -> Option<$ty> {
+                self.read_array().map(<$ty>::from_le_bytes)
+            }
+
+            #[doc = concat!("Reads a big endian `", stringify!($ty), "`.")]
+            pub fn $read_be(&mut self) -> Option<$ty> {
+                self.read_array().map(<$ty>::from_be_bytes)
+            }
+        )*
+    };
+}
+
+impl<'a> BufferCursor<'a> {
+    /// Creates a new cursor at the start of `bytes`, reading little endian.
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self::with_endian(bytes, Endian::default())
+    }
+
+    /// Creates a new cursor at the start of `bytes` with the given default endianness.
+    pub fn with_endian(bytes: &'a [u8], endian: Endian) -> Self {
+        Self {
+            bytes,
+            position: 0,
+            endian,
+        }
+    }
+
+    /// Returns the default endianness used by the plain `read_*` methods.
+    pub fn endian(&self) -> Endian {
+        self.endian
+    }
+
+    /// Sets the default endianness used by the plain `read_*` methods.
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.endian = endian;
+    }
+
+    /// Returns the cursor's current offset, in bytes.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Moves the cursor to the given offset. This is clamped to the length of the bytes.
+    pub fn set_position(&mut self, position: usize) {
+        self.position = position.min(self.bytes.len());
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    /// Reads `len` bytes as a slice.
+    pub fn read_bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let end = self.position.checked_add(len)?;
+        let out = self.bytes.get(self.position..end)?;
+        self.position = end;
+
+        Some(out)
+    }
+
+    /// Reads a single `u8`. Byte order doesn't matter here.
+    pub fn read_u8(&mut self) -> Option<u8> {
+        self.read_array().map(u8::from_le_bytes)
+    }
+
+    /// Reads a single `i8`. Byte order doesn't matter here.
+    pub fn read_i8(&mut self) -> Option<i8> {
+        self.read_array().map(i8::from_le_bytes)
+    }
+
+    read_impls! {
+        u16 => read_u16, read_u16_le, read_u16_be;
+        i16 => read_i16, read_i16_le, read_i16_be;
+        u32 => read_u32, read_u32_le, read_u32_be;
+        i32 => read_i32, read_i32_le, read_i32_be;
+        u64 => read_u64, read_u64_le, read_u64_be;
+        i64 => read_i64, read_i64_le, read_i64_be;
+        f32 => read_f32, read_f32_le, read_f32_be;
+        f64 => read_f64, read_f64_le, read_f64_be;
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        self.read_bytes(N).map(|b| b.try_into().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endianness() {
+        let bytes = [0x01, 0x02, 0x01, 0x02, 0xFF];
+        let mut cursor = BufferCursor::new(&bytes);
+
+        assert_eq!(cursor.read_u16(), Some(0x0201));
+        assert_eq!(cursor.read_u16_be(), Some(0x0102));
+        assert_eq!(cursor.read_u16(), None);
+        assert_eq!(cursor.read_u8(), Some(0xFF));
+
+        cursor.set_position(0);
+        cursor.set_endian(Endian::Big);
+        assert_eq!(cursor.read_u32(), Some(0x01020102));
+        assert_eq!(cursor.remaining(), 1);
+    }
+}
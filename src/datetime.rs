@@ -0,0 +1,102 @@
+//! GM datetimes, and converting them to and from [SystemTime].
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A GM datetime: a real counting days (with the time of day as the fraction) since
+/// 1899-12-30, the same epoch OLE automation dates use.
+///
+/// GM interprets datetimes in whatever timezone was set with `date_set_timezone`. The
+/// [SystemTime] conversions here treat the value as UTC, so call `date_set_timezone(timezone_utc)`
+/// in GML if values are going to cross the boundary.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmDateTime(pub f64);
+
+impl GmDateTime {
+    /// The number of days between GM's epoch (1899-12-30) and the Unix epoch (1970-01-01).
+    pub const UNIX_EPOCH_DAYS: f64 = 25_569.0;
+
+    /// The Unix epoch, as a GM datetime.
+    pub const UNIX_EPOCH: GmDateTime = GmDateTime(Self::UNIX_EPOCH_DAYS);
+
+    const SECONDS_PER_DAY: f64 = 86_400.0;
+
+    /// Creates a new datetime from a raw GM real.
+    pub const fn new(days: f64) -> Self {
+        Self(days)
+    }
+
+    /// Returns the current time.
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+
+    /// Converts from seconds since the Unix epoch.
+    pub fn from_unix_seconds(seconds: f64) -> Self {
+        Self(Self::UNIX_EPOCH_DAYS + seconds / Self::SECONDS_PER_DAY)
+    }
+
+    /// Converts to seconds since the Unix epoch. This is negative for dates before 1970.
+    pub fn to_unix_seconds(self) -> f64 {
+        (self.0 - Self::UNIX_EPOCH_DAYS) * Self::SECONDS_PER_DAY
+    }
+
+    /// Converts to a [SystemTime]. Returns `None` if the value isn't finite or can't be
+    /// represented on this platform.
+    pub fn to_system_time(self) -> Option<SystemTime> {
+        let seconds = self.to_unix_seconds();
+        if !seconds.is_finite() {
+            return None;
+        }
+
+        let offset = Duration::try_from_secs_f64(seconds.abs()).ok()?;
+        if seconds >= 0.0 {
+            UNIX_EPOCH.checked_add(offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset)
+        }
+    }
+
+    /// Returns the inner f64.
+    pub const fn inner(self) -> f64 {
+        self.0
+    }
+}
+
+impl From<SystemTime> for GmDateTime {
+    fn from(o: SystemTime) -> Self {
+        let seconds = match o.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_secs_f64(),
+            Err(before) => -before.duration().as_secs_f64(),
+        };
+
+        Self::from_unix_seconds(seconds)
+    }
+}
+
+impl From<GmDateTime> for f64 {
+    fn from(o: GmDateTime) -> Self {
+        o.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips() {
+        assert_eq!(GmDateTime::from(UNIX_EPOCH), GmDateTime::UNIX_EPOCH);
+        assert_eq!(GmDateTime::new(0.0).to_unix_seconds(), -2_209_161_600.0);
+
+        // 2000-01-01 12:00 UTC
+        let noon = UNIX_EPOCH + Duration::from_secs(946_728_000);
+        let gm = GmDateTime::from(noon);
+        assert_eq!(gm.0, 36_526.5);
+        assert_eq!(gm.to_system_time(), Some(noon));
+
+        assert!(GmDateTime::new(f64::NAN).to_system_time().is_none());
+    }
+}
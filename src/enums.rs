@@ -0,0 +1,139 @@
+//! Enums which cross the boundary as reals, and keeping GML's constants for them in sync.
+
+use core::fmt;
+
+/// Derives conversions for a fieldless enum whose variants map to consecutive reals, starting
+/// from 0: `TryFrom<f64>` and `TryFrom<GmReal>`, failing with [InvalidEnumValue], and
+/// `From<Self>` for f64 and [GmReal](crate::GmReal). Variants can't set their own
+/// discriminants.
+///
+/// The enum also gets a `VARIANTS` constant, listing every variant in order, and a
+/// `gml_macros()` function, which returns a GML `#macro` block with a constant for each
+/// variant, named `TYPE_VARIANT`. Paste it into (or generate) a script in the project, and the
+/// two sides can't drift apart.
+/// ```
+/// use gm_ffi::enums::GmEnum;
+///
+/// /// The weather in a zone.
+/// #[derive(Debug, Clone, Copy, PartialEq, GmEnum)]
+/// pub enum Weather {
+///     Clear,
+///     HeavyRain,
+///     Snow,
+/// }
+///
+/// assert_eq!(Weather::try_from(1.0), Ok(Weather::HeavyRain));
+/// assert!(Weather::try_from(3.0).is_err());
+/// assert_eq!(f64::from(Weather::Snow), 2.0);
+///
+/// assert!(Weather::gml_macros().contains("#macro WEATHER_HEAVY_RAIN 1"));
+/// ```
+///
+/// The generated code names this crate as `gm_ffi`, so it can't be renamed in `Cargo.toml`.
+pub use gm_ffi_derive::GmEnum;
+
+/// Declares an enum which derives [GmEnum](macro@crate::enums::GmEnum), along with `Clone`
+/// and `Copy`, for code which would rather not name the derive.
+/// ```
+/// gm_ffi::gm_enum! {
+///     #[derive(Debug, PartialEq)]
+///     pub enum Weather {
+///         Clear,
+///         HeavyRain,
+///     }
+/// }
+///
+/// assert_eq!(Weather::try_from(1.0), Ok(Weather::HeavyRain));
+/// ```
+#[macro_export]
+macro_rules! gm_enum {
+    (
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($(#[$vmeta:meta])* $variant:ident),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, $crate::enums::GmEnum)]
+        $vis enum $name {
+            $($(#[$vmeta])* $variant),*
+        }
+    };
+}
+
+/// The error returned when a real doesn't match any variant of a [GmEnum](macro@GmEnum).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidEnumValue {
+    /// The name of the enum.
+    pub name: &'static str,
+    /// The value which didn't match.
+    pub value: f64,
+}
+
+impl fmt::Display for InvalidEnumValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not a valid {}", self.value, self.name)
+    }
+}
+
+impl std::error::Error for InvalidEnumValue {}
+
+/// Builds the name of a GML constant, like `WEATHER_HEAVY_RAIN` from `Weather` and `HeavyRain`.
+#[doc(hidden)]
+pub fn gml_constant_name(type_name: &str, item: &str) -> String {
+    let mut output = String::with_capacity(type_name.len() + item.len() + 4);
+
+    for part in [type_name, item] {
+        if !output.is_empty() {
+            output.push('_');
+        }
+
+        let mut prev_lower = false;
+        for c in part.chars() {
+            if c == '_' {
+                output.push('_');
+                prev_lower = false;
+                continue;
+            }
+
+            if c.is_uppercase() && prev_lower {
+                output.push('_');
+            }
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+            output.extend(c.to_uppercase());
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, GmEnum)]
+    enum Facing {
+        Left,
+        Right,
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(Facing::try_from(crate::GmReal(1.0)), Ok(Facing::Right));
+        assert!(Facing::try_from(0.5).is_err());
+        assert!(Facing::try_from(-1.0).is_err());
+        assert_eq!(
+            Facing::gml_macros(),
+            "#macro FACING_LEFT 0\n#macro FACING_RIGHT 1\n"
+        );
+
+        assert_eq!(
+            gml_constant_name("HttpState", "Http2Ready"),
+            "HTTP_STATE_HTTP2_READY"
+        );
+        assert_eq!(
+            gml_constant_name("snake_case", "already_OK"),
+            "SNAKE_CASE_ALREADY_OK"
+        );
+    }
+}
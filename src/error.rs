@@ -0,0 +1,354 @@
+//! Errors, and a "last error" slot so GML can find out why a call failed.
+//!
+//! An export can only return a number, so when something goes wrong it returns
+//! [OutputCode::FAILURE] and stashes the reason with [set_last_error]. GML then calls the
+//! export generated by [export_last_error](crate::export_last_error) to read it:
+//! ```
+//! # use gm_ffi::{error::{set_last_error, GmError}, GmPtr, OutputCode};
+//! gm_ffi::export_last_error!();
+//!
+//! #[no_mangle]
+//! pub extern "C" fn load_level(name: GmPtr) -> OutputCode {
+//!     let name = match name.try_to_str() {
+//!         Ok(name) => name,
+//!         Err(e) => return set_last_error(GmError::from(e)),
+//!     };
+//!
+//!     # let _ = name;
+//!     OutputCode::SUCCESS
+//! }
+//! ```
+//! ```gml
+//! if (!load_level(_name)) {
+//!     show_debug_message("couldn't load level: " + gm_last_error());
+//! }
+//! ```
+
+use crate::{GmStrError, GmString, OutputCode};
+use core::ffi::c_char;
+use core::fmt;
+use std::cell::RefCell;
+
+/// The things that commonly go wrong in an export.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GmError {
+    /// A string from GM wasn't valid UTF-8.
+    Utf8(core::str::Utf8Error),
+    /// A pointer from GM was null.
+    NullPointer,
+    /// An index was past the end of a buffer.
+    OutOfBounds {
+        /// The index asked for.
+        index: usize,
+        /// The length of the buffer.
+        len: usize,
+    },
+    /// Anything else.
+    Custom(String),
+}
+
+impl GmError {
+    /// Creates a [GmError::Custom].
+    pub fn custom(message: impl fmt::Display) -> Self {
+        Self::Custom(message.to_string())
+    }
+}
+
+impl fmt::Display for GmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GmError::Utf8(e) => write!(f, "string wasn't valid utf-8: {}", e),
+            GmError::NullPointer => f.write_str("pointer was null (was `undefined` passed?)"),
+            GmError::OutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds for length {}", index, len)
+            }
+            GmError::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for GmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GmError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<GmStrError> for GmError {
+    fn from(o: GmStrError) -> Self {
+        match o {
+            GmStrError::Null => GmError::NullPointer,
+            GmStrError::Utf8(e) => GmError::Utf8(e),
+            other => GmError::Custom(other.to_string()),
+        }
+    }
+}
+
+impl From<core::str::Utf8Error> for GmError {
+    fn from(o: core::str::Utf8Error) -> Self {
+        GmError::Utf8(o)
+    }
+}
+
+impl From<String> for GmError {
+    fn from(o: String) -> Self {
+        GmError::Custom(o)
+    }
+}
+
+impl From<&str> for GmError {
+    fn from(o: &str) -> Self {
+        GmError::Custom(o.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+    static LAST_ERROR_STR: RefCell<GmString> = const { RefCell::new(GmString::new()) };
+}
+
+/// Records `error` as the last error on this thread, and returns [OutputCode::FAILURE], so
+/// it can be returned straight from an export.
+pub fn set_last_error(error: impl fmt::Display) -> OutputCode {
+    let message = error.to_string();
+    LAST_ERROR.with_borrow_mut(|e| *e = Some(message));
+
+    OutputCode::FAILURE
+}
+
+/// Returns the last error recorded on this thread, if any.
+pub fn last_error() -> Option<String> {
+    LAST_ERROR.with_borrow(|e| e.clone())
+}
+
+/// Takes the last error recorded on this thread, leaving nothing behind.
+pub fn take_last_error() -> Option<String> {
+    LAST_ERROR.with_borrow_mut(|e| e.take())
+}
+
+/// Clears the last error on this thread.
+pub fn clear_last_error() {
+    LAST_ERROR.with_borrow_mut(|e| *e = None);
+}
+
+/// Returns the last error recorded on this thread as a string for GM, or an empty string if
+/// there isn't one. This is what [export_last_error](crate::export_last_error) calls.
+pub fn last_error_ptr() -> *const c_char {
+    let message = last_error().unwrap_or_default();
+    LAST_ERROR_STR.with_borrow_mut(|s| s.set(&message))
+}
+
+/// Generates an export returning the last error recorded with [set_last_error](crate::error::set_last_error),
+/// as a string, called `gm_last_error` by default. See [Naming exports](crate#naming-exports) for renaming it.
+///
+/// In the extension editor, declare it as taking no arguments and returning a string.
+/// ```
+/// gm_ffi::export_last_error!(my_extension_last_error);
+/// ```
+#[macro_export]
+macro_rules! export_last_error {
+    () => {
+        $crate::export_last_error!(gm_last_error);
+    };
+    ($name:ident) => {
+        /// Returns the last error recorded on this thread, or an empty string.
+        #[no_mangle]
+        pub extern "C" fn $name() -> *const ::core::ffi::c_char {
+            $crate::error::last_error_ptr()
+        }
+    };
+}
+
+/// Runs `f`, catching any panic so it can't unwind across the FFI boundary into GM, which is
+/// undefined behavior. If `f` panics, the panic message is recorded with [set_last_error] and
+/// `fallback` is returned instead.
+///
+/// This is what [catch_ffi](crate::catch_ffi) expands to.
+pub fn catch_unwind_or<R>(fallback: R, f: impl FnOnce() -> R) -> R {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(output) => output,
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("unknown panic payload");
+            set_last_error(format_args!("panicked: {}", message));
+
+            fallback
+        }
+    }
+}
+
+/// Wraps the body of an export so a panic inside it returns [OutputCode::FAILURE] (and sets the
+/// [last error](crate::error::last_error)) instead of unwinding into GM.
+/// The exports generated by this crate's `export_*!` macros are already wrapped, including
+/// any handlers or callbacks they run.
+///
+/// For exports which don't return an [OutputCode], give the value to return on a panic first.
+/// ```
+/// # use gm_ffi::OutputCode;
+/// #[no_mangle]
+/// pub extern "C" fn divide(a: f64, b: f64) -> OutputCode {
+///     gm_ffi::catch_ffi!({
+///         assert!(b != 0.0, "divided by zero");
+///         OutputCode::custom(a / b)
+///     })
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn checked_len(len: f64) -> f64 {
+///     gm_ffi::catch_ffi!(-1.0 => {
+///         usize::try_from(len as i64).unwrap() as f64
+///     })
+/// }
+///
+/// assert!(divide(1.0, 0.0).is_failure());
+/// assert_eq!(checked_len(-3.0), -1.0);
+/// ```
+///
+/// The panic hook still runs first, so with a hook which exits the process, like the macOS
+/// `setup_panic_hook`, this never gets the chance to return. Install a
+/// [PanicHook](crate::panic::PanicHook) with [PanicPolicy::Continue](crate::panic::PanicPolicy::Continue)
+/// to keep the game running instead.
+#[macro_export]
+macro_rules! catch_ffi {
+    ($fallback:expr => $body:block) => {
+        $crate::error::catch_unwind_or($fallback, || $body)
+    };
+    ($body:block) => {
+        $crate::error::catch_unwind_or($crate::OutputCode::FAILURE, || $body)
+    };
+}
+
+/// Reports a failed [gm_assert](crate::gm_assert), printing it with
+/// [gm_println](crate::gm_println) and recording it with [set_last_error].
+#[doc(hidden)]
+pub fn assert_failed(condition: &str, file: &str, line: u32, message: Option<fmt::Arguments<'_>>) {
+    let message = match message {
+        Some(message) => format!(
+            "assertion failed: {} ({}) at {}:{}",
+            condition, message, file, line
+        ),
+        None => format!("assertion failed: {} at {}:{}", condition, file, line),
+    };
+
+    crate::gm_println!("{}", message);
+    set_last_error(message);
+}
+
+/// Like `assert`, but instead of panicking, which takes the whole game down, it prints the
+/// failure with [gm_println](crate::gm_println), records it as the
+/// [last error](crate::error::last_error), and returns [OutputCode::FAILURE] from the export.
+///
+/// Like [catch_ffi](crate::catch_ffi), exports which don't return an [OutputCode] give the value
+/// to return first.
+/// ```
+/// # use gm_ffi::OutputCode;
+/// #[no_mangle]
+/// pub extern "C" fn set_volume(volume: f64) -> OutputCode {
+///     gm_ffi::gm_assert!((0.0..=1.0).contains(&volume), "volume was {}", volume);
+///     OutputCode::SUCCESS
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn inverse(x: f64) -> f64 {
+///     gm_ffi::gm_assert!(f64::NAN => x != 0.0);
+///     1.0 / x
+/// }
+///
+/// assert!(set_volume(3.0).is_failure());
+/// assert!(inverse(0.0).is_nan());
+/// ```
+#[macro_export]
+macro_rules! gm_assert {
+    ($fallback:expr => $cond:expr $(,)?) => {
+        if !$cond {
+            $crate::error::assert_failed(
+                ::core::stringify!($cond),
+                ::core::file!(),
+                ::core::line!(),
+                ::core::option::Option::None,
+            );
+            return $fallback;
+        }
+    };
+    ($fallback:expr => $cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::error::assert_failed(
+                ::core::stringify!($cond),
+                ::core::file!(),
+                ::core::line!(),
+                ::core::option::Option::Some(::core::format_args!($($arg)+)),
+            );
+            return $fallback;
+        }
+    };
+    ($cond:expr $(, $($arg:tt)*)?) => {
+        $crate::gm_assert!($crate::OutputCode::FAILURE => $cond $(, $($arg)*)?)
+    };
+}
+
+/// Like [gm_assert](crate::gm_assert), but only checked in debug builds, like `debug_assert`.
+#[macro_export]
+macro_rules! gm_debug_assert {
+    ($($arg:tt)*) => {
+        if ::core::cfg!(debug_assertions) {
+            $crate::gm_assert!($($arg)*);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn last_error_slot() {
+        assert!(set_last_error(GmError::from(GmStrError::Null)).is_failure());
+        assert_eq!(
+            GmPtr::new(last_error_ptr()).try_to_str(),
+            Ok("pointer was null (was `undefined` passed?)")
+        );
+
+        set_last_error(GmError::OutOfBounds { index: 4, len: 2 });
+        assert_eq!(
+            take_last_error().as_deref(),
+            Some("index 4 is out of bounds for length 2")
+        );
+        assert_eq!(last_error(), None);
+        assert_eq!(GmPtr::new(last_error_ptr()).try_to_str(), Ok(""));
+    }
+
+    #[test]
+    fn catches_panics() {
+        let code = crate::catch_ffi!({
+            if last_error().is_none() {
+                panic!("boom {}", 1);
+            }
+            OutputCode::SUCCESS
+        });
+
+        assert!(code.is_failure());
+        assert_eq!(take_last_error().as_deref(), Some("panicked: boom 1"));
+        assert_eq!(crate::catch_ffi!(0 => { 5 }), 5);
+    }
+
+    #[test]
+    fn assertions_return_failure() {
+        fn check(len: usize) -> OutputCode {
+            crate::gm_assert!(len < 4, "len was {}", len);
+            crate::gm_debug_assert!(len != 2);
+            OutputCode::SUCCESS
+        }
+
+        assert!(check(1).is_success());
+        assert!(check(5).is_failure());
+        assert!(take_last_error()
+            .is_some_and(|e| e.starts_with("assertion failed: len < 4 (len was 5)")));
+        assert_eq!(check(2).is_failure(), cfg!(debug_assertions));
+    }
+}
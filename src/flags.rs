@@ -0,0 +1,115 @@
+//! Sets of boolean flags packed into a single real.
+
+use core::fmt;
+
+/// The number of flags which fit in a real. Every integer up to `2^52` is exact in an f64,
+/// so bits `0..52` can be used.
+pub const MAX_FLAGS: u32 = 52;
+
+/// Derives a set of flags packed into the bits of a real, so a whole bundle of options can be
+/// passed to or from an export in one argument. It's derived for a `struct Name(u64);`, with
+/// the flags listed in a `#[gm_flags(...)]` attribute.
+///
+/// Each flag gives the bit it lives in, which must be less than [MAX_FLAGS], and becomes an
+/// associated constant. The struct also gets `EMPTY` and `ALL` constants, the usual set
+/// operations, conversions to and from reals, failing with [InvalidFlags], and, like
+/// [GmEnum](macro@crate::enums::GmEnum), a `gml_macros()` function which returns a GML `#macro`
+/// block of the flags, named `TYPE_FLAG`, which GML can combine with `|`.
+/// ```
+/// use gm_ffi::flags::GmFlags;
+///
+/// /// How an enemy spawns.
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq, GmFlags)]
+/// #[gm_flags(
+///     HIDDEN = 0,
+///     /// Can't take damage.
+///     INVINCIBLE = 1,
+///     BOSS = 4,
+/// )]
+/// pub struct SpawnFlags(u64);
+///
+/// let flags = SpawnFlags::try_from(17.0).unwrap();
+/// assert!(flags.contains(SpawnFlags::HIDDEN | SpawnFlags::BOSS));
+/// assert!(!flags.contains(SpawnFlags::INVINCIBLE));
+///
+/// // bit 3 isn't a flag
+/// assert!(SpawnFlags::try_from(8.0).is_err());
+///
+/// assert!(SpawnFlags::gml_macros().contains("#macro SPAWN_FLAGS_BOSS 16"));
+/// ```
+///
+/// The generated code names this crate as `gm_ffi`, so it can't be renamed in `Cargo.toml`.
+pub use gm_ffi_derive::GmFlags;
+
+/// Declares a struct which derives [GmFlags](macro@crate::flags::GmFlags), along with
+/// `Debug`, `Clone`, `Copy`, `PartialEq`, `Eq`, `Hash`, and `Default`, for code which would
+/// rather not name the derive.
+/// ```
+/// gm_ffi::gm_flags! {
+///     /// How an enemy spawns.
+///     pub struct SpawnFlags {
+///         const HIDDEN = 0;
+///         const BOSS = 4;
+///     }
+/// }
+///
+/// assert_eq!(f64::from(SpawnFlags::HIDDEN | SpawnFlags::BOSS), 17.0);
+/// ```
+#[macro_export]
+macro_rules! gm_flags {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* const $flag:ident = $bit:expr;)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, $crate::flags::GmFlags)]
+        #[gm_flags($($(#[$fmeta])* $flag = $bit),*)]
+        $vis struct $name(u64);
+    };
+}
+
+/// The error returned when a real isn't a valid set of [GmFlags](macro@GmFlags): it's
+/// negative, fractional, too big, or has bits set which aren't flags.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidFlags {
+    /// The name of the flags type.
+    pub name: &'static str,
+    /// The value which didn't match.
+    pub value: f64,
+}
+
+impl fmt::Display for InvalidFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} is not a valid set of {}", self.value, self.name)
+    }
+}
+
+impl std::error::Error for InvalidFlags {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, GmFlags)]
+    #[gm_flags(LOOP = 0, MUTED = 51)]
+    struct Options(u64);
+
+    #[test]
+    fn packing() {
+        let mut options = Options::EMPTY;
+        options.set(Options::MUTED, true);
+        options.insert(Options::LOOP);
+        assert_eq!(options, Options::ALL);
+
+        let real = f64::from(options);
+        assert_eq!(Options::try_from(real), Ok(options));
+        assert!(Options::try_from(2.0).is_err());
+        assert!(Options::try_from(-1.0).is_err());
+
+        options.remove(Options::LOOP);
+        assert_eq!(options.bits(), 1 << 51);
+        assert!(!options.is_empty());
+    }
+}
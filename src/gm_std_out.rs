@@ -0,0 +1,341 @@
+//! Output to adam, NPC Studio's IPC log viewer.
+
+use interprocess::local_socket::LocalSocketStream;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// Where [GmStdOut] sends output. [GmStdOut::setup] takes a list of these, and uses the first
+/// one which works.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputTarget {
+    /// adam, over the socket named by the `ADAM_IPC_SOCKET` environment variable. This only
+    /// works if the variable is set and adam is listening.
+    Adam,
+    /// A file, which is appended to. Somewhere in the game's save directory is a good spot.
+    File(PathBuf),
+    /// The process's stderr.
+    Stderr,
+    /// The process's stdout.
+    Stdout,
+    /// Nowhere. Always works.
+    Silent,
+}
+
+impl OutputTarget {
+    /// What's used if [GmStdOut::setup] is never called: adam, and stdout when it's not around.
+    pub const DEFAULT_CHAIN: &'static [OutputTarget] = &[OutputTarget::Adam, OutputTarget::Stdout];
+}
+
+/// Which stream a message written with [GmStdOut::write_str_to] belongs to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Channel {
+    /// Normal output, like [gm_println](crate::gm_println).
+    Stdout = 0,
+    /// Error output, like [gm_eprintln](crate::gm_eprintln).
+    Stderr = 1,
+}
+
+#[derive(Debug)]
+enum Fallback {
+    File(File),
+    Stderr,
+    Stdout,
+    Silent,
+}
+
+/// This struct abstracts for our purposes to only `adam`. It's not very useful
+/// to people outside NPC Studio (unless they also use `adam`), so it's kept internally.
+///
+/// By default, this connects to adam if `ADAM_IPC_SOCKET` is set, and falls back to stdout
+/// otherwise, so a DLL still works when the game is run on its own. Call
+/// [setup](GmStdOut::setup) to choose something else.
+///
+/// If adam goes away (say, it's restarted during a long play session), messages are buffered
+/// and we try to reconnect, backing off up to [MAX_BACKOFF](GmStdOut::MAX_BACKOFF) between
+/// attempts. Once reconnected, the buffered messages are sent first.
+#[derive(Debug)]
+pub struct GmStdOut {
+    socket: Option<LocalSocketStream>,
+    socket_name: Option<String>,
+    pending: VecDeque<Vec<u8>>,
+    backoff: Duration,
+    next_attempt: Option<Instant>,
+    fallback: Fallback,
+}
+
+static STRUCTURED: AtomicBool = AtomicBool::new(false);
+
+static GM_STD_OUT: Lazy<RwLock<GmStdOut>> =
+    Lazy::new(|| RwLock::new(GmStdOut::open(OutputTarget::DEFAULT_CHAIN).0));
+
+impl GmStdOut {
+    /// The most messages buffered while adam is disconnected. Past this, the oldest are dropped.
+    pub const MAX_PENDING: usize = 1024;
+    /// How long we wait before the first reconnect attempt.
+    pub const MIN_BACKOFF: Duration = Duration::from_millis(100);
+    /// The longest we wait between reconnect attempts.
+    pub const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+    /// Chooses where output goes, using the first target in `chain` which works, and returns
+    /// the one chosen. If none work, output is [Silent](OutputTarget::Silent).
+    ///
+    /// Call this once, at startup, before anything is printed:
+    /// ```no_run
+    /// use gm_ffi::{GmStdOut, OutputTarget};
+    ///
+    /// GmStdOut::setup(&[
+    ///     OutputTarget::Adam,
+    ///     OutputTarget::File("saves/my_extension.log".into()),
+    ///     OutputTarget::Stderr,
+    /// ]);
+    /// ```
+    pub fn setup(chain: &[OutputTarget]) -> OutputTarget {
+        let (output, target) = Self::open(chain);
+        *GM_STD_OUT.write() = output;
+
+        target
+    }
+
+    fn open(chain: &[OutputTarget]) -> (GmStdOut, OutputTarget) {
+        let mut output = GmStdOut {
+            socket: None,
+            socket_name: None,
+            pending: VecDeque::new(),
+            backoff: Self::MIN_BACKOFF,
+            next_attempt: None,
+            fallback: Fallback::Silent,
+        };
+
+        for target in chain {
+            match target {
+                OutputTarget::Adam => {
+                    let Ok(socket_name) = std::env::var("ADAM_IPC_SOCKET") else {
+                        continue;
+                    };
+                    let Ok(socket) = LocalSocketStream::connect(socket_name.as_str()) else {
+                        continue;
+                    };
+
+                    output.socket = Some(socket);
+                    output.socket_name = Some(socket_name);
+                }
+                OutputTarget::File(path) => {
+                    let Ok(file) = File::options().create(true).append(true).open(path) else {
+                        continue;
+                    };
+
+                    output.fallback = Fallback::File(file);
+                }
+                OutputTarget::Stderr => output.fallback = Fallback::Stderr,
+                OutputTarget::Stdout => output.fallback = Fallback::Stdout,
+                OutputTarget::Silent => {}
+            }
+
+            return (output, target.clone());
+        }
+
+        (output, OutputTarget::Silent)
+    }
+
+    const LENGTH_MASK: u64 = (1 << 56) - 1;
+
+    /// Gets a handle to stdout.
+    pub fn stdout() -> impl std::ops::DerefMut<Target = GmStdOut> {
+        GM_STD_OUT.write()
+    }
+
+    /// Tries to write a string out, handling errors by buffering the message and reconnecting
+    /// later.
+    pub fn write_str(&mut self, input: &str) {
+        self.write_str_to(Channel::Stdout, input);
+    }
+
+    /// Writes a string out on a [Channel], so adam can tell errors apart from normal output.
+    ///
+    /// Each frame starts with a little-endian u64 holding the message's length in its low 56
+    /// bits, and the channel in its top 8 bits. Stdout frames have a channel of 0, so they look
+    /// exactly like they always have.
+    pub fn write_str_to(&mut self, channel: Channel, input: &str) {
+        if self.socket_name.is_none() {
+            let _ = match (channel, &self.fallback) {
+                (Channel::Stderr, Fallback::Stdout) => {
+                    std::io::stderr().write_all(input.as_bytes())
+                }
+                _ => self.write_fallback(input.as_bytes()),
+            };
+            return;
+        }
+
+        let header = (input.len() as u64 & Self::LENGTH_MASK) | (channel as u64) << 56;
+        let mut frame = Vec::with_capacity(8 + input.len());
+        frame.extend_from_slice(&header.to_le_bytes());
+        frame.extend_from_slice(input.as_bytes());
+
+        if self.pending.len() == Self::MAX_PENDING {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(frame);
+
+        self.flush_pending();
+    }
+
+    /// Sends everything buffered, reconnecting first if we need to and it's time to try.
+    fn flush_pending(&mut self) {
+        if self.socket.is_none() && !self.reconnect() {
+            return;
+        }
+        let Some(socket) = &mut self.socket else {
+            return;
+        };
+
+        while let Some(frame) = self.pending.front() {
+            if socket.write_all(frame).is_err() {
+                // a partly written frame is lost, but the rest wait for the next connection
+                self.pending.pop_front();
+                self.disconnect();
+                return;
+            }
+            self.pending.pop_front();
+        }
+
+        if socket.flush().is_err() {
+            self.disconnect();
+        }
+    }
+
+    fn reconnect(&mut self) -> bool {
+        let now = Instant::now();
+        if self
+            .next_attempt
+            .is_some_and(|next_attempt| now < next_attempt)
+        {
+            return false;
+        }
+        let Some(socket_name) = self.socket_name.as_deref() else {
+            return false;
+        };
+
+        match LocalSocketStream::connect(socket_name) {
+            Ok(socket) => {
+                self.socket = Some(socket);
+                self.backoff = Self::MIN_BACKOFF;
+                self.next_attempt = None;
+                true
+            }
+            Err(_) => {
+                self.next_attempt = Some(now + self.backoff);
+                self.backoff = (self.backoff * 2).min(Self::MAX_BACKOFF);
+                false
+            }
+        }
+    }
+
+    fn write_fallback(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        match &mut self.fallback {
+            Fallback::File(file) => file.write_all(buf),
+            Fallback::Stderr => std::io::stderr().write_all(buf),
+            Fallback::Stdout => std::io::stdout().write_all(buf),
+            Fallback::Silent => Ok(()),
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.socket = None;
+        self.backoff = Self::MIN_BACKOFF;
+        self.next_attempt = Some(Instant::now() + self.backoff);
+    }
+
+    /// Turns structured mode on or off. In structured mode, messages from the leveled macros
+    /// (like [gm_info](crate::gm_info)) are sent to adam as JSON frames,
+    /// `{"level":"INFO","target":"my_extension::net","message":"...","timestamp":1700000000.123}`,
+    /// so it can color, filter, and group them. Frames are still length-prefixed, and plain
+    /// [gm_println](crate::gm_println) output is still sent as text.
+    pub fn set_structured(structured: bool) {
+        STRUCTURED.store(structured, Ordering::Relaxed);
+    }
+
+    /// Returns if structured mode is on. See [set_structured](GmStdOut::set_structured).
+    pub fn is_structured() -> bool {
+        STRUCTURED.load(Ordering::Relaxed)
+    }
+
+    /// Writes a leveled message, as a JSON frame in structured mode, or as a line of text
+    /// otherwise. Errors and warnings go on [Channel::Stderr].
+    pub fn write_record(&mut self, level: crate::level::Level, target: &str, message: &str) {
+        let channel = if level <= crate::level::Level::Warn {
+            Channel::Stderr
+        } else {
+            Channel::Stdout
+        };
+
+        if Self::is_structured() && self.socket_name.is_some() {
+            self.write_str_to(channel, &crate::level::json_frame(level, target, message));
+        } else {
+            self.write_str_to(channel, &format!("[{} {}] {}\n", level, target, message));
+        }
+    }
+
+    /// Returns if this is connected to adam right now.
+    pub fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Returns the number of messages waiting to be sent to adam once it reconnects.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl std::io::Write for GmStdOut {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.socket {
+            Some(socket) => socket.write(buf),
+            None => self.write_fallback(buf).map(|()| buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.socket {
+            Some(socket) => socket.flush(),
+            None => match &mut self.fallback {
+                Fallback::File(file) => file.flush(),
+                Fallback::Stderr => std::io::stderr().flush(),
+                Fallback::Stdout => std::io::stdout().flush(),
+                Fallback::Silent => Ok(()),
+            },
+        }
+    }
+
+    fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> std::io::Result<()> {
+        // Create a shim which translates a Write to a fmt::Write and saves
+        // off I/O errors. instead of discarding them
+        struct Adapter<'a> {
+            inner: &'a mut GmStdOut,
+        }
+
+        impl std::fmt::Write for Adapter<'_> {
+            fn write_str(&mut self, s: &str) -> std::fmt::Result {
+                self.inner.write_str(s);
+
+                Ok(())
+            }
+        }
+
+        let mut output = Adapter { inner: self };
+        let _ = std::fmt::write(&mut output, fmt);
+
+        Ok(())
+    }
+}
+
+/// This sets up a fairly decent panic hook. Pass in the name for us to format to use to identify the DLL.
+pub fn setup_panic_hook(project_name: &str) {
+    crate::panic::PanicHook::new(project_name).install();
+}
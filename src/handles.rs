@@ -0,0 +1,444 @@
+//! Handles to Rust objects, which GML holds as reals.
+
+use crate::GmError;
+use core::fmt;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// A handle to an object in a [HandleRegistry].
+///
+/// This is `#[repr(transparent)]` over an f64, so it can be used directly as an argument or
+/// return type of an export. It packs the object's slot and the slot's generation together as
+/// `generation * 2^32 + slot`, so a handle to a freed object won't find whatever's put in its
+/// slot next. Handles are never 0, so GML can use 0 for "nothing".
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmHandle(pub f64);
+
+impl GmHandle {
+    const SLOT_RANGE: f64 = 4_294_967_296.0; // 2^32
+
+    // keeps `generation * 2^32 + slot` below 2^53, so it's exact in an f64
+    const MAX_GENERATION: u32 = (1 << 21) - 1;
+
+    /// Creates a handle from a raw real, such as one passed from GML.
+    pub const fn new(handle: f64) -> Self {
+        Self(handle)
+    }
+
+    /// Returns the inner f64.
+    pub const fn inner(self) -> f64 {
+        self.0
+    }
+
+    fn from_parts(slot: usize, generation: u32) -> Self {
+        Self(generation as f64 * Self::SLOT_RANGE + slot as f64)
+    }
+
+    fn parts(self) -> Option<(usize, u32)> {
+        let valid = self.0.is_finite() && self.0.fract() == 0.0 && self.0 >= Self::SLOT_RANGE;
+        if !valid || self.0 >= (Self::MAX_GENERATION as f64 + 1.0) * Self::SLOT_RANGE {
+            return None;
+        }
+
+        let slot = (self.0 % Self::SLOT_RANGE) as usize;
+        let generation = (self.0 / Self::SLOT_RANGE) as u32;
+
+        Some((slot, generation))
+    }
+}
+
+/// Why a [GmHandle] didn't find anything in a [HandleRegistry].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandleError {
+    /// The real isn't a handle at all, or is for a slot which was never used.
+    Invalid(GmHandle),
+    /// The handle's object was freed.
+    Stale(GmHandle),
+}
+
+impl fmt::Display for HandleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandleError::Invalid(handle) => write!(f, "{} is not a valid handle", handle.0),
+            HandleError::Stale(handle) => write!(f, "handle {} was already freed", handle.0),
+        }
+    }
+}
+
+impl std::error::Error for HandleError {}
+
+impl From<HandleError> for GmError {
+    fn from(o: HandleError) -> Self {
+        GmError::Custom(o.to_string())
+    }
+}
+
+#[derive(Debug)]
+struct Slot<T> {
+    generation: u32,
+    refs: usize,
+    value: Option<T>,
+    created_at: Instant,
+    #[cfg(debug_assertions)]
+    backtrace: Option<std::backtrace::Backtrace>,
+}
+
+/// Stores Rust objects, handing out [GmHandle]s for GML to hold onto.
+///
+/// Instead of every DLL keeping its own `HashMap<u64, Thing>`, and every DLL having its own
+/// use-after-free bugs, freed slots are reused with a new generation, so an old handle reports
+/// [HandleError::Stale] instead of finding the new object. A slot which runs out of generations
+/// is retired, rather than starting over and handing out old handles again.
+/// ```
+/// # use gm_ffi::handles::{GmHandle, HandleRegistry};
+/// use std::sync::Mutex;
+///
+/// struct Enemy {
+///     hp: f64,
+/// }
+///
+/// static ENEMIES: Mutex<HandleRegistry<Enemy>> = Mutex::new(HandleRegistry::new());
+///
+/// #[no_mangle]
+/// pub extern "C" fn enemy_create(hp: f64) -> GmHandle {
+///     ENEMIES.lock().unwrap().insert(Enemy { hp })
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn enemy_hp(enemy: GmHandle) -> f64 {
+///     ENEMIES.lock().unwrap().get(enemy).map_or(-1.0, |e| e.hp)
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn enemy_destroy(enemy: GmHandle) {
+///     ENEMIES.lock().unwrap().free(enemy);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct HandleRegistry<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> HandleRegistry<T> {
+    /// Creates a new, empty registry.
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Stores `value`, returning its handle.
+    pub fn insert(&mut self, value: T) -> GmHandle {
+        self.len += 1;
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.refs = 1;
+            slot.value = Some(value);
+            slot.created_at = Instant::now();
+            #[cfg(debug_assertions)]
+            {
+                slot.backtrace = Some(std::backtrace::Backtrace::capture());
+            }
+            return GmHandle::from_parts(index, slot.generation);
+        }
+
+        self.slots.push(Slot {
+            generation: 1,
+            refs: 1,
+            value: Some(value),
+            created_at: Instant::now(),
+            #[cfg(debug_assertions)]
+            backtrace: Some(std::backtrace::Backtrace::capture()),
+        });
+        GmHandle::from_parts(self.slots.len() - 1, 1)
+    }
+
+    fn slot(&self, handle: GmHandle) -> Result<&Slot<T>, HandleError> {
+        let (index, generation) = handle.parts().ok_or(HandleError::Invalid(handle))?;
+        let slot = self.slots.get(index).ok_or(HandleError::Invalid(handle))?;
+
+        if slot.generation > generation {
+            Err(HandleError::Stale(handle))
+        } else if slot.generation < generation || slot.value.is_none() {
+            Err(HandleError::Invalid(handle))
+        } else {
+            Ok(slot)
+        }
+    }
+
+    /// Returns the object for `handle`, or why there isn't one.
+    pub fn try_get(&self, handle: GmHandle) -> Result<&T, HandleError> {
+        self.slot(handle).map(|slot| slot.value.as_ref().unwrap())
+    }
+
+    /// Returns the object for `handle`, mutably, or why there isn't one.
+    pub fn try_get_mut(&mut self, handle: GmHandle) -> Result<&mut T, HandleError> {
+        self.slot(handle)?;
+        let (index, _) = handle.parts().unwrap();
+
+        Ok(self.slots[index].value.as_mut().unwrap())
+    }
+
+    /// Returns the object for `handle`.
+    pub fn get(&self, handle: GmHandle) -> Option<&T> {
+        self.try_get(handle).ok()
+    }
+
+    /// Returns the object for `handle`, mutably.
+    pub fn get_mut(&mut self, handle: GmHandle) -> Option<&mut T> {
+        self.try_get_mut(handle).ok()
+    }
+
+    /// Returns if `handle` points to an object.
+    pub fn contains(&self, handle: GmHandle) -> bool {
+        self.slot(handle).is_ok()
+    }
+
+    /// Removes and returns the object for `handle`, however many references it has. Afterwards,
+    /// `handle` (and any copies GML kept) is stale.
+    pub fn free(&mut self, handle: GmHandle) -> Option<T> {
+        self.slot(handle).ok()?;
+        let (index, _) = handle.parts()?;
+
+        let slot = &mut self.slots[index];
+        let value = slot.value.take();
+        #[cfg(debug_assertions)]
+        {
+            slot.backtrace = None;
+        }
+        // past MAX_GENERATION, no handle can match the slot again, so it's never reused
+        slot.generation += 1;
+        if slot.generation <= GmHandle::MAX_GENERATION {
+            self.free.push(index);
+        }
+        self.len -= 1;
+
+        value
+    }
+
+    /// Adds a reference to the object for `handle`, so it takes one more
+    /// [release](HandleRegistry::release) to free it. Returns the number of references now.
+    pub fn retain(&mut self, handle: GmHandle) -> Result<usize, HandleError> {
+        self.slot(handle)?;
+        let (index, _) = handle.parts().unwrap();
+
+        let slot = &mut self.slots[index];
+        slot.refs += 1;
+
+        Ok(slot.refs)
+    }
+
+    /// Drops a reference to the object for `handle`, freeing it if that was the last one.
+    /// Returns the object if it was freed.
+    ///
+    /// Objects start with one reference, so without any [retain](HandleRegistry::retain)s,
+    /// this is the same as [free](HandleRegistry::free).
+    pub fn release(&mut self, handle: GmHandle) -> Result<Option<T>, HandleError> {
+        self.slot(handle)?;
+        let (index, _) = handle.parts().unwrap();
+
+        let slot = &mut self.slots[index];
+        slot.refs -= 1;
+        if slot.refs > 0 {
+            return Ok(None);
+        }
+
+        Ok(self.free(handle))
+    }
+
+    /// Returns the number of references to the object for `handle`.
+    pub fn refs(&self, handle: GmHandle) -> Option<usize> {
+        self.slot(handle).ok().map(|slot| slot.refs)
+    }
+
+    /// The number of objects stored.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns if nothing is stored.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns every handle still stored, and how long ago it was created, oldest first.
+    pub fn leaks(&self) -> Vec<(GmHandle, Duration)> {
+        let mut output: Vec<_> = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.value.is_some())
+            .map(|(index, slot)| {
+                (
+                    GmHandle::from_parts(index, slot.generation),
+                    slot.created_at.elapsed(),
+                )
+            })
+            .collect();
+        output.sort_by_key(|e| core::cmp::Reverse(e.1));
+
+        output
+    }
+
+    /// Returns a human readable report of every handle still stored, oldest first. In debug
+    /// builds with `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` set, this includes a backtrace of
+    /// where each was created. This is empty if there's nothing stored.
+    pub fn leak_report(&self) -> String {
+        let mut output = String::new();
+
+        for (handle, age) in self.leaks() {
+            let slot = self.slot(handle).unwrap();
+            writeln!(
+                output,
+                "handle {}: {} reference(s), created {:.1}s ago",
+                handle.0,
+                slot.refs,
+                age.as_secs_f64()
+            )
+            .unwrap();
+
+            #[cfg(debug_assertions)]
+            if let Some(backtrace) = slot
+                .backtrace
+                .as_ref()
+                .filter(|b| b.status() == std::backtrace::BacktraceStatus::Captured)
+            {
+                for line in backtrace.to_string().lines() {
+                    writeln!(output, "    {}", line).unwrap();
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Returns every handle and its object.
+    pub fn iter(&self) -> impl Iterator<Item = (GmHandle, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let value = slot.value.as_ref()?;
+            Some((GmHandle::from_parts(index, slot.generation), value))
+        })
+    }
+}
+
+impl<T> Default for HandleRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates exports for sharing objects in a [HandleRegistry](crate::HandleRegistry) between
+/// several GML systems: one which adds a reference, called `handle_clone` by default, and one
+/// which drops a reference, freeing the object once the last is gone, called `handle_free` by
+/// default. Each registry needs its own names, as described in [Naming exports](crate#naming-exports).
+///
+/// The registry must be a `static` `Mutex<HandleRegistry<T>>`. In the extension editor, declare
+/// both exports as taking a real and returning a real. `handle_clone` returns the handle it was
+/// given (the same number, now with one more reference), and both return 0 and set the
+/// [last error](crate::error::last_error) if the handle is stale.
+/// ```
+/// # use gm_ffi::HandleRegistry;
+/// use std::sync::Mutex;
+///
+/// static TEXTURES: Mutex<HandleRegistry<Vec<u8>>> = Mutex::new(HandleRegistry::new());
+/// gm_ffi::export_shared_handles!(TEXTURES => texture_clone, texture_free);
+///
+/// let texture = TEXTURES.lock().unwrap().insert(vec![0; 16]);
+/// assert_eq!(texture_clone(texture), texture);
+/// assert!(texture_free(texture).is_success());
+/// assert!(TEXTURES.lock().unwrap().contains(texture));
+/// assert!(texture_free(texture).is_success());
+/// assert!(!TEXTURES.lock().unwrap().contains(texture));
+/// ```
+#[macro_export]
+macro_rules! export_shared_handles {
+    ($registry:path) => {
+        $crate::export_shared_handles!($registry => handle_clone, handle_free);
+    };
+    ($registry:path => $clone:ident, $free:ident) => {
+        /// Adds a reference to a shared handle, returning the handle.
+        #[no_mangle]
+        pub extern "C" fn $clone(handle: $crate::GmHandle) -> $crate::GmHandle {
+            $crate::catch_ffi!($crate::GmHandle::new(0.0) => {
+                let mut registry = $registry.lock().unwrap_or_else(|e| e.into_inner());
+                match registry.retain(handle) {
+                    Ok(_) => handle,
+                    Err(e) => {
+                        $crate::error::set_last_error(e);
+                        $crate::GmHandle::new(0.0)
+                    }
+                }
+            })
+        }
+
+        /// Drops a reference to a shared handle, freeing it if that was the last one.
+        #[no_mangle]
+        pub extern "C" fn $free(handle: $crate::GmHandle) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let released = $registry
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .release(handle);
+                // dropped outside the lock, in case the object's drop uses the registry
+                match released {
+                    Ok(object) => {
+                        drop(object);
+                        $crate::OutputCode::SUCCESS
+                    }
+                    Err(e) => $crate::error::set_last_error(e),
+                }
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_stale_handles() {
+        let mut registry = HandleRegistry::new();
+        let first = registry.insert("goblin");
+        assert_eq!(first, GmHandle::new(4_294_967_296.0));
+
+        assert_eq!(registry.free(first), Some("goblin"));
+        let second = registry.insert("orc");
+        assert_ne!(first, second);
+
+        assert_eq!(registry.try_get(first), Err(HandleError::Stale(first)));
+        assert_eq!(registry.get(second), Some(&"orc"));
+        assert_eq!(
+            registry.try_get(GmHandle::new(0.0)),
+            Err(HandleError::Invalid(GmHandle::new(0.0)))
+        );
+        assert!(registry.free(first).is_none());
+        assert_eq!(registry.len(), 1);
+
+        registry.slots[0].generation = GmHandle::MAX_GENERATION;
+        let last = GmHandle::from_parts(0, GmHandle::MAX_GENERATION);
+        assert_eq!(registry.free(last), Some("orc"));
+        assert_eq!(registry.try_get(last), Err(HandleError::Stale(last)));
+        assert_eq!(registry.insert("troll"), GmHandle::from_parts(1, 1));
+    }
+
+    #[test]
+    fn counts_references() {
+        let mut registry = HandleRegistry::new();
+        let handle = registry.insert(String::from("shared"));
+
+        assert_eq!(registry.retain(handle), Ok(2));
+        assert_eq!(registry.release(handle), Ok(None));
+        assert_eq!(registry.refs(handle), Some(1));
+        assert_eq!(registry.release(handle), Ok(Some(String::from("shared"))));
+        assert_eq!(registry.release(handle), Err(HandleError::Stale(handle)));
+    }
+}
@@ -0,0 +1,352 @@
+//! Named tunables which can be tweaked while the game is running, and written back
+//! to a settings file once they feel right.
+
+use crate::ini::GmIni;
+use crate::{Bridge, BridgeWriter, GmError, NeedsResize, OutputCode};
+
+/// A single tunable value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotValue {
+    /// The name this value was registered with.
+    pub name: String,
+    /// The current value.
+    pub value: f64,
+    /// The value it was registered with.
+    pub default: f64,
+    /// The smallest value this can be set to.
+    pub min: f64,
+    /// The largest value this can be set to.
+    pub max: f64,
+    changed: bool,
+}
+
+/// A set of named, range-limited tunables.
+///
+/// Register every tunable once at startup, then read them each step. Something else
+/// (an editor, a debug console) can [set](HotValues::set) them whenever, and values
+/// which have changed can be saved back out with [save_to_ini](HotValues::save_to_ini).
+/// [export_hotvalues](crate::export_hotvalues) generates exports for getting, setting, and
+/// listing them from GML.
+///
+/// ```
+/// # use gm_ffi::{hotvalues::HotValues, GmReal};
+/// # use std::sync::Mutex;
+/// static TUNABLES: Mutex<HotValues> = Mutex::new(HotValues::new());
+///
+/// #[no_mangle]
+/// pub extern "C" fn enemy_speed() -> GmReal {
+///     let mut tunables = TUNABLES.lock().unwrap();
+///     GmReal::new(tunables.register("enemy_speed", 4.0, 0.0, 20.0).unwrap_or(4.0))
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HotValues {
+    values: Vec<HotValue>,
+}
+
+impl HotValues {
+    /// Creates a new, empty set.
+    pub const fn new() -> Self {
+        Self { values: Vec::new() }
+    }
+
+    /// Registers a tunable, returning its current value. If it's already registered,
+    /// this just returns the current value, so it's fine to call every step.
+    ///
+    /// Returns an error, and registers nothing, if `min` is greater than `max`, or any of the
+    /// numbers is NaN.
+    pub fn register(
+        &mut self,
+        name: &str,
+        default: f64,
+        min: f64,
+        max: f64,
+    ) -> Result<f64, GmError> {
+        if let Some(v) = self.find(name) {
+            return Ok(v.value);
+        }
+
+        let default = clamp(default, min, max).ok_or_else(|| {
+            GmError::custom(format_args!(
+                "hot value `{}` has an invalid range: {} in {}..={}",
+                name, default, min, max
+            ))
+        })?;
+        self.values.push(HotValue {
+            name: name.to_string(),
+            value: default,
+            default,
+            min,
+            max,
+            changed: false,
+        });
+
+        Ok(default)
+    }
+
+    /// Returns the current value of a tunable.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.find(name).map(|v| v.value)
+    }
+
+    /// Sets a tunable, clamping it into its range. Returns the value it was actually set to,
+    /// or `None` if there's no tunable with that name or `value` is NaN.
+    pub fn set(&mut self, name: &str, value: f64) -> Option<f64> {
+        let v = self.values.iter_mut().find(|v| v.name == name)?;
+        let value = clamp(value, v.min, v.max)?;
+        if v.value != value {
+            v.value = value;
+            v.changed = true;
+        }
+
+        Some(value)
+    }
+
+    /// Sets a tunable back to its default.
+    pub fn reset(&mut self, name: &str) -> Option<f64> {
+        let default = self.find(name)?.default;
+        self.set(name, default)
+    }
+
+    /// Returns every registered tunable, in registration order.
+    pub fn iter(&self) -> impl Iterator<Item = &HotValue> {
+        self.values.iter()
+    }
+
+    /// Returns the names of every tunable which has been changed since the last save.
+    pub fn changed(&self) -> impl Iterator<Item = &str> {
+        self.values
+            .iter()
+            .filter(|v| v.changed)
+            .map(|v| v.name.as_str())
+    }
+
+    /// Writes every changed tunable into `section` of `ini`, and marks them unchanged.
+    pub fn save_to_ini(&mut self, ini: &mut GmIni, section: &str) {
+        for v in self.values.iter_mut().filter(|v| v.changed) {
+            ini.write_real(section, &v.name, v.value);
+            v.changed = false;
+        }
+    }
+
+    /// Loads every registered tunable found in `section` of `ini`, clamping them into range.
+    /// Values loaded this way aren't counted as changed.
+    pub fn load_from_ini(&mut self, ini: &GmIni, section: &str) {
+        for v in &mut self.values {
+            let value = ini.read_real(section, &v.name, v.value);
+            if let Some(value) = clamp(value, v.min, v.max) {
+                v.value = value;
+            }
+        }
+    }
+
+    /// Writes every tunable into `bridge`, in registration order, and returns how many were
+    /// written.
+    ///
+    /// The layout is a u32 count, then for each tunable its value, default, min, and max as
+    /// f64s, a u32 length in bytes, and its name as a NUL-terminated string, padded to a
+    /// multiple of 4 bytes. [GML_READER] reads it back. If the bridge is too small, nothing is
+    /// written, and this returns a [NeedsResize].
+    pub fn write_into(&self, bridge: &mut Bridge) -> OutputCode {
+        let units = 1 + self
+            .values
+            .iter()
+            .map(|v| 8 + 1 + BridgeWriter::padded_str_len(&v.name))
+            .sum::<usize>();
+
+        let mut writer = bridge.writer();
+        if writer.remaining() < units {
+            return NeedsResize(units * 4).into();
+        }
+
+        writer.write_u32(self.values.len() as u32);
+        for v in &self.values {
+            writer.write_f64(v.value);
+            writer.write_f64(v.default);
+            writer.write_f64(v.min);
+            writer.write_f64(v.max);
+            writer.write_u32(v.name.len() as u32);
+            writer.write_padded_str(&v.name);
+        }
+
+        OutputCode::custom(self.values.len() as f64)
+    }
+
+    fn find(&self, name: &str) -> Option<&HotValue> {
+        self.values.iter().find(|v| v.name == name)
+    }
+}
+
+// `f64::clamp` panics on a bad range, which would abort the game from inside an export
+fn clamp(value: f64, min: f64, max: f64) -> Option<f64> {
+    if value.is_nan() || min.is_nan() || max.is_nan() || min > max {
+        return None;
+    }
+
+    Some(value.clamp(min, max))
+}
+
+/// A GML function which reads the tunables written by [HotValues::write_into] into an array of
+/// `{ name, value, default_value, min, max }` structs.
+pub const GML_READER: &str = r#"/// @param {Id.Buffer} _buffer
+function gm_read_hotvalues(_buffer) {
+    buffer_seek(_buffer, buffer_seek_start, 0);
+    var _count = buffer_read(_buffer, buffer_u32);
+    var _out = array_create(_count);
+    for (var _i = 0; _i < _count; _i++) {
+        var _value = buffer_read(_buffer, buffer_f64);
+        var _default = buffer_read(_buffer, buffer_f64);
+        var _min = buffer_read(_buffer, buffer_f64);
+        var _max = buffer_read(_buffer, buffer_f64);
+        var _len = buffer_read(_buffer, buffer_u32);
+        var _start = buffer_tell(_buffer);
+        var _name = buffer_read(_buffer, buffer_string);
+        buffer_seek(_buffer, buffer_seek_start, _start + (_len div 4 + 1) * 4);
+        _out[_i] = { name: _name, value: _value, default_value: _default, min: _min, max: _max };
+    }
+    return _out;
+}
+"#;
+
+/// Generates exports for a [HotValues](crate::hotvalues::HotValues): one which gets a tunable,
+/// called `hotvalue_get` by default, one which sets one, called `hotvalue_set` by default, and
+/// one which lists them all into a GM buffer with
+/// [write_into](crate::hotvalues::HotValues::write_into), called `hotvalue_list` by default.
+/// See [Naming exports](crate#naming-exports) for renaming them.
+///
+/// The tunables must be a `static` `Mutex<HotValues>`. In the extension editor, declare
+/// `hotvalue_get` as taking a string, `hotvalue_set` as taking a string and a real, and
+/// `hotvalue_list` as taking a buffer id, a buffer address, and the buffer's size in bytes.
+/// All return a real. `hotvalue_get` and `hotvalue_set` return the tunable's value (after
+/// clamping, for a set), or NaN, setting the [last error](crate::error::last_error), if there's
+/// no tunable with that name.
+/// ```
+/// # use gm_ffi::hotvalues::HotValues;
+/// use std::sync::Mutex;
+///
+/// static TUNABLES: Mutex<HotValues> = Mutex::new(HotValues::new());
+/// gm_ffi::export_hotvalues!(TUNABLES => tunable_get, tunable_set, tunable_list);
+///
+/// TUNABLES.lock().unwrap().register("spawn_rate", 2.0, 0.5, 10.0).unwrap();
+/// let name = gm_ffi::GmPtr::new(c"spawn_rate".as_ptr());
+/// assert_eq!(tunable_set(name, 20.0), 10.0);
+/// assert_eq!(tunable_get(name), 10.0);
+///
+/// // a GM buffer too small to be a bridge asks to be resized
+/// let mut small = [0u32; 4];
+/// let ptr = gm_ffi::GmPtr::new(small.as_mut_ptr() as *const _);
+/// let code = unsafe { tunable_list(gm_ffi::GmId::dummy(), ptr, 16.0) };
+/// assert_eq!(code.required_len(), Some(1024));
+/// ```
+#[macro_export]
+macro_rules! export_hotvalues {
+    ($values:path) => {
+        $crate::export_hotvalues!($values => hotvalue_get, hotvalue_set, hotvalue_list);
+    };
+    ($values:path => $get:ident, $set:ident, $list:ident) => {
+        /// Returns the current value of a tunable.
+        #[no_mangle]
+        pub extern "C" fn $get(name: $crate::GmPtr) -> f64 {
+            $crate::catch_ffi!(f64::NAN => {
+                let result = name.try_to_str().map_err($crate::GmError::from).and_then(|name| {
+                    $values
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .get(name)
+                        .ok_or_else(|| $crate::GmError::custom(format_args!("there's no hot value `{}`", name)))
+                });
+
+                result.unwrap_or_else(|e| {
+                    $crate::error::set_last_error(e);
+                    f64::NAN
+                })
+            })
+        }
+
+        /// Sets a tunable, clamping it into its range, and returns the value it was set to.
+        #[no_mangle]
+        pub extern "C" fn $set(name: $crate::GmPtr, value: f64) -> f64 {
+            $crate::catch_ffi!(f64::NAN => {
+                let result = name.try_to_str().map_err($crate::GmError::from).and_then(|name| {
+                    $values
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .set(name, value)
+                        .ok_or_else(|| {
+                            $crate::GmError::custom(format_args!(
+                                "couldn't set hot value `{}` to {}",
+                                name, value
+                            ))
+                        })
+                });
+
+                result.unwrap_or_else(|e| {
+                    $crate::error::set_last_error(e);
+                    f64::NAN
+                })
+            })
+        }
+
+        /// Lists every tunable into a GM buffer.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $list(
+            id: $crate::GmId,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let buffer = unsafe { $crate::GmBuffer::new(id, ptr, len as usize / 4) };
+                let mut bridge = match $crate::Bridge::try_new(buffer) {
+                    Ok(bridge) => bridge,
+                    Err(e) => return e.into(),
+                };
+                $values
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .write_into(&mut bridge)
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_bridge;
+
+    #[test]
+    fn tweak_and_save() {
+        let mut values = HotValues::new();
+        assert_eq!(values.register("speed", 4.0, 0.0, 10.0), Ok(4.0));
+        assert_eq!(values.register("speed", 1.0, 0.0, 1.0), Ok(4.0));
+        assert!(values.register("broken", 1.0, 5.0, 0.0).is_err());
+        assert!(values.register("nan", 1.0, f64::NAN, 2.0).is_err());
+
+        assert_eq!(values.set("speed", 50.0), Some(10.0));
+        assert_eq!(values.set("missing", 1.0), None);
+        assert_eq!(values.changed().collect::<Vec<_>>(), ["speed"]);
+
+        let mut ini = GmIni::new();
+        values.save_to_ini(&mut ini, "tunables");
+        assert_eq!(values.changed().count(), 0);
+        assert_eq!(ini.read_real("tunables", "speed", 0.0), 10.0);
+
+        values.reset("speed");
+        values.load_from_ini(&ini, "tunables");
+        assert_eq!(values.get("speed"), Some(10.0));
+
+        let (backing, mut bridge) = test_bridge();
+        assert_eq!(values.write_into(&mut bridge).as_f64(), 1.0);
+        assert_eq!(backing[0], 1);
+        assert_eq!(
+            backing[9..12],
+            [5, u32::from_le_bytes(*b"spee"), u32::from(b'd')]
+        );
+
+        values.register(&"x".repeat(1100), 0.0, 0.0, 1.0).unwrap();
+        assert_eq!(values.write_into(&mut bridge).required_len(), Some(1188));
+    }
+}
@@ -0,0 +1,298 @@
+//! Reading and writing GameMaker-style ini files, like the ones `ini_open` and friends produce.
+//!
+//! GM writes every value quoted (`key="value"`), and writes reals with six decimal
+//! places (`volume="0.500000"`). When reading, it strips one pair of surrounding quotes,
+//! doesn't understand escapes, and ignores comment lines starting with `;` or `#`.
+//! [GmIni] follows those rules, and keeps lines it hasn't been asked to change exactly as
+//! they were, so a file can be round tripped without mangling it.
+
+use std::fmt;
+
+/// An ini file, held in memory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GmIni {
+    sections: Vec<Section>,
+    dirty: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Section {
+    /// The section's name. Lines before the first section header go in a section named "".
+    name: String,
+    /// The header, exactly as it was written.
+    header: Option<String>,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Line {
+    /// Blank lines, comments, and anything else we couldn't make sense of.
+    Other(String),
+    Pair {
+        key: String,
+        value: String,
+        raw: String,
+    },
+}
+
+impl GmIni {
+    /// Creates a new, empty ini.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses an ini. Like GM, this never fails; lines it doesn't understand are kept around
+    /// but otherwise ignored.
+    pub fn parse(input: &str) -> Self {
+        let mut sections = vec![Section {
+            name: String::new(),
+            header: None,
+            lines: Vec::new(),
+        }];
+
+        for raw in input.lines() {
+            let trimmed = raw.trim();
+
+            if let Some(name) = trimmed
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                sections.push(Section {
+                    name: name.trim().to_string(),
+                    header: Some(raw.to_string()),
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            let line = match trimmed.split_once('=') {
+                Some((key, value)) if !trimmed.starts_with([';', '#']) => Line::Pair {
+                    key: key.trim().to_string(),
+                    value: unquote(value.trim()).to_string(),
+                    raw: raw.to_string(),
+                },
+                _ => Line::Other(raw.to_string()),
+            };
+
+            sections.last_mut().unwrap().lines.push(line);
+        }
+
+        Self {
+            sections,
+            dirty: false,
+        }
+    }
+
+    /// Returns the raw string value of `key` in `section`, without its quotes.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.sections
+            .iter()
+            .filter(|s| s.name == section)
+            .flat_map(|s| s.lines.iter())
+            .find_map(|l| match l {
+                Line::Pair { key: k, value, .. } if k == key => Some(value.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Reads a string, like `ini_read_string`. Returns `default` if the key is missing.
+    pub fn read_string<'a>(&'a self, section: &str, key: &str, default: &'a str) -> &'a str {
+        self.get(section, key).unwrap_or(default)
+    }
+
+    /// Reads a real, like `ini_read_real`. Returns `default` if the key is missing or isn't a number.
+    pub fn read_real(&self, section: &str, key: &str, default: f64) -> f64 {
+        self.get(section, key)
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Reads a bool, stored the way GML stores them: as a real, where anything above 0.5 is true.
+    /// Returns `default` if the key is missing or isn't a number.
+    pub fn read_bool(&self, section: &str, key: &str, default: bool) -> bool {
+        self.get(section, key)
+            .and_then(|v| v.trim().parse::<f64>().ok())
+            .map_or(default, |v| v > 0.5)
+    }
+
+    /// Writes a string, like `ini_write_string`.
+    pub fn write_string(&mut self, section: &str, key: &str, value: &str) {
+        self.set_raw(section, key, value.to_string());
+    }
+
+    /// Writes a real, like `ini_write_real`, with six decimal places.
+    pub fn write_real(&mut self, section: &str, key: &str, value: f64) {
+        self.set_raw(section, key, format!("{:.6}", value));
+    }
+
+    /// Writes a bool as the real `1` or `0`.
+    pub fn write_bool(&mut self, section: &str, key: &str, value: bool) {
+        self.write_real(section, key, if value { 1.0 } else { 0.0 });
+    }
+
+    /// Returns if `key` exists in `section`.
+    pub fn key_exists(&self, section: &str, key: &str) -> bool {
+        self.get(section, key).is_some()
+    }
+
+    /// Returns if `section` exists.
+    pub fn section_exists(&self, section: &str) -> bool {
+        self.sections
+            .iter()
+            .any(|s| s.header.is_some() && s.name == section)
+    }
+
+    /// Deletes `key` from `section`, like `ini_key_delete`.
+    pub fn delete_key(&mut self, section: &str, key: &str) {
+        for s in self.sections.iter_mut().filter(|s| s.name == section) {
+            let before = s.lines.len();
+            s.lines
+                .retain(|l| !matches!(l, Line::Pair { key: k, .. } if k == key));
+            self.dirty |= before != s.lines.len();
+        }
+    }
+
+    /// Deletes `section` and everything in it, like `ini_section_delete`.
+    pub fn delete_section(&mut self, section: &str) {
+        let before = self.sections.len();
+        self.sections
+            .retain(|s| s.name != section || s.header.is_none());
+        self.dirty |= before != self.sections.len();
+    }
+
+    /// Returns the names of every section.
+    pub fn sections(&self) -> impl Iterator<Item = &str> {
+        self.sections
+            .iter()
+            .filter(|s| s.header.is_some())
+            .map(|s| s.name.as_str())
+    }
+
+    /// Returns every key and value in `section`.
+    pub fn pairs<'a>(&'a self, section: &'a str) -> impl Iterator<Item = (&'a str, &'a str)> {
+        self.sections
+            .iter()
+            .filter(move |s| s.name == section)
+            .flat_map(|s| s.lines.iter())
+            .filter_map(|l| match l {
+                Line::Pair { key, value, .. } => Some((key.as_str(), value.as_str())),
+                Line::Other(_) => None,
+            })
+    }
+
+    /// Returns if anything has been changed since this was parsed or last marked clean.
+    /// Writing a value that's identical to the one already there doesn't count.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Marks the ini as clean, usually right after saving it.
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+
+    fn set_raw(&mut self, section: &str, key: &str, value: String) {
+        let raw = format!("{}=\"{}\"", key, value);
+
+        let existing = self
+            .sections
+            .iter_mut()
+            .filter(|s| s.name == section)
+            .flat_map(|s| s.lines.iter_mut())
+            .find_map(|l| match l {
+                Line::Pair {
+                    key: k,
+                    value: v,
+                    raw: r,
+                } if k == key => Some((v, r)),
+                _ => None,
+            });
+
+        if let Some((v, r)) = existing {
+            if *v != value {
+                *v = value;
+                *r = raw;
+                self.dirty = true;
+            }
+            return;
+        }
+
+        let line = Line::Pair {
+            key: key.to_string(),
+            value,
+            raw,
+        };
+        match self.sections.iter_mut().find(|s| s.name == section) {
+            Some(s) => s.lines.push(line),
+            None => self.sections.push(Section {
+                name: section.to_string(),
+                header: Some(format!("[{}]", section)),
+                lines: vec![line],
+            }),
+        }
+        self.dirty = true;
+    }
+}
+
+impl fmt::Display for GmIni {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in &self.sections {
+            if let Some(header) = &section.header {
+                writeln!(f, "{}", header)?;
+            }
+
+            for line in &section.lines {
+                match line {
+                    Line::Other(raw) | Line::Pair { raw, .. } => writeln!(f, "{}", raw)?,
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str =
+        "; settings\n[audio]\nvolume=\"0.500000\"\nmuted=0\n\n[player]\nname='Gabe'\n";
+
+    #[test]
+    fn round_trip() {
+        let ini = GmIni::parse(INPUT);
+
+        assert_eq!(ini.read_real("audio", "volume", 1.0), 0.5);
+        assert!(!ini.read_bool("audio", "muted", true));
+        assert_eq!(ini.read_string("player", "name", ""), "Gabe");
+        assert_eq!(ini.read_string("player", "class", "rogue"), "rogue");
+        assert_eq!(ini.to_string(), INPUT);
+        assert!(!ini.is_dirty());
+    }
+
+    #[test]
+    fn writes() {
+        let mut ini = GmIni::parse(INPUT);
+
+        ini.write_string("player", "name", "Gabe");
+        assert!(!ini.is_dirty());
+
+        ini.write_real("audio", "volume", 0.25);
+        ini.write_bool("video", "vsync", true);
+        ini.delete_key("audio", "muted");
+        assert!(ini.is_dirty());
+
+        assert_eq!(
+            ini.to_string(),
+            "; settings\n[audio]\nvolume=\"0.250000\"\n\n[player]\nname='Gabe'\n[video]\nvsync=\"1.000000\"\n"
+        );
+    }
+}
@@ -0,0 +1,206 @@
+//! Instance ids, GML's special instance keywords, and Rust state kept per instance.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// The id of a GM instance, as handed to an export.
+///
+/// GML's instance keywords (`noone`, `all`, `other`, `self`) are negative numbers which can
+/// be passed anywhere an instance id can, so exports should check [is_real_instance](GmInstanceId::is_real_instance)
+/// before using an id as a key.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmInstanceId(pub f64);
+
+impl GmInstanceId {
+    /// GML's `noone`.
+    pub const NOONE: GmInstanceId = GmInstanceId(-4.0);
+    /// GML's `all`.
+    pub const ALL: GmInstanceId = GmInstanceId(-3.0);
+    /// GML's `other`.
+    pub const OTHER: GmInstanceId = GmInstanceId(-2.0);
+    /// GML's `self`.
+    pub const SELF: GmInstanceId = GmInstanceId(-1.0);
+
+    /// The lowest id GM gives to an actual instance.
+    pub const FIRST_INSTANCE: f64 = 100_000.0;
+
+    /// Creates a new instance id.
+    pub const fn new(id: f64) -> Self {
+        Self(id)
+    }
+
+    /// Returns the inner f64.
+    pub const fn inner(self) -> f64 {
+        self.0
+    }
+
+    /// Returns if this is `noone`.
+    pub fn is_noone(self) -> bool {
+        self == Self::NOONE
+    }
+
+    /// Returns if this is one of GML's instance keywords (`noone`, `all`, `other`, or `self`).
+    pub fn is_keyword(self) -> bool {
+        [Self::NOONE, Self::ALL, Self::OTHER, Self::SELF].contains(&self)
+    }
+
+    /// Returns if this could be an actual instance: a whole number, at least [FIRST_INSTANCE](GmInstanceId::FIRST_INSTANCE).
+    /// This doesn't check that the instance still exists.
+    pub fn is_real_instance(self) -> bool {
+        self.0 >= Self::FIRST_INSTANCE && self.0.fract() == 0.0 && self.0.is_finite()
+    }
+
+    /// Returns the id, if it [is_real_instance](GmInstanceId::is_real_instance).
+    pub fn real(self) -> Option<Self> {
+        self.is_real_instance().then_some(self)
+    }
+}
+
+/// Rust state kept for each GM instance, such as a native pathfinder for each enemy.
+///
+/// Declare it as a static, and list it in [export_instance_destroyed](crate::export_instance_destroyed),
+/// so the state is reclaimed when GM destroys the instance:
+/// ```
+/// use gm_ffi::{GmInstanceId, InstanceStateMap};
+///
+/// struct Path {
+///     nodes: Vec<(f64, f64)>,
+/// }
+///
+/// static PATHS: InstanceStateMap<Path> = InstanceStateMap::new();
+/// gm_ffi::export_instance_destroyed!(PATHS);
+///
+/// #[no_mangle]
+/// pub extern "C" fn path_start(id: GmInstanceId) {
+///     PATHS.insert(id, Path { nodes: Vec::new() });
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn path_len(id: GmInstanceId) -> f64 {
+///     PATHS.with(id, |path| path.nodes.len() as f64).unwrap_or(0.0)
+/// }
+/// ```
+#[derive(Debug)]
+pub struct InstanceStateMap<T> {
+    // keyed by the bits of the id, since instance ids are whole numbers
+    states: Mutex<BTreeMap<u64, T>>,
+}
+
+impl<T> InstanceStateMap<T> {
+    /// Creates a new, empty map.
+    pub const fn new() -> Self {
+        Self {
+            states: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn states(&self) -> std::sync::MutexGuard<'_, BTreeMap<u64, T>> {
+        self.states.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stores `state` for the instance, returning what was there before. Only use ids which
+    /// are [real instances](GmInstanceId::is_real_instance), not keywords like `self`.
+    pub fn insert(&self, id: GmInstanceId, state: T) -> Option<T> {
+        self.states().insert(id.0.to_bits(), state)
+    }
+
+    /// Runs `f` with the instance's state, if it has any.
+    pub fn with<R>(&self, id: GmInstanceId, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.states().get_mut(&id.0.to_bits()).map(f)
+    }
+
+    /// Runs `f` with the instance's state, creating it with `default` first if it has none.
+    pub fn with_or_insert<R>(
+        &self,
+        id: GmInstanceId,
+        default: impl FnOnce() -> T,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> R {
+        f(self.states().entry(id.0.to_bits()).or_insert_with(default))
+    }
+
+    /// Removes and returns the instance's state.
+    pub fn remove(&self, id: GmInstanceId) -> Option<T> {
+        self.states().remove(&id.0.to_bits())
+    }
+
+    /// Returns if the instance has state.
+    pub fn contains(&self, id: GmInstanceId) -> bool {
+        self.states().contains_key(&id.0.to_bits())
+    }
+
+    /// The number of instances with state.
+    pub fn len(&self) -> usize {
+        self.states().len()
+    }
+
+    /// Returns if no instances have state.
+    pub fn is_empty(&self) -> bool {
+        self.states().is_empty()
+    }
+}
+
+impl<T> Default for InstanceStateMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Generates an export, called `gm_instance_destroyed` by default, which removes an instance's
+/// state from every listed [InstanceStateMap](crate::InstanceStateMap). See
+/// [Naming exports](crate#naming-exports) for renaming it.
+///
+/// In the extension editor, declare it as taking a real, and call it with `id` from the Clean
+/// Up event of every object which has native state. (Clean Up runs however the instance goes
+/// away, including room changes, unlike Destroy.)
+/// ```
+/// # use gm_ffi::InstanceStateMap;
+/// static HEALTH: InstanceStateMap<f64> = InstanceStateMap::new();
+/// static NAMES: InstanceStateMap<String> = InstanceStateMap::new();
+///
+/// gm_ffi::export_instance_destroyed!(my_extension_instance_destroyed => HEALTH, NAMES);
+/// ```
+#[macro_export]
+macro_rules! export_instance_destroyed {
+    ($name:ident => $($map:path),+ $(,)?) => {
+        /// Removes an instance's native state.
+        #[no_mangle]
+        pub extern "C" fn $name(id: $crate::GmInstanceId) {
+            $crate::catch_ffi!(() => {
+                $($map.remove(id);)+
+            })
+        }
+    };
+    ($($map:path),+ $(,)?) => {
+        $crate::export_instance_destroyed!(gm_instance_destroyed => $($map),+);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keywords() {
+        assert!(GmInstanceId::NOONE.is_noone());
+        assert!(GmInstanceId::new(-2.0).is_keyword());
+        assert!(!GmInstanceId::NOONE.is_real_instance());
+        assert!(GmInstanceId::new(100_004.0).is_real_instance());
+        assert!(GmInstanceId::new(100_004.5).real().is_none());
+    }
+
+    #[test]
+    fn state_per_instance() {
+        static STATE: InstanceStateMap<u32> = InstanceStateMap::new();
+        let id = GmInstanceId::new(100_001.0);
+
+        assert_eq!(STATE.with_or_insert(id, || 1, |n| *n + 1), 2);
+        assert_eq!(STATE.with(id, |n| std::mem::replace(n, 5)), Some(1));
+        assert!(STATE.with(GmInstanceId::new(100_002.0), |_| ()).is_none());
+        assert_eq!(STATE.remove(id), Some(5));
+        assert!(STATE.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+//! Integers which are guaranteed to survive the trip through a GM real.
+
+use core::fmt;
+
+/// An integer within `-(2^53 - 1)..=2^53 - 1`, the range in which every integer can be
+/// represented exactly by an f64 (and so by a GM real).
+///
+/// Ids, byte counts, and timestamps sent to GM as plain `u64 as f64` silently lose precision
+/// once they get big enough. Constructing one of these instead makes that a checked error.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "i64", into = "i64"))]
+pub struct GmInt53(i64);
+
+/// The error returned when a value doesn't fit in a [GmInt53].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfRange;
+
+impl fmt::Display for OutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("value cannot be represented exactly as a GM real")
+    }
+}
+
+impl std::error::Error for OutOfRange {}
+
+impl GmInt53 {
+    /// The largest value, `2^53 - 1`.
+    pub const MAX: GmInt53 = GmInt53((1 << 53) - 1);
+    /// The smallest value, `-(2^53 - 1)`.
+    pub const MIN: GmInt53 = GmInt53(-((1 << 53) - 1));
+    /// Zero.
+    pub const ZERO: GmInt53 = GmInt53(0);
+
+    /// Creates a new value, returning `None` if it's out of range.
+    pub const fn new(value: i64) -> Option<Self> {
+        if value >= Self::MIN.0 && value <= Self::MAX.0 {
+            Some(Self(value))
+        } else {
+            None
+        }
+    }
+
+    /// Converts from a GM real, returning `None` if it isn't a whole number in range.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        if value.fract() != 0.0 || !value.is_finite() {
+            return None;
+        }
+
+        Self::new(value as i64)
+    }
+
+    /// Converts to an f64. This is always exact.
+    pub const fn to_f64(self) -> f64 {
+        self.0 as f64
+    }
+
+    /// Returns the inner i64.
+    pub const fn get(self) -> i64 {
+        self.0
+    }
+
+    /// Adds, returning `None` if the result is out of range.
+    pub const fn checked_add(self, rhs: Self) -> Option<Self> {
+        Self::new(self.0 + rhs.0)
+    }
+
+    /// Subtracts, returning `None` if the result is out of range.
+    pub const fn checked_sub(self, rhs: Self) -> Option<Self> {
+        Self::new(self.0 - rhs.0)
+    }
+
+    /// Multiplies, returning `None` if the result is out of range.
+    pub const fn checked_mul(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_mul(rhs.0) {
+            Some(v) => Self::new(v),
+            None => None,
+        }
+    }
+
+    /// Divides, returning `None` if `rhs` is zero.
+    pub const fn checked_div(self, rhs: Self) -> Option<Self> {
+        match self.0.checked_div(rhs.0) {
+            Some(v) => Some(Self(v)),
+            None => None,
+        }
+    }
+}
+
+impl TryFrom<i64> for GmInt53 {
+    type Error = OutOfRange;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        Self::new(value).ok_or(OutOfRange)
+    }
+}
+
+impl TryFrom<u64> for GmInt53 {
+    type Error = OutOfRange;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        i64::try_from(value)
+            .ok()
+            .and_then(Self::new)
+            .ok_or(OutOfRange)
+    }
+}
+
+impl TryFrom<usize> for GmInt53 {
+    type Error = OutOfRange;
+
+    fn try_from(value: usize) -> Result<Self, Self::Error> {
+        Self::try_from(value as u64)
+    }
+}
+
+impl TryFrom<f64> for GmInt53 {
+    type Error = OutOfRange;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::from_f64(value).ok_or(OutOfRange)
+    }
+}
+
+impl From<i32> for GmInt53 {
+    fn from(o: i32) -> Self {
+        Self(o as i64)
+    }
+}
+
+impl From<u32> for GmInt53 {
+    fn from(o: u32) -> Self {
+        Self(o as i64)
+    }
+}
+
+impl From<GmInt53> for f64 {
+    fn from(o: GmInt53) -> Self {
+        o.to_f64()
+    }
+}
+
+impl From<GmInt53> for i64 {
+    fn from(o: GmInt53) -> Self {
+        o.0
+    }
+}
+
+impl fmt::Display for GmInt53 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_checks() {
+        assert!(GmInt53::try_from(1u64 << 53).is_err());
+        assert!(GmInt53::try_from(u64::MAX).is_err());
+        assert_eq!(GmInt53::try_from((1u64 << 53) - 1), Ok(GmInt53::MAX));
+
+        assert_eq!(GmInt53::MAX.checked_add(GmInt53::from(1)), None);
+        assert_eq!(GmInt53::MAX.checked_mul(GmInt53::MAX), None);
+        assert_eq!(GmInt53::from(7).checked_div(GmInt53::ZERO), None);
+
+        assert_eq!(GmInt53::from_f64(12.0).unwrap().to_f64(), 12.0);
+        assert!(GmInt53::from_f64(1.5).is_none());
+        assert!(GmInt53::from_f64(f64::NAN).is_none());
+    }
+}
@@ -0,0 +1,251 @@
+//! Background jobs on a thread pool, which GML kicks off and collects later, so long-running
+//! work (pathfinding, generation, compression) doesn't freeze the frame.
+//! ```
+//! use gm_ffi::jobs::{self, JobId};
+//! gm_ffi::export_jobs!();
+//!
+//! #[no_mangle]
+//! pub extern "C" fn generate_level(seed: f64) -> JobId {
+//!     jobs::spawn_job(move || {
+//!         // something slow
+//!         seed * 2.0
+//!     })
+//! }
+//! ```
+//! ```gml
+//! job = generate_level(42);
+//!
+//! // later, in a step event
+//! if (job_status(job) == JOB_STATUS_DONE) {
+//!     job_result(job, global.bridge, buffer_get_address(global.bridge), buffer_get_size(global.bridge));
+//!     var _level = buffer_peek(global.bridge, 0, buffer_f64);
+//! }
+//! ```
+//! [JobStatus::gml_macros] has the `JOB_STATUS_*` constants.
+
+use crate::{enums::GmEnum, error::set_last_error, Bridge, BridgeWriter, GmReturn, OutputCode};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+crate::define_gm_id!(
+    /// A job started with [spawn_job].
+    pub JobId
+);
+
+/// Where a job is up to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, GmEnum)]
+pub enum JobStatus {
+    /// There's no job with this id, or its result has already been taken.
+    Unknown,
+    /// The job is queued or running.
+    Running,
+    /// The job finished, and its result is waiting.
+    Done,
+    /// The job panicked.
+    Panicked,
+}
+
+type Task = Box<dyn FnOnce() + Send>;
+type Output = Box<dyn FnOnce(&mut BridgeWriter<'_>) -> OutputCode + Send>;
+
+enum JobState {
+    Running,
+    Done(Output),
+    Panicked(String),
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static JOBS: Lazy<Mutex<HashMap<u64, JobState>>> = Lazy::new(Default::default);
+
+static POOL: Lazy<Mutex<mpsc::Sender<Task>>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<Task>();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    let workers = std::thread::available_parallelism().map_or(2, |n| n.get());
+    for i in 0..workers {
+        let receiver = receiver.clone();
+        std::thread::Builder::new()
+            .name(format!("gm-ffi-job-{}", i))
+            .spawn(move || loop {
+                let task = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                match task {
+                    Ok(task) => task(),
+                    Err(_) => break,
+                }
+            })
+            .expect("couldn't spawn a job thread");
+    }
+
+    Mutex::new(sender)
+});
+
+/// Runs `job` on the thread pool, returning an id GML can poll with the export generated by
+/// [export_jobs](crate::export_jobs). The result is written into a bridge with [GmReturn] once
+/// GML asks for it.
+///
+/// A job which panics is caught, and reports [JobStatus::Panicked]. The panic hook still runs
+/// first, so with a [PanicPolicy](crate::panic::PanicPolicy) which exits, the game goes down
+/// anyway.
+pub fn spawn_job<R, F>(job: F) -> JobId
+where
+    R: GmReturn + Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    jobs().insert(id, JobState::Running);
+
+    let task = Box::new(move || {
+        let state = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(job)) {
+            Ok(output) => JobState::Done(Box::new(move |writer: &mut BridgeWriter<'_>| {
+                output.write_to(writer)
+            })),
+            Err(payload) => JobState::Panicked(
+                payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic payload".to_string()),
+            ),
+        };
+        jobs().insert(id, state);
+    });
+    let _ = POOL.lock().unwrap_or_else(|e| e.into_inner()).send(task);
+
+    JobId::new(id as f64)
+}
+
+/// Like [spawn_job], but the job doesn't start until the [shared](crate::simclock::shared)
+/// clock isn't paused, for work which belongs to the simulation. While it waits, it reports
+/// [JobStatus::Running], and holds onto one of the pool's threads.
+pub fn spawn_sim_job<R, F>(job: F) -> JobId
+where
+    R: GmReturn + Send + 'static,
+    F: FnOnce() -> R + Send + 'static,
+{
+    spawn_job(move || {
+        crate::simclock::wait_while_paused();
+        job()
+    })
+}
+
+fn jobs() -> std::sync::MutexGuard<'static, HashMap<u64, JobState>> {
+    JOBS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns where a job is up to.
+pub fn status(id: JobId) -> JobStatus {
+    match jobs().get(&(id.inner() as u64)) {
+        None => JobStatus::Unknown,
+        Some(JobState::Running) => JobStatus::Running,
+        Some(JobState::Done(_)) => JobStatus::Done,
+        Some(JobState::Panicked(_)) => JobStatus::Panicked,
+    }
+}
+
+/// Writes a finished job's result into `bridge`, returning its [OutputCode], and forgets the
+/// job.
+///
+/// If the job is still running, or doesn't exist, this returns [OutputCode::FAILURE] and sets
+/// the [last error](crate::error::last_error). A job which panicked is forgotten too, with the
+/// panic as the last error.
+pub fn take_result(id: JobId, bridge: &mut Bridge) -> OutputCode {
+    let key = id.inner() as u64;
+    let mut jobs = jobs();
+
+    match jobs.get(&key) {
+        None => return set_last_error(format_args!("there's no job {}", id.inner())),
+        Some(JobState::Running) => {
+            return set_last_error(format_args!("job {} is still running", id.inner()))
+        }
+        Some(_) => {}
+    }
+
+    match jobs.remove(&key) {
+        Some(JobState::Done(output)) => {
+            drop(jobs);
+            output(&mut bridge.writer())
+        }
+        Some(JobState::Panicked(message)) => {
+            set_last_error(format_args!("job {} panicked: {}", id.inner(), message))
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Generates the exports for [jobs](crate::jobs): one which returns a job's [JobStatus], called
+/// `job_status` by default, and one which writes a finished job's result into a GM buffer with
+/// [take_result](crate::jobs::take_result), called `job_result` by default. See
+/// [Naming exports](crate#naming-exports) for renaming them.
+///
+/// In the extension editor, declare the first as taking a real and returning a real, and the
+/// second as taking a real, a buffer id, a buffer address, and the buffer's size in bytes. The
+/// buffer must be at least 1KiB.
+/// ```
+/// gm_ffi::export_jobs!(my_extension_job_status, my_extension_job_result);
+/// ```
+#[macro_export]
+macro_rules! export_jobs {
+    () => {
+        $crate::export_jobs!(job_status, job_result);
+    };
+    ($status:ident, $result:ident) => {
+        /// Returns where a job is up to.
+        #[no_mangle]
+        pub extern "C" fn $status(job: $crate::jobs::JobId) -> f64 {
+            $crate::catch_ffi!(f64::from($crate::jobs::JobStatus::Unknown) => {
+                $crate::jobs::status(job).into()
+            })
+        }
+
+        /// Writes a finished job's result into a GM buffer.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $result(
+            job: $crate::jobs::JobId,
+            id: $crate::GmId,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let buffer = unsafe { $crate::GmBuffer::new(id, ptr, len as usize / 4) };
+                let mut bridge = match $crate::Bridge::try_new(buffer) {
+                    Ok(bridge) => bridge,
+                    Err(e) => return e.into(),
+                };
+                $crate::jobs::take_result(job, &mut bridge)
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_bridge;
+
+    #[test]
+    fn runs_and_collects() {
+        let (backing, mut bridge) = test_bridge();
+
+        let (sender, receiver) = mpsc::channel::<()>();
+        let job = spawn_job(move || {
+            let _ = receiver.recv();
+            7u32
+        });
+        assert_eq!(status(job), JobStatus::Running);
+        assert!(take_result(job, &mut bridge).is_failure());
+
+        sender.send(()).unwrap();
+        while status(job) == JobStatus::Running {
+            std::thread::yield_now();
+        }
+
+        assert!(take_result(job, &mut bridge).is_success());
+        assert_eq!(backing[0], 7);
+        assert_eq!(status(job), JobStatus::Unknown);
+    }
+}
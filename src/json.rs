@@ -0,0 +1,642 @@
+//! Passing structured data between Rust and GML as JSON.
+//!
+//! ## Returning data
+//!
+//! Rather than packing a struct into a buffer by hand, and unpacking it again in GML, derive
+//! `Serialize` on it and return it as JSON:
+//! ```
+//! use gm_ffi::{json::to_gm_json, GmString};
+//! use std::{cell::RefCell, ffi::c_char};
+//!
+//! #[derive(serde::Serialize)]
+//! struct Stats {
+//!     name: String,
+//!     hp: f64,
+//!     tags: Vec<String>,
+//! }
+//!
+//! thread_local! {
+//!     static STATS: RefCell<GmString> = const { RefCell::new(GmString::new()) };
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn player_stats() -> *const c_char {
+//!     let stats = Stats { name: "knight".into(), hp: 12.5, tags: vec!["armoured".into()] };
+//!     STATS.with_borrow_mut(|s| {
+//!         *s = to_gm_json(&stats).unwrap_or_default();
+//!         s.as_ptr()
+//!     })
+//! }
+//! ```
+//! ```gml
+//! var _stats = json_parse(player_stats());
+//! show_debug_message(_stats.hp);
+//! ```
+//! The JSON differs from `serde_json`'s in two ways, so that nothing is lost on the way in:
+//! - NaN and the infinities are written as `"@@nan$$"`, `"@@infinity$$"`, and
+//!   `"@@-infinity$$"`, which is what `json_stringify` writes, and `json_parse` turns back
+//!   into the reals. (`serde_json` writes them as `null`.)
+//! - Integers outside `-(2^53 - 1)..=2^53 - 1`, which a GM real can't hold exactly, are
+//!   written as strings of their digits, so GML can at least get them back with `int64`.
+//!   Use a [GmInt53](crate::GmInt53) to make these an error instead.
+//!
+//! ## Taking data
+//!
+//! Exports which take a `json_stringify`'d struct can deserialize it with [from_gm_json],
+//! which reports where in the JSON a bad value was, instead of panicking somewhere in serde:
+//! ```
+//! use gm_ffi::{error::set_last_error, json::from_gm_json, GmPtr, OutputCode};
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Spawn {
+//!     kind: String,
+//!     x: f64,
+//!     y: f64,
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn spawn(json: GmPtr) -> OutputCode {
+//!     let spawn: Spawn = match from_gm_json(json) {
+//!         Ok(spawn) => spawn,
+//!         Err(e) => return set_last_error(e),
+//!     };
+//!
+//!     # let _ = (spawn.kind, spawn.x, spawn.y);
+//!     OutputCode::SUCCESS
+//! }
+//! ```
+
+use crate::{
+    error::set_last_error, Bridge, BridgeWriter, GmError, GmPtr, GmString, NeedsResize, OutputCode,
+};
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Error as _, Serialize};
+use serde_path_to_error::Segment;
+use std::fmt::Write;
+
+type Error = serde_json::Error;
+
+const MAX_EXACT: u64 = (1 << 53) - 1;
+
+/// Serializes `value` to JSON which `json_parse` reads without losing anything. See the
+/// [module docs](crate::json) for how this differs from `serde_json`.
+pub fn to_gm_json_string<T: Serialize + ?Sized>(value: &T) -> Result<String, GmError> {
+    let mut serializer = Serializer {
+        output: String::new(),
+    };
+    value
+        .serialize(&mut serializer)
+        .map_err(|e| GmError::custom(format_args!("couldn't serialize to json: {}", e)))?;
+
+    Ok(serializer.output)
+}
+
+/// Serializes `value` to JSON, like [to_gm_json_string], as a [GmString] ready to return to
+/// GM.
+pub fn to_gm_json<T: Serialize + ?Sized>(value: &T) -> Result<GmString, GmError> {
+    let json = to_gm_json_string(value)?;
+    let mut output = GmString::new();
+    output.set(&json);
+
+    Ok(output)
+}
+
+/// Serializes `value` to JSON, like [to_gm_json_string], and writes it into `bridge` as a u32
+/// length in bytes followed by the JSON, NUL-terminated and padded to a multiple of 4 bytes,
+/// so GML can read it with `buffer_string` from byte 4. This avoids the copy GM makes of a
+/// returned string, for big payloads.
+///
+/// If the bridge is too small, nothing is written, and this returns a [NeedsResize]. If
+/// `value` can't be serialized, this returns [OutputCode::FAILURE] and sets the
+/// [last error](crate::error::last_error).
+pub fn write_gm_json<T: Serialize + ?Sized>(value: &T, bridge: &mut Bridge) -> OutputCode {
+    let json = match to_gm_json_string(value) {
+        Ok(json) => json,
+        Err(e) => return set_last_error(e),
+    };
+    let units = 1 + BridgeWriter::padded_str_len(&json);
+
+    let mut writer = bridge.writer();
+    if writer.remaining() < units {
+        return NeedsResize(units * 4).into();
+    }
+
+    writer.write_u32(json.len() as u32);
+    writer.write_padded_str(&json);
+
+    OutputCode::SUCCESS
+}
+
+/// Deserializes a `T` from the JSON string GM passed in `json`, usually made with
+/// `json_stringify`.
+///
+/// If the JSON doesn't match `T`, the error includes the
+/// [JSON pointer](https://www.rfc-editor.org/rfc/rfc6901) to the offending value, such as
+/// `/enemies/0/hp: invalid type: string "lots", expected f64`.
+pub fn from_gm_json<T: DeserializeOwned>(json: GmPtr) -> Result<T, GmError> {
+    from_gm_json_str(json.try_to_str()?)
+}
+
+/// Deserializes a `T` from `json`, like [from_gm_json].
+pub fn from_gm_json_str<T: DeserializeOwned>(json: &str) -> Result<T, GmError> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    let value = serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let mut pointer = String::new();
+        for segment in e.path().iter() {
+            pointer.push('/');
+            match segment {
+                Segment::Seq { index } => write!(pointer, "{}", index).unwrap(),
+                Segment::Map { key } => {
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"))
+                }
+                Segment::Enum { variant } => pointer.push_str(variant),
+                Segment::Unknown => pointer.push('?'),
+            }
+        }
+
+        let at = if pointer.is_empty() { "/" } else { &pointer };
+        GmError::custom(format_args!(
+            "couldn't deserialize json at {}: {}",
+            at,
+            e.inner()
+        ))
+    })?;
+
+    Ok(value)
+}
+
+struct Serializer {
+    output: String,
+}
+
+impl Serializer {
+    fn write_str(&mut self, value: &str) {
+        self.output.push('"');
+        for c in value.chars() {
+            match c {
+                '"' => self.output.push_str("\\\""),
+                '\\' => self.output.push_str("\\\\"),
+                '\n' => self.output.push_str("\\n"),
+                '\r' => self.output.push_str("\\r"),
+                '\t' => self.output.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(self.output, "\\u{:04x}", c as u32).unwrap(),
+                c => self.output.push(c),
+            }
+        }
+        self.output.push('"');
+    }
+
+    fn write_int(&mut self, value: i128) {
+        if value.unsigned_abs() <= MAX_EXACT as u128 {
+            write!(self.output, "{}", value).unwrap();
+        } else {
+            write!(self.output, "\"{}\"", value).unwrap();
+        }
+    }
+
+    fn write_uint(&mut self, value: u128) {
+        if value <= MAX_EXACT as u128 {
+            write!(self.output, "{}", value).unwrap();
+        } else {
+            write!(self.output, "\"{}\"", value).unwrap();
+        }
+    }
+
+    fn write_float(&mut self, value: f64) {
+        if value.is_nan() {
+            self.output.push_str("\"@@nan$$\"");
+        } else if value == f64::INFINITY {
+            self.output.push_str("\"@@infinity$$\"");
+        } else if value == f64::NEG_INFINITY {
+            self.output.push_str("\"@@-infinity$$\"");
+        } else {
+            // `{:?}` is the shortest representation which round-trips
+            write!(self.output, "{:?}", value).unwrap();
+        }
+    }
+}
+
+/// A sequence, map, or struct part way through being written, which needs a comma before its
+/// next element if it's had one already.
+struct Compound<'a> {
+    ser: &'a mut Serializer,
+    first: bool,
+    // what closes a variant's wrapping object, after the sequence or map itself
+    close_variant: bool,
+}
+
+impl<'a> Compound<'a> {
+    fn new(ser: &'a mut Serializer, open: char, close_variant: bool) -> Self {
+        ser.output.push(open);
+        Self {
+            ser,
+            first: true,
+            close_variant,
+        }
+    }
+
+    fn comma(&mut self) {
+        if !self.first {
+            self.ser.output.push(',');
+        }
+        self.first = false;
+    }
+
+    fn end(self, close: char) {
+        self.ser.output.push(close);
+        if self.close_variant {
+            self.ser.output.push('}');
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.comma();
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.comma();
+        self.ser.write_str(key);
+        self.ser.output.push(':');
+        value.serialize(&mut *self.ser)
+    }
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.output.push_str(if v { "true" } else { "false" });
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.write_int(v.into());
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.write_int(v.into());
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.write_int(v.into());
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.write_int(v.into());
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        self.write_int(v);
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.write_uint(v.into());
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.write_uint(v.into());
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.write_uint(v.into());
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.write_uint(v.into());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        self.write_uint(v);
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.write_float(v.into());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.write_float(v);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.write_str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        let mut seq = Compound::new(self, '[', false);
+        for byte in v {
+            ser::SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        seq.end(']');
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.output.push_str("null");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write_str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let mut object = Compound::new(self, '{', false);
+        object.field(variant, value)?;
+        object.end('}');
+        Ok(())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(Compound::new(self, '[', false))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(Compound::new(self, '[', false))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        Ok(Compound::new(self, '[', false))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        self.output.push('{');
+        self.write_str(variant);
+        self.output.push(':');
+        Ok(Compound::new(self, '[', true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a>, Error> {
+        Ok(Compound::new(self, '{', false))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>, Error> {
+        Ok(Compound::new(self, '{', false))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>, Error> {
+        self.output.push('{');
+        self.write_str(variant);
+        self.output.push(':');
+        Ok(Compound::new(self, '{', true))
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, ']');
+        Ok(())
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, ']');
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, ']');
+        Ok(())
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, ']');
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.comma();
+
+        // JSON keys have to be strings, so numbers and the like are quoted
+        let mut inner = Serializer {
+            output: String::new(),
+        };
+        key.serialize(&mut inner)?;
+        match inner.output.as_bytes().first() {
+            Some(b'"') => self.ser.output.push_str(&inner.output),
+            Some(b'[' | b'{') | None => return Err(Error::custom("map keys must be strings")),
+            Some(_) => self.ser.write_str(&inner.output),
+        }
+
+        self.ser.output.push(':');
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, '}');
+        Ok(())
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, '}');
+        Ok(())
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self, '}');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: u32, h: u32 },
+    }
+
+    #[test]
+    fn writes_gm_friendly_json() {
+        let mut map = BTreeMap::new();
+        map.insert(3, vec![Shape::Point, Shape::Circle(0.5)]);
+        map.insert(7, vec![Shape::Rect { w: 2, h: 1 }]);
+        assert_eq!(
+            to_gm_json_string(&map).unwrap(),
+            r#"{"3":["Point",{"Circle":0.5}],"7":[{"Rect":{"w":2,"h":1}}]}"#
+        );
+
+        let awkward = (
+            f64::NAN,
+            -f64::INFINITY,
+            u64::MAX,
+            -(1i64 << 53) + 1,
+            "a\"\n\u{1}",
+        );
+        let json = to_gm_json_string(&awkward).unwrap();
+        assert_eq!(
+            json,
+            r#"["@@nan$$","@@-infinity$$","18446744073709551615",-9007199254740991,"a\"\n\u0001"]"#
+        );
+        assert_eq!(to_gm_json(&1.0).unwrap().as_str(), "1.0");
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Wave {
+        enemies: Vec<BTreeMap<String, f64>>,
+    }
+
+    #[test]
+    fn reports_json_pointers() {
+        let err = from_gm_json_str::<Wave>(r#"{"enemies":[{"hp":1},{"a/b":"lots"}]}"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "couldn't deserialize json at /enemies/1/a~1b: invalid type: string \"lots\", expected f64 at line 1 column 34"
+        );
+
+        let json = std::ffi::CString::new(r#"{"enemies":[]}"#).unwrap();
+        assert!(from_gm_json::<Wave>(GmPtr::new(json.as_ptr())).is_ok());
+        assert_eq!(
+            from_gm_json::<Wave>(GmPtr::new(core::ptr::null())).unwrap_err(),
+            GmError::NullPointer
+        );
+    }
+}
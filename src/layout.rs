@@ -0,0 +1,315 @@
+//! Structs laid out exactly as GML's `buffer_write` writes them, with one definition shared by
+//! both sides.
+//!
+//! Derive [GmBufferSerialize] for the struct, and paste the output of its `gml_functions()`
+//! into a script in the project:
+//! ```
+//! use gm_ffi::layout::GmBufferSerialize;
+//!
+//! /// An enemy for GML to spawn.
+//! #[derive(Debug, PartialEq, GmBufferSerialize)]
+//! pub struct EnemySpawn {
+//!     pub kind: String,
+//!     pub x: f32,
+//!     pub y: f32,
+//!     pub hp: u16,
+//!     pub elite: bool,
+//! }
+//!
+//! let spawn = EnemySpawn { kind: "slime".into(), x: 16.0, y: 32.0, hp: 5, elite: false };
+//! let bytes = spawn.to_gm_bytes();
+//! assert_eq!(bytes.len(), 6 + 4 + 4 + 2 + 1);
+//! assert_eq!(EnemySpawn::from_gm_bytes(&bytes), Some(spawn));
+//!
+//! assert!(EnemySpawn::gml_functions().contains("function enemy_spawn_read(_buffer) {"));
+//! ```
+//! ```gml
+//! var _spawn = enemy_spawn_read(global.bridge);
+//! instance_create_layer(_spawn.x, _spawn.y, "Instances", obj_enemy, _spawn);
+//! ```
+//! GM pads values written to buffers with an alignment above 1, so buffers shared this way
+//! should be created with an alignment of 1.
+
+use crate::{BufferCursor, GmBuffer, NeedsResize, OutputCode};
+
+/// Derives [GmBufferSerialize] for a struct with named fields, writing its fields in order,
+/// with no padding, exactly as GML's `buffer_write` would.
+///
+/// Every field's type must implement `GmBufferSerialize` too. The struct also gets a
+/// `gml_functions()` function, which returns a pair of GML functions, `<name>_write(buffer,
+/// value)` and `<name>_read(buffer)`, where `<name>` is the struct's name in snake case. These
+/// read and write GML structs with the same field names, so the two sides can't drift apart.
+/// [gm_buffer_struct](crate::gm_buffer_struct) declares a struct with this derive.
+///
+/// The generated code names this crate as `gm_ffi`, so it can't be renamed in `Cargo.toml`.
+pub use gm_ffi_derive::GmBufferSerialize;
+
+/// A value with a fixed layout in a GM buffer, matching what `buffer_write` writes and
+/// `buffer_read` reads.
+///
+/// This is implemented for the integer and float types GM buffers support, for `bool`
+/// (`buffer_bool`), for `String` (`buffer_string`, which is NUL-terminated), and can be
+/// derived for structs, which can be nested.
+pub trait GmBufferSerialize: Sized {
+    /// Appends the value to `output`.
+    fn write_gm(&self, output: &mut Vec<u8>);
+
+    /// Reads a value from `cursor`, returning `None`, without advancing, if it's cut off.
+    fn read_gm(cursor: &mut BufferCursor<'_>) -> Option<Self>;
+
+    /// Returns a GML statement which writes the GML expression `value` into the buffer
+    /// `buffer`.
+    fn gml_write(buffer: &str, value: &str) -> String;
+
+    /// Returns a GML expression which reads a value from the buffer `buffer`.
+    fn gml_read(buffer: &str) -> String;
+
+    /// Returns the value as bytes.
+    fn to_gm_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        self.write_gm(&mut output);
+        output
+    }
+
+    /// Reads a value from the start of `bytes`, such as a [GmBuffer]. Trailing bytes are
+    /// ignored.
+    fn from_gm_bytes(bytes: &[u8]) -> Option<Self> {
+        Self::read_gm(&mut BufferCursor::new(bytes))
+    }
+
+    /// Writes the value into the start of `buffer`, and returns the number of bytes written as
+    /// an [OutputCode::custom]. If the buffer is too small, nothing is written, and this
+    /// returns a [NeedsResize].
+    fn write_into(&self, buffer: &mut GmBuffer<u8>) -> OutputCode {
+        let bytes = self.to_gm_bytes();
+
+        let Some(output) = buffer.as_bytes_mut().get_mut(..bytes.len()) else {
+            return NeedsResize(bytes.len()).into();
+        };
+        output.copy_from_slice(&bytes);
+
+        OutputCode::custom(bytes.len() as f64)
+    }
+}
+
+macro_rules! primitive_impls {
+    ($($ty:ty => $read:ident, $gm_type:literal;)*) => {
+        $(
+            impl GmBufferSerialize for $ty {
+                fn write_gm(&self, output: &mut Vec<u8>) {
+                    output.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_gm(cursor: &mut BufferCursor<'_>) -> Option<Self> {
+                    cursor.$read()
+                }
+
+                fn gml_write(buffer: &str, value: &str) -> String {
+                    format!("buffer_write({}, {}, {});", buffer, $gm_type, value)
+                }
+
+                fn gml_read(buffer: &str) -> String {
+                    format!("buffer_read({}, {})", buffer, $gm_type)
+                }
+            }
+        )*
+    };
+}
+
+primitive_impls! {
+    u8 => read_u8, "buffer_u8";
+    i8 => read_i8, "buffer_s8";
+    u16 => read_u16_le, "buffer_u16";
+    i16 => read_i16_le, "buffer_s16";
+    u32 => read_u32_le, "buffer_u32";
+    i32 => read_i32_le, "buffer_s32";
+    u64 => read_u64_le, "buffer_u64";
+    f32 => read_f32_le, "buffer_f32";
+    f64 => read_f64_le, "buffer_f64";
+}
+
+impl GmBufferSerialize for bool {
+    fn write_gm(&self, output: &mut Vec<u8>) {
+        output.push(*self as u8);
+    }
+
+    fn read_gm(cursor: &mut BufferCursor<'_>) -> Option<Self> {
+        cursor.read_u8().map(|b| b != 0)
+    }
+
+    fn gml_write(buffer: &str, value: &str) -> String {
+        format!("buffer_write({}, buffer_bool, {});", buffer, value)
+    }
+
+    fn gml_read(buffer: &str) -> String {
+        format!("buffer_read({}, buffer_bool)", buffer)
+    }
+}
+
+impl GmBufferSerialize for String {
+    /// Writes the string and a NUL. GM strings can't hold NULs, so if the string contains one,
+    /// it's cut off there.
+    fn write_gm(&self, output: &mut Vec<u8>) {
+        let bytes = self.as_bytes();
+        let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+        output.extend_from_slice(&bytes[..len]);
+        output.push(0);
+    }
+
+    /// Reads up to the next NUL. Invalid UTF-8 is replaced, as GM would.
+    fn read_gm(cursor: &mut BufferCursor<'_>) -> Option<Self> {
+        let start = cursor.position();
+        let rest = cursor.read_bytes(cursor.remaining())?;
+
+        let Some(len) = rest.iter().position(|&b| b == 0) else {
+            cursor.set_position(start);
+            return None;
+        };
+        cursor.set_position(start + len + 1);
+
+        Some(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    fn gml_write(buffer: &str, value: &str) -> String {
+        format!("buffer_write({}, buffer_string, {});", buffer, value)
+    }
+
+    fn gml_read(buffer: &str) -> String {
+        format!("buffer_read({}, buffer_string)", buffer)
+    }
+}
+
+/// Builds the prefix of a struct's GML functions, like `enemy_spawn` from `EnemySpawn`.
+#[doc(hidden)]
+pub fn gml_function_prefix(type_name: &str) -> String {
+    let mut output = crate::enums::gml_constant_name(type_name, "").to_lowercase();
+    output.pop(); // the `_` before the empty item
+    output
+}
+
+/// Builds a derived struct's `gml_functions()`, from each field's name, `gml_write`, and
+/// `gml_read`.
+#[doc(hidden)]
+pub fn gml_struct_functions(type_name: &str, fields: &[(&str, String, String)]) -> String {
+    let prefix = gml_function_prefix(type_name);
+
+    let mut output = format!(
+        "/// @param {{Id.Buffer}} _buffer\n/// @param {{Struct}} _value\nfunction {}_write(_buffer, _value) {{\n",
+        prefix
+    );
+    for (_, write, _) in fields {
+        output.push_str(&format!("    {}\n", write));
+    }
+
+    output.push_str(&format!(
+        "}}\n\n/// @param {{Id.Buffer}} _buffer\nfunction {}_read(_buffer) {{\n    var _value = {{}};\n",
+        prefix
+    ));
+    for (name, _, read) in fields {
+        output.push_str(&format!("    _value.{} = {};\n", name, read));
+    }
+    output.push_str("    return _value;\n}\n");
+
+    output
+}
+
+/// Declares a struct which derives [GmBufferSerialize](macro@crate::layout::GmBufferSerialize),
+/// for code which would rather not name the derive.
+/// ```
+/// gm_ffi::gm_buffer_struct! {
+///     pub struct Tile {
+///         pub id: u16,
+///         pub solid: bool,
+///     }
+/// }
+///
+/// assert_eq!(
+///     Tile::gml_functions(),
+///     "/// @param {Id.Buffer} _buffer
+/// /// @param {Struct} _value
+/// function tile_write(_buffer, _value) {
+///     buffer_write(_buffer, buffer_u16, _value.id);
+///     buffer_write(_buffer, buffer_bool, _value.solid);
+/// }
+///
+/// /// @param {Id.Buffer} _buffer
+/// function tile_read(_buffer) {
+///     var _value = {};
+///     _value.id = buffer_read(_buffer, buffer_u16);
+///     _value.solid = buffer_read(_buffer, buffer_bool);
+///     return _value;
+/// }
+/// "
+/// );
+/// ```
+#[macro_export]
+macro_rules! gm_buffer_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($(#[$fmeta:meta])* $fvis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive($crate::layout::GmBufferSerialize)]
+        $vis struct $name {
+            $($(#[$fmeta])* $fvis $field: $ty),*
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::gm_buffer_struct! {
+        #[derive(Debug, PartialEq)]
+        struct Point {
+            x: i16,
+            y: i16,
+        }
+    }
+
+    #[derive(Debug, PartialEq, GmBufferSerialize)]
+    struct NamedPoint {
+        name: String,
+        at: Point,
+    }
+
+    #[derive(Debug, PartialEq, GmBufferSerialize)]
+    struct Pair<T> {
+        r#type: u8,
+        value: T,
+    }
+
+    #[test]
+    fn matches_buffer_write() {
+        let value = NamedPoint {
+            name: "spawn".into(),
+            at: Point { x: -1, y: 2 },
+        };
+        let bytes = value.to_gm_bytes();
+        assert_eq!(bytes, b"spawn\0\xff\xff\x02\x00");
+        assert_eq!(NamedPoint::from_gm_bytes(&bytes), Some(value));
+
+        let mut cursor = BufferCursor::new(&bytes[..8]);
+        assert_eq!(NamedPoint::read_gm(&mut cursor), None);
+        assert_eq!(cursor.position(), 0);
+
+        let gml = NamedPoint::gml_functions();
+        assert!(gml.contains("    point_write(_buffer, _value.at);\n"));
+        assert!(gml.contains("    _value.at = point_read(_buffer);\n"));
+        assert!(
+            Point::gml_functions().contains("    _value.x = buffer_read(_buffer, buffer_s16);\n")
+        );
+
+        let pair = Pair {
+            r#type: 1,
+            value: 2.5f32,
+        };
+        assert_eq!(Pair::from_gm_bytes(&pair.to_gm_bytes()), Some(pair));
+        assert!(Pair::<f32>::gml_functions()
+            .contains("    _value.type = buffer_read(_buffer, buffer_u8);\n"));
+    }
+}
@@ -0,0 +1,154 @@
+//! Reports of native resources GML forgot to free, for tracking down leaks in long play
+//! sessions.
+//! ```
+//! use gm_ffi::{leaks::LeakReport, BufferRegistry, HandleRegistry};
+//! use std::sync::Mutex;
+//!
+//! static ENEMIES: Mutex<HandleRegistry<String>> = Mutex::new(HandleRegistry::new());
+//!
+//! #[no_mangle]
+//! pub extern "C" fn report_leaks() {
+//!     LeakReport::new()
+//!         .handles("enemies", &ENEMIES.lock().unwrap())
+//!         .log();
+//! }
+//! ```
+
+use crate::{Bridge, BridgeWriter, BufferRegistry, HandleRegistry, NeedsResize, OutputCode};
+use std::fmt;
+
+/// A report on everything still held in some [HandleRegistry]s and [BufferRegistry]s.
+///
+/// Each registry gets a section with a count, and a line per leak with its age. In debug
+/// builds, each leak also has a backtrace of where it was created.
+#[derive(Debug, Clone, Default)]
+pub struct LeakReport {
+    sections: Vec<Section>,
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    name: String,
+    kind: &'static str,
+    count: usize,
+    report: String,
+}
+
+impl LeakReport {
+    /// Creates an empty report.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a section for the handles still in `registry`.
+    pub fn handles<T>(mut self, name: &str, registry: &HandleRegistry<T>) -> Self {
+        self.sections.push(Section {
+            name: name.to_string(),
+            kind: "handle",
+            count: registry.len(),
+            report: registry.leak_report(),
+        });
+        self
+    }
+
+    /// Adds a section for the buffers still in `registry`.
+    pub fn buffers<T>(mut self, name: &str, registry: &BufferRegistry<T>) -> Self {
+        self.sections.push(Section {
+            name: name.to_string(),
+            kind: "buffer",
+            count: registry.len(),
+            report: registry.leak_report(),
+        });
+        self
+    }
+
+    /// The number of leaks across every section.
+    pub fn total(&self) -> usize {
+        self.sections.iter().map(|s| s.count).sum()
+    }
+
+    /// Returns if nothing leaked.
+    pub fn is_empty(&self) -> bool {
+        self.total() == 0
+    }
+
+    /// Prints the report with [gm_println](crate::gm_println), a line at a time.
+    pub fn log(&self) {
+        for line in self.to_string().lines() {
+            crate::gm_println!("{}", line);
+        }
+    }
+
+    /// Writes the report into `bridge`, as a u32 length in bytes followed by the report,
+    /// NUL-terminated and padded to a multiple of 4 bytes, so GML can read it with
+    /// `buffer_string` from byte 4.
+    ///
+    /// If the bridge is too small, nothing is written, and this returns a [NeedsResize].
+    pub fn write_into(&self, bridge: &mut Bridge) -> OutputCode {
+        let report = self.to_string();
+        let units = 1 + BridgeWriter::padded_str_len(&report);
+
+        let mut writer = bridge.writer();
+        if writer.remaining() < units {
+            return NeedsResize(units * 4).into();
+        }
+
+        writer.write_u32(report.len() as u32);
+        writer.write_padded_str(&report);
+
+        OutputCode::SUCCESS
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in &self.sections {
+            let plural = if section.count == 1 { "" } else { "s" };
+            writeln!(
+                f,
+                "{}: {} {}{} leaked",
+                section.name, section.count, section.kind, plural
+            )?;
+            for line in section.report.lines() {
+                writeln!(f, "  {}", line)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_bridge;
+
+    #[test]
+    fn reports_sections() {
+        let mut handles = HandleRegistry::new();
+        handles.insert(());
+        let buffers = BufferRegistry::<u8>::new();
+
+        let report = LeakReport::new()
+            .handles("enemies", &handles)
+            .buffers("textures", &buffers);
+        assert_eq!(report.total(), 1);
+
+        let text = report.to_string();
+        assert!(text.starts_with("enemies: 1 handle leaked\n  handle 4294967296: 1 reference(s)"));
+        assert!(text.ends_with("textures: 0 buffers leaked\n"));
+
+        let (backing, mut bridge) = test_bridge();
+        let empty = HandleRegistry::<()>::new();
+        let code = LeakReport::new()
+            .handles("none", &empty)
+            .write_into(&mut bridge);
+        assert!(code.is_success());
+        assert_eq!(backing[..2], [23, u32::from_le_bytes(*b"none")]);
+
+        let code = LeakReport::new()
+            .handles(&"x".repeat(2000), &empty)
+            .write_into(&mut bridge);
+        assert_eq!(code.required_len(), Some(2024));
+    }
+}
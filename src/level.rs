@@ -0,0 +1,294 @@
+//! Leveled logging macros, and a `RUST_LOG`-style filter which can be changed while the game
+//! is running.
+//!
+//! The filter is a comma separated list of directives, each either a level, which sets the
+//! default, or `module=level`, which sets the level for a module and everything inside it:
+//! ```
+//! use gm_ffi::level::{self, Level};
+//!
+//! level::set_filter("warn,my_extension::net=trace").unwrap();
+//! assert!(level::enabled(Level::Trace, "my_extension::net::http"));
+//! assert!(!level::enabled(Level::Info, "my_extension::ai"));
+//!
+//! gm_ffi::gm_warn!("this shows up");
+//! gm_ffi::gm_debug!("this doesn't");
+//! ```
+//! Without any filter set, everything at [Level::Info] or above is shown.
+
+use core::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::RwLock;
+
+/// How important a message is. Lower is more important.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(u8)]
+pub enum Level {
+    /// Nothing is shown. Only used in filters.
+    Off = 0,
+    /// Something failed.
+    Error = 1,
+    /// Something odd happened.
+    Warn = 2,
+    /// Normal, useful output.
+    Info = 3,
+    /// Output for tracking down problems.
+    Debug = 4,
+    /// Very verbose output.
+    Trace = 5,
+}
+
+impl Level {
+    /// Returns the level's name, like `"INFO"`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl core::str::FromStr for Level {
+    type Err = FilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(FilterError(s.trim().to_string())),
+        }
+    }
+}
+
+/// Returned when a filter has a level we don't recognize in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError(pub String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not a log level", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[derive(Debug)]
+struct Filter {
+    default: Level,
+    // sorted longest first, so the most specific module wins
+    modules: Vec<(String, Level)>,
+}
+
+static FILTER: RwLock<Filter> = RwLock::new(Filter {
+    default: Level::Info,
+    modules: Vec::new(),
+});
+
+// the most verbose level any directive allows, so filtered out messages are just an atomic load
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+/// Replaces the filter. On an error, the filter is left as it was.
+pub fn set_filter(filter: &str) -> Result<(), FilterError> {
+    let mut default = Level::Info;
+    let mut modules = Vec::new();
+
+    for directive in filter.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        match directive.split_once('=') {
+            Some((module, level)) => modules.push((module.trim().to_string(), level.parse()?)),
+            None => default = directive.parse()?,
+        }
+    }
+    modules.sort_by_key(|(m, _): &(String, Level)| core::cmp::Reverse(m.len()));
+
+    let max = modules.iter().map(|(_, l)| *l).fold(default, Ord::max);
+    *FILTER.write().unwrap_or_else(|e| e.into_inner()) = Filter { default, modules };
+    MAX_LEVEL.store(max as u8, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Sets the filter from the environment variable `var` (like `"GM_LOG"`), if it's set.
+pub fn set_filter_from_env(var: &str) -> Result<(), FilterError> {
+    match std::env::var(var) {
+        Ok(filter) => set_filter(&filter),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Returns if a message at `level` from the module `target` would be shown.
+pub fn enabled(level: Level, target: &str) -> bool {
+    if level == Level::Off || level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    let filter = FILTER.read().unwrap_or_else(|e| e.into_inner());
+    let allowed = filter
+        .modules
+        .iter()
+        .find(|(module, _)| {
+            target
+                .strip_prefix(module.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+        })
+        .map_or(filter.default, |(_, level)| *level);
+
+    level <= allowed
+}
+
+/// Logs at `level`, if the filter allows it. This is what the leveled macros call.
+#[doc(hidden)]
+pub fn log(level: Level, target: &str, args: fmt::Arguments<'_>) {
+    if !enabled(level, target) {
+        return;
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    {
+        let message = args.to_string();
+        crate::panic::record_line(&format!("[{} {}] {}", level, target, message));
+        crate::GmStdOut::stdout().write_record(level, target, &message);
+    }
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    crate::gm_println!("[{} {}] {}", level, target, args);
+}
+
+/// Formats a message as a JSON object for adam's structured mode.
+#[cfg_attr(
+    not(any(target_os = "macos", target_os = "windows", test)),
+    allow(dead_code)
+)]
+pub(crate) fn json_frame(level: Level, target: &str, message: &str) -> String {
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    format!(
+        r#"{{"level":"{}","target":{},"message":{},"timestamp":{:.3}}}"#,
+        level,
+        json_string(target),
+        json_string(message),
+        timestamp
+    )
+}
+
+fn json_string(input: &str) -> String {
+    use fmt::Write;
+
+    let mut output = String::with_capacity(input.len() + 2);
+    output.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(output, "\\u{:04x}", c as u32);
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+
+    output
+}
+
+/// Logs at a [Level] through [gm_println](crate::gm_println), if the [filter](crate::level)
+/// allows it. The leveled macros, like [gm_info](crate::gm_info), are usually nicer.
+#[macro_export]
+macro_rules! gm_log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::level::log($level, ::core::module_path!(), ::core::format_args!($($arg)*))
+    };
+}
+
+/// Logs an error through [gm_println](crate::gm_println), if the [filter](crate::level) allows it.
+#[macro_export]
+macro_rules! gm_error {
+    ($($arg:tt)*) => {
+        $crate::gm_log!($crate::level::Level::Error, $($arg)*)
+    };
+}
+
+/// Logs a warning through [gm_println](crate::gm_println), if the [filter](crate::level) allows it.
+#[macro_export]
+macro_rules! gm_warn {
+    ($($arg:tt)*) => {
+        $crate::gm_log!($crate::level::Level::Warn, $($arg)*)
+    };
+}
+
+/// Logs info through [gm_println](crate::gm_println), if the [filter](crate::level) allows it.
+#[macro_export]
+macro_rules! gm_info {
+    ($($arg:tt)*) => {
+        $crate::gm_log!($crate::level::Level::Info, $($arg)*)
+    };
+}
+
+/// Logs debug output through [gm_println](crate::gm_println), if the [filter](crate::level) allows it.
+#[macro_export]
+macro_rules! gm_debug {
+    ($($arg:tt)*) => {
+        $crate::gm_log!($crate::level::Level::Debug, $($arg)*)
+    };
+}
+
+/// Logs trace output through [gm_println](crate::gm_println), if the [filter](crate::level) allows it.
+#[macro_export]
+macro_rules! gm_trace {
+    ($($arg:tt)*) => {
+        $crate::gm_log!($crate::level::Level::Trace, $($arg)*)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters() {
+        assert_eq!(
+            set_filter("info,bogus=loud"),
+            Err(FilterError("loud".to_string()))
+        );
+
+        set_filter("error, game::net=debug ,game::net::http=off").unwrap();
+        assert!(enabled(Level::Error, "game"));
+        assert!(!enabled(Level::Warn, "game"));
+        assert!(enabled(Level::Debug, "game::net::socket"));
+        assert!(!enabled(Level::Error, "game::net::http"));
+        assert!(!enabled(Level::Debug, "game::network"));
+
+        set_filter("").unwrap();
+        assert!(enabled(Level::Info, "game"));
+        crate::gm_info!("{} still compiles", "logging");
+    }
+
+    #[test]
+    fn json_frames() {
+        let frame = json_frame(Level::Warn, "game", "say \"hi\"\n\u{1}");
+        assert!(frame.starts_with(
+            r#"{"level":"WARN","target":"game","message":"say \"hi\"\n\u0001","timestamp":"#
+        ));
+
+        let parsed: serde_json::Value = serde_json::from_str(&frame).unwrap();
+        assert_eq!(parsed["message"], "say \"hi\"\n\u{1}");
+    }
+}
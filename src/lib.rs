@@ -1,4 +1,21 @@
 //! A Rust crate to interface between GameMaker and Rust.
+//!
+//! # Naming exports
+//!
+//! The `export_*!` macros, like [export_tick] and [export_callbacks], generate
+//! `#[no_mangle]` functions for the extension editor to declare. Each has default names, like
+//! `gm_tick`, but GM looks exports up by name across every extension in a project, so if more
+//! than one extension uses this crate, the defaults collide. Every macro takes its own names
+//! after a `=>`, or in place of its arguments when it has none:
+//! ```
+//! gm_ffi::export_tick!(my_extension_tick);
+//! gm_ffi::export_callbacks!(my_extension_register_callback, my_extension_dispatch_callbacks);
+//! ```
+//! Macros over a `static`, like [export_hotvalues], need their own names for each `static`
+//! too. GML helpers which call the default names, like [callbacks::GML_HELPERS], have to be
+//! renamed to match.
+//!
+//! Every generated export catches panics, as with [catch_ffi], so they can't unwind into GM.
 
 // lets us compare floats in asserts
 #![cfg_attr(test, allow(clippy::float_cmp))]
@@ -8,10 +25,82 @@
 
 use core::ffi::c_char;
 
+// lets the crate (and its tests) use its own derives, which name `::gm_ffi`
+extern crate self as gm_ffi;
+
+pub mod arena;
+pub mod async_event;
+pub mod batch;
+pub mod callbacks;
+pub mod color;
+pub mod cursor;
+pub mod datetime;
+pub mod enums;
+pub mod error;
+pub mod flags;
+pub mod handles;
+pub mod hotvalues;
+pub mod ini;
+pub mod instance;
+pub mod int53;
+pub mod jobs;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod layout;
+pub mod leaks;
+pub mod level;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod math;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod pagination;
+pub mod panic;
+pub mod pool;
+pub mod registry;
+pub mod returns;
+pub mod ring;
+pub mod runner;
+pub mod simclock;
+pub mod snapshot;
+pub mod state;
+pub mod string;
+pub mod tick;
+#[cfg(feature = "tracing")]
+pub mod tracing_layer;
+#[cfg(feature = "validate")]
+pub mod validate;
+pub mod view;
+pub mod warnings;
+
+pub use arena::GmArena;
+pub use async_event::AsyncEvent;
+pub use color::GmColor;
+pub use cursor::{BufferCursor, Endian};
+pub use datetime::GmDateTime;
+pub use enums::GmEnum;
+pub use error::GmError;
+pub use flags::GmFlags;
+pub use handles::{GmHandle, HandleRegistry};
+pub use instance::{GmInstanceId, InstanceStateMap};
+pub use int53::GmInt53;
+pub use layout::GmBufferSerialize;
+pub use math::{GmMatrix4, GmVec2, GmVec3, GmVec4};
+pub use pagination::ResultSets;
+pub use pool::BufferPool;
+pub use registry::BufferRegistry;
+pub use returns::GmReturn;
+pub use ring::GmRingBuffer;
+pub use snapshot::OwnedSnapshot;
+pub use string::GmString;
+pub use view::View2d;
+
 /// A status code the represents the outcome of a Rust-side function,
 /// intended to be sent back to GameMaker.
 #[derive(Debug, Copy, Clone, PartialEq)]
 #[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct OutputCode(f64);
 
 impl OutputCode {
@@ -22,9 +111,160 @@ impl OutputCode {
 
     /// Creates a custom OutputCode. This can mean whatever you want it to mean,
     /// for example, returning the number of bytes written into a shared buffer.
+    ///
+    /// Negative whole numbers are reserved for [NeedsResize], so
+    /// [required_len](OutputCode::required_len) reads them as one.
     pub const fn custom(code: f64) -> Self {
         Self(code)
     }
+
+    /// Returns if this is [OutputCode::SUCCESS].
+    pub fn is_success(self) -> bool {
+        self.0 == Self::SUCCESS.0
+    }
+
+    /// Returns if this is [OutputCode::FAILURE].
+    pub fn is_failure(self) -> bool {
+        self.0 == Self::FAILURE.0
+    }
+
+    /// Returns the inner f64, as GM will see it.
+    pub const fn as_f64(self) -> f64 {
+        self.0
+    }
+
+    /// If this code came from a [NeedsResize], returns the length the buffer needs to be.
+    ///
+    /// That's any negative whole number, so other negative codes, like `-0.5` or `-0.0`,
+    /// return `None`.
+    pub fn required_len(self) -> Option<usize> {
+        (self.0 <= -1.0 && self.0.fract() == 0.0).then(|| (-self.0) as usize)
+    }
+
+    const PAYLOAD_RANGE: f64 = 4_294_967_296.0; // 2^32
+
+    /// Creates a successful code carrying a 32-bit value. Unlike [custom](OutputCode::custom),
+    /// this can't be mistaken for [SUCCESS](OutputCode::SUCCESS), [FAILURE](OutputCode::FAILURE),
+    /// or an [error](OutputCode::error), even if `value` is 0 or 1.
+    ///
+    /// The kind and payload are packed as `kind * 2^32 + payload`, which an f64 holds exactly.
+    /// GML can unpack them with [GML_HELPERS](OutputCode::GML_HELPERS).
+    pub const fn value(value: u32) -> Self {
+        Self(Self::PAYLOAD_RANGE + value as f64)
+    }
+
+    /// Creates a failed code carrying a 32-bit error code. See [value](OutputCode::value).
+    pub const fn error(code: u32) -> Self {
+        Self(2.0 * Self::PAYLOAD_RANGE + code as f64)
+    }
+
+    /// Returns whether this code was made with [value](OutputCode::value),
+    /// [error](OutputCode::error), or something else.
+    pub fn kind(self) -> CodeKind {
+        if self.0.fract() != 0.0 || self.0 < Self::PAYLOAD_RANGE {
+            CodeKind::Other
+        } else if self.0 < 2.0 * Self::PAYLOAD_RANGE {
+            CodeKind::Value
+        } else if self.0 < 3.0 * Self::PAYLOAD_RANGE {
+            CodeKind::Error
+        } else {
+            CodeKind::Other
+        }
+    }
+
+    /// Returns the payload of a code made with [value](OutputCode::value) or
+    /// [error](OutputCode::error).
+    pub fn payload(self) -> Option<u32> {
+        match self.kind() {
+            CodeKind::Value | CodeKind::Error => Some((self.0 % Self::PAYLOAD_RANGE) as u32),
+            CodeKind::Other => None,
+        }
+    }
+
+    /// GML functions for unpacking codes made with [value](OutputCode::value) and
+    /// [error](OutputCode::error). Paste these into a script in the project.
+    pub const GML_HELPERS: &'static str = r#"/// @param {Real} _code
+function output_code_is_value(_code) {
+    return _code >= 4294967296 && _code < 8589934592;
+}
+
+/// @param {Real} _code
+function output_code_is_error(_code) {
+    return _code >= 8589934592 && _code < 12884901888;
+}
+
+/// @param {Real} _code
+function output_code_payload(_code) {
+    return _code mod 4294967296;
+}
+"#;
+}
+
+/// What an [OutputCode] carries, as returned by [OutputCode::kind].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CodeKind {
+    /// Made with [OutputCode::value].
+    Value,
+    /// Made with [OutputCode::error].
+    Error,
+    /// Anything else, such as [OutputCode::SUCCESS], a [NeedsResize], or a custom code.
+    Other,
+}
+
+/// Returned when the buffer GM gave us is too small for the output, carrying the length,
+/// in bytes, that the buffer needs to be.
+///
+/// This converts into an [OutputCode] holding the *negated* required length, so it can't be
+/// confused with [OutputCode::SUCCESS] or a custom code counting bytes written. GML can then
+/// `buffer_resize` and call again. See [gml_retry_wrapper](NeedsResize::gml_retry_wrapper).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NeedsResize(pub usize);
+
+impl NeedsResize {
+    /// Generates a GML function, named `<export_name>_retry`, which calls `export_name` with a
+    /// buffer's address and size, and if it returns a [NeedsResize], resizes the buffer and
+    /// calls it once more.
+    ///
+    /// This assumes the export's signature is `(buffer_address, buffer_size)`.
+    pub fn gml_retry_wrapper(export_name: &str) -> String {
+        format!(
+            r#"/// @param {{Id.Buffer}} _buffer
+function {name}_retry(_buffer) {{
+    var _code = {name}(buffer_get_address(_buffer), buffer_get_size(_buffer));
+    if (_code < 0) {{
+        buffer_resize(_buffer, -_code);
+        _code = {name}(buffer_get_address(_buffer), buffer_get_size(_buffer));
+    }}
+    return _code;
+}}
+"#,
+            name = export_name
+        )
+    }
+}
+
+impl From<NeedsResize> for OutputCode {
+    /// A `NeedsResize(0)` asks for 1 byte instead, since `-0.0` would be
+    /// [OutputCode::FAILURE].
+    fn from(o: NeedsResize) -> Self {
+        OutputCode(-(o.0.max(1) as f64))
+    }
+}
+
+impl From<bool> for OutputCode {
+    fn from(o: bool) -> Self {
+        if o {
+            OutputCode::SUCCESS
+        } else {
+            OutputCode::FAILURE
+        }
+    }
+}
+
+impl<T> From<Option<T>> for OutputCode {
+    fn from(o: Option<T>) -> Self {
+        o.is_some().into()
+    }
 }
 
 // blanket implementation
@@ -68,9 +308,176 @@ impl GmPtr {
     ///
     /// # Saftey
     /// Assumes that the pointer being used is valid as a c_str pointer.
+    ///
+    /// The `'static` here is a lie: GM frees strings it passes us once the call returns. Prefer
+    /// [to_str_in](GmPtr::to_str_in), which can't outlive the call.
     pub fn to_str(self) -> Result<&'static str, core::str::Utf8Error> {
         unsafe { core::ffi::CStr::from_ptr(self.0) }.to_str()
     }
+
+    /// Transforms the inner value into an &str which borrows from `guard`, so it can't be
+    /// stashed somewhere that outlives the current call.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer being used is valid as a c_str pointer.
+    pub fn to_str_in(self, guard: &CallGuard) -> Result<&str, core::str::Utf8Error> {
+        let _ = guard;
+        unsafe { core::ffi::CStr::from_ptr(self.0) }.to_str()
+    }
+
+    /// Like [to_str](GmPtr::to_str), but returns an error instead of crashing if the pointer
+    /// is null, which is what GM sends for `undefined` and `pointer_null`.
+    pub fn try_to_str(self) -> Result<&'static str, GmStrError> {
+        if self.0.is_null() {
+            return Err(GmStrError::Null);
+        }
+
+        unsafe { core::ffi::CStr::from_ptr(self.0) }
+            .to_str()
+            .map_err(GmStrError::Utf8)
+    }
+
+    /// Like [try_to_str](GmPtr::try_to_str), but gives up if no NUL turns up in the first
+    /// `max_len` bytes, rather than reading off into whatever memory follows.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer, if not null, is valid for reads up to its NUL or `max_len`
+    /// bytes, whichever comes first.
+    pub fn try_to_str_max(self, max_len: usize) -> Result<&'static str, GmStrError> {
+        if self.0.is_null() {
+            return Err(GmStrError::Null);
+        }
+
+        let mut len = 0;
+        loop {
+            if len == max_len {
+                return Err(GmStrError::Unterminated { max_len });
+            }
+            if unsafe { *self.0.add(len) } == 0 {
+                break;
+            }
+            len += 1;
+        }
+
+        let bytes = unsafe { core::slice::from_raw_parts(self.0 as *const u8, len) };
+        core::str::from_utf8(bytes).map_err(GmStrError::Utf8)
+    }
+
+    /// Views `len` bytes of binary data behind the pointer. Unlike the string methods, this
+    /// doesn't stop at zero bytes.
+    ///
+    /// # Safety
+    /// The pointer must be valid for reads of `len` bytes, and the returned slice mustn't be
+    /// used after GM frees that memory, which for arguments is once the call returns.
+    pub unsafe fn to_bytes<'a>(self, len: usize) -> &'a [u8] {
+        if len == 0 {
+            return &[];
+        }
+
+        unsafe { core::slice::from_raw_parts(self.0 as *const u8, len) }
+    }
+
+    /// Copies the inner value into a String, replacing any invalid UTF-8 with `U+FFFD`.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer being used is valid as a c_str pointer.
+    pub fn to_str_lossy(self) -> String {
+        unsafe { core::ffi::CStr::from_ptr(self.0) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Representation of a pointer to a NUL-terminated UTF-16 string, which some runner paths
+/// (mostly on Windows) hand over instead of UTF-8.
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GmWidePtr(*const u16);
+
+impl GmWidePtr {
+    /// Creates a new GmWidePtr based on the given pointer.
+    pub fn new(ptr: *const u16) -> Self {
+        Self(ptr)
+    }
+
+    /// Returns a copy of the inner value.
+    pub const fn inner(self) -> *const u16 {
+        self.0
+    }
+
+    /// Returns the string's code units, not including the NUL, borrowed for the current call.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer being used is a valid, NUL-terminated UTF-16 string.
+    pub fn as_units_in(self, guard: &CallGuard) -> &[u16] {
+        let _ = guard;
+        let mut len = 0;
+        unsafe {
+            while *self.0.add(len) != 0 {
+                len += 1;
+            }
+
+            core::slice::from_raw_parts(self.0, len)
+        }
+    }
+
+    /// Decodes the string into a String.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer being used is a valid, NUL-terminated UTF-16 string.
+    pub fn to_string_utf16(self) -> Result<String, std::string::FromUtf16Error> {
+        String::from_utf16(self.as_units_in(&CallGuard::new()))
+    }
+
+    /// Decodes the string into a String, replacing any unpaired surrogates with `U+FFFD`.
+    ///
+    /// # Saftey
+    /// Assumes that the pointer being used is a valid, NUL-terminated UTF-16 string.
+    pub fn to_string_utf16_lossy(self) -> String {
+        String::from_utf16_lossy(self.as_units_in(&CallGuard::new()))
+    }
+}
+
+unsafe impl Send for GmWidePtr {}
+unsafe impl Sync for GmWidePtr {}
+
+/// A token representing a single call from GM into an export. Strings borrowed with
+/// [GmPtr::to_str_in] are tied to its lifetime, so the borrow checker stops them escaping the
+/// call, after which GM is free to release them.
+/// ```
+/// # use gm_ffi::{CallGuard, GmPtr, OutputCode};
+/// #[no_mangle]
+/// pub extern "C" fn set_name(name: GmPtr) -> OutputCode {
+///     let guard = CallGuard::new();
+///     let Ok(name) = name.to_str_in(&guard) else {
+///         return OutputCode::FAILURE;
+///     };
+///
+///     // `name` can't be kept past this point, so copy it if it's needed later.
+///     let _owned = name.to_owned();
+///     OutputCode::SUCCESS
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CallGuard {
+    // keeps the guard on the calling thread, and stops it being built outside of `new`
+    _not_send: core::marker::PhantomData<*mut ()>,
+}
+
+impl CallGuard {
+    /// Creates a guard for the current call. Create this at the start of an export, and let
+    /// it drop at the end.
+    pub fn new() -> Self {
+        Self {
+            _not_send: core::marker::PhantomData,
+        }
+    }
+}
+
+impl Default for CallGuard {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl core::ops::Deref for GmPtr {
@@ -86,21 +493,154 @@ impl core::ops::DerefMut for GmPtr {
     }
 }
 
+/// An error returned by [GmPtr::try_to_str] and [GmPtr::try_to_str_max].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GmStrError {
+    /// The pointer was null, probably because GM passed `undefined`.
+    Null,
+    /// No NUL was found within the maximum length.
+    Unterminated {
+        /// The number of bytes scanned.
+        max_len: usize,
+    },
+    /// The string wasn't valid UTF-8.
+    Utf8(core::str::Utf8Error),
+}
+
+impl core::fmt::Display for GmStrError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            GmStrError::Null => f.write_str("string pointer was null (was `undefined` passed?)"),
+            GmStrError::Unterminated { max_len } => {
+                write!(f, "string wasn't terminated within {} bytes", max_len)
+            }
+            GmStrError::Utf8(e) => write!(f, "string wasn't valid utf-8: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for GmStrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GmStrError::Utf8(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 unsafe impl Send for GmPtr {}
 unsafe impl Sync for GmPtr {}
 
+/// Representation of a pointer sent from GameMaker which Rust is meant to write into, such as
+/// the `buffer_get_address` of a buffer GML will read text out of.
+///
+/// Since GM doesn't tell us how big the memory behind the pointer is, every write takes the
+/// length of that memory, which GML should send alongside the pointer (usually from
+/// `buffer_get_size`).
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GmPtrMut(*mut c_char);
+
+impl GmPtrMut {
+    /// Creates a new GmPtrMut based on the given pointer.
+    pub fn new(ptr: *mut c_char) -> Self {
+        Self(ptr)
+    }
+
+    /// Returns a self with `NULL` inside it.
+    pub const fn null() -> Self {
+        Self(core::ptr::null_mut())
+    }
+
+    /// Returns a copy of the inner value.
+    pub const fn inner(self) -> *mut c_char {
+        self.0
+    }
+
+    /// Returns if the pointer is null.
+    pub fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+
+    /// Views `len` bytes behind the pointer mutably.
+    ///
+    /// # Safety
+    /// The pointer must be valid for reads and writes of `len` bytes, nothing else may access
+    /// that memory while the slice is alive, and the slice mustn't be used after GM frees it.
+    pub unsafe fn to_bytes_mut<'a>(self, len: usize) -> &'a mut [u8] {
+        if len == 0 {
+            return &mut [];
+        }
+
+        unsafe { core::slice::from_raw_parts_mut(self.0 as *mut u8, len) }
+    }
+
+    /// Copies `bytes` to the start of the memory behind the pointer. If `bytes` doesn't fit
+    /// in `len`, nothing is written, and the length needed is returned instead.
+    ///
+    /// # Safety
+    /// The pointer must be valid for writes of `len` bytes.
+    pub unsafe fn write_bytes(self, len: usize, bytes: &[u8]) -> Result<(), NeedsResize> {
+        if bytes.len() > len {
+            return Err(NeedsResize(bytes.len()));
+        }
+
+        unsafe { core::ptr::copy_nonoverlapping(bytes.as_ptr(), self.0 as *mut u8, bytes.len()) };
+
+        Ok(())
+    }
+
+    /// Writes `value` and a NUL terminator to the memory behind the pointer, so GML can read it
+    /// with `buffer_read(buffer, buffer_string)`. Returns the number of bytes written, including
+    /// the NUL. If it doesn't fit in `len`, nothing is written, and the length needed is
+    /// returned instead.
+    ///
+    /// # Safety
+    /// The pointer must be valid for writes of `len` bytes.
+    pub unsafe fn write_cstr(self, len: usize, value: &str) -> Result<usize, NeedsResize> {
+        let needed = value.len() + 1;
+        if needed > len {
+            return Err(NeedsResize(needed));
+        }
+
+        unsafe {
+            self.write_bytes(len, value.as_bytes())?;
+            *self.0.add(value.len()) = 0;
+        }
+
+        Ok(needed)
+    }
+}
+
+impl From<GmPtrMut> for GmPtr {
+    fn from(o: GmPtrMut) -> Self {
+        Self(o.0)
+    }
+}
+
+unsafe impl Send for GmPtrMut {}
+unsafe impl Sync for GmPtrMut {}
+
 /// This is a Gm Id for a buffer, or any other dynamically allocated resource.
 /// It is transparent in memory but opaque in type (ie, you can't inspect what's inside it),
 /// so it can be sent back and forth to GM as an f64.
 ///
-/// If you want to inspect an ID from Gm, you probably want `GmResourceId`, which is transparent
+/// If you want to inspect an ID from Gm, you probably want [GmResourceId], which is transparent
 /// in type as well.
 ///
 /// Generally, you shouldn't be constructing this, but should be getting this from Gm.
-/// The one exception is in Unit Tests, where you can get access to a `new` method, or
-/// the `dummy` variant, which will give you an f64::MAX inside.
+/// The exceptions are in Unit Tests, where you can get access to a `new` method, or
+/// the `dummy` variant, which will give you an f64::MAX inside, and [from_raw](GmId::from_raw),
+/// for ids which went through some other channel first.
+///
+/// Equality, hashing, and ordering all work on the bit pattern of the inner f64, so GmIds
+/// can be used as `HashMap` and `BTreeMap` keys directly. That means `0.0` and `-0.0` are
+/// different ids, and a NaN is equal to itself (if it has the same bits). GM never hands
+/// out either, so in practice this is the same as comparing the numbers.
 #[repr(transparent)]
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct GmId(f64);
 
 impl GmId {
@@ -110,97 +650,593 @@ impl GmId {
         Self(id)
     }
 
-    /// Returns a dummy, with the f64::MAX inside it.
-    pub const fn dummy() -> Self {
-        Self(f64::MAX)
+    /// Creates an ID from a raw f64, such as one GM sent earlier inside a buffer or a string.
+    /// Nothing checks that the id is real, so only use this with values that came from GM.
+    pub const fn from_raw(id: f64) -> Self {
+        Self(id)
+    }
+
+    /// Returns a dummy, with the f64::MAX inside it.
+    pub const fn dummy() -> Self {
+        Self(f64::MAX)
+    }
+}
+
+impl PartialEq for GmId {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for GmId {}
+
+impl core::hash::Hash for GmId {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+impl PartialOrd for GmId {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for GmId {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        // `total_cmp` is equal exactly when the bits are, so this agrees with `eq`
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// This is a Gm Id for a stable resource, like a sprite, sound, or object. Unlike [GmId], it's
+/// transparent in type, so you can look inside it.
+///
+/// If you want ids which can't be mixed up with each other, make your own with [define_gm_id].
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmResourceId(pub f64);
+
+impl GmResourceId {
+    /// Creates a new resource id.
+    pub const fn new(id: f64) -> Self {
+        Self(id)
+    }
+
+    /// Returns the inner f64.
+    pub const fn inner(self) -> f64 {
+        self.0
+    }
+
+    /// Returns the inner as a usize.
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// Defines a new, zero-cost resource id type, so that ids for different kinds of resources
+/// can't be passed where the other is expected.
+///
+/// The new type is `#[repr(transparent)]` over an f64, so it can be used directly as an
+/// argument or return type of an export, and converts to and from [GmResourceId].
+///
+/// ```
+/// gm_ffi::define_gm_id!(pub SpriteId);
+/// gm_ffi::define_gm_id!(
+///     /// A sound asset.
+///     pub SoundId
+/// );
+///
+/// #[no_mangle]
+/// pub extern "C" fn play(sound: SoundId) -> f64 {
+///     sound.inner()
+/// }
+///
+/// let sprite = SpriteId::new(3.0);
+/// let resource: gm_ffi::GmResourceId = sprite.into();
+/// assert_eq!(resource.inner(), 3.0);
+/// ```
+///
+/// Attributes are passed through, so ids which need serde (or anything else) can derive it
+/// themselves, like `#[derive(serde::Serialize)]`.
+#[macro_export]
+macro_rules! define_gm_id {
+    ($(#[$meta:meta])* $vis:vis $name:ident) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+        $vis struct $name(pub f64);
+
+        impl $name {
+            /// Creates a new id.
+            pub const fn new(id: f64) -> Self {
+                Self(id)
+            }
+
+            /// Returns the inner f64.
+            pub const fn inner(self) -> f64 {
+                self.0
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::GmResourceId {
+            fn from(o: $name) -> Self {
+                $crate::GmResourceId::new(o.0)
+            }
+        }
+
+        impl ::core::convert::From<$crate::GmResourceId> for $name {
+            fn from(o: $crate::GmResourceId) -> Self {
+                Self(o.inner())
+            }
+        }
+    };
+}
+
+/// This is a Gm Real, which can be a resource, or any other stable resource.
+///
+/// Generally, you shouldn't be constructing this, but should be getting this from Gm.
+/// The one exception is in Unit Tests, where you can get access to a `new` method, or
+/// the `dummy` variant, which will give you an f64::MAX inside.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[repr(transparent)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmReal(pub f64);
+
+impl GmReal {
+    /// Creates a new ID. You probably shouldn't be using this, but there are times
+    /// when you might be reconstructing it.
+    pub const fn new(id: f64) -> Self {
+        Self(id)
+    }
+
+    /// Returns the inner as a usize.
+    pub const fn as_usize(self) -> usize {
+        self.0 as usize
+    }
+
+    /// Returns the inner as an f64.
+    pub const fn as_f64(self) -> f64 {
+        self.0
+    }
+
+    /// Returns the inner f64.
+    pub const fn inner(self) -> f64 {
+        self.0
+    }
+
+    /// Returns a dummy, with the f64::MAX inside it.
+    pub const fn dummy() -> Self {
+        Self(f64::MAX)
+    }
+
+    /// The sentinel for a missing argument. See [OptionalReal].
+    pub const UNDEFINED: GmReal = GmReal(f64::NAN);
+
+    /// Returns if this is the [UNDEFINED](GmReal::UNDEFINED) sentinel, which is any NaN.
+    pub fn is_undefined(self) -> bool {
+        self.0.is_nan()
+    }
+}
+
+/// A real argument which the caller is allowed to leave out.
+///
+/// GM always passes every argument an export declares, so there's no way to tell that an
+/// argument was omitted on its own. Instead, we use NaN as the sentinel: no meaningful argument
+/// is ever NaN, and GML can pass one with the `NaN` constant. The GML wrapper for an export with
+/// optional arguments usually looks like:
+/// ```gml
+/// function spawn_enemy(_x, _y, _hp = undefined) {
+///     return __spawn_enemy(_x, _y, _hp ?? NaN);
+/// }
+/// ```
+/// Since the layout is just an f64, this can be used directly as an export's argument type:
+/// ```
+/// # use gm_ffi::{OptionalReal, OutputCode};
+/// #[no_mangle]
+/// pub extern "C" fn __spawn_enemy(x: f64, y: f64, hp: OptionalReal) -> OutputCode {
+///     let hp = hp.unwrap_or(100.0);
+///     # let _ = (x, y, hp);
+///     OutputCode::SUCCESS
+/// }
+/// ```
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy)]
+pub struct OptionalReal(f64);
+
+impl OptionalReal {
+    /// A missing value.
+    pub const NONE: OptionalReal = OptionalReal(f64::NAN);
+
+    /// Returns the value, or `None` if it was left out.
+    pub fn get(self) -> Option<f64> {
+        (!self.0.is_nan()).then_some(self.0)
+    }
+
+    /// Returns if the value was left out.
+    pub fn is_none(self) -> bool {
+        self.0.is_nan()
+    }
+
+    /// Returns the value, or `default` if it was left out.
+    pub fn unwrap_or(self, default: f64) -> f64 {
+        self.get().unwrap_or(default)
+    }
+}
+
+impl From<Option<f64>> for OptionalReal {
+    fn from(o: Option<f64>) -> Self {
+        o.map_or(Self::NONE, Self)
+    }
+}
+
+impl From<OptionalReal> for Option<f64> {
+    fn from(o: OptionalReal) -> Self {
+        o.get()
+    }
+}
+
+impl From<GmReal> for OptionalReal {
+    fn from(o: GmReal) -> Self {
+        Self(o.0)
+    }
+}
+
+/// Our basic GmBuffer. This holds anything you want.
+///
+/// # Safety
+/// We LIE to Rust and tell it that the buffer held within is `'static`.
+/// It is **not** static, but we're going to act like it is. Because GM is our
+/// allocator, a user could easily decide to deallocate a buffer.
+///
+/// We would very much so like if they don't do that, and will pretend like they cannot.
+/// If, however, they do, this entire data structure will be inadequate.
+///
+/// # Threading
+/// GM owns the memory, and only touches it from its main thread, so a GmBuffer is neither
+/// `Send` nor `Sync`. If you really need to fill a buffer from a background thread, see [SendBuffer].
+#[derive(Debug)]
+pub struct GmBuffer<T: 'static> {
+    /// An Id for the GameMaker buffer to return when we want to destruct this.
+    id: GmId,
+
+    /// The actual vertex buffer that we write to.
+    pub buffer: &'static mut [T],
+
+    /// The number of bytes after `buffer` holding a canary. See [GmBuffer::new_with_canary].
+    canary_bytes: usize,
+
+    /// Keeps this on the GM thread. See the threading notes above.
+    _not_send: core::marker::PhantomData<*mut ()>,
+}
+
+/// The pattern written into the tail of a buffer by [GmBuffer::new_with_canary].
+const CANARY: [u8; 8] = 0x6D67_6666_695F_6363_u64.to_le_bytes();
+
+impl<T> GmBuffer<T> {
+    /// Creates a new Gm Buffer.
+    ///
+    /// - `gm_id` is the id, in GameMaker, of the buffer we're trying to create.
+    /// - `gm_ptr` is the pointer provided to the buffer that GameMaker gives us.
+    /// - `len` is the number of T's that can be fit within the buffer, **not** the
+    ///   number of bytes. For more information, see [from_raw_parts](core::slice::from_raw_parts_mut)
+    ///
+    /// # Safety
+    /// Buffer must be allocated BY GAMEMAKER, not by some Rust code. The following invariants, in particular
+    /// must be held in order for this type to be safe:
+    /// - The buffer must be valid until `GmBuffer` is dropped
+    /// - The buffer's `id` must be a valid `GmId` from GameMaker.
+    /// - T must be sized, non-zero sized, and **must be zeroable**. This means that an "all zeroes"
+    ///   representation of the buffer is valid.  
+    pub unsafe fn new(gm_id: GmId, gm_ptr: GmPtr, len: usize) -> Self {
+        let buffer = {
+            let buf = gm_ptr.inner() as *mut T;
+
+            core::slice::from_raw_parts_mut(buf, len)
+        };
+
+        Self {
+            id: gm_id,
+            buffer,
+            canary_bytes: 0,
+            _not_send: core::marker::PhantomData,
+        }
+    }
+
+    /// Creates a new Gm Buffer which reserves its last few slots for a canary, so we can notice if
+    /// GM has freed the buffer (and something else has been allocated there) while we still hold it.
+    ///
+    /// `len` is the full length of the GM buffer, in T's. The canary takes up enough T's
+    /// to hold at least 8 bytes, and those slots are not part of [GmBuffer::buffer].
+    ///
+    /// In debug builds, the canary is checked whenever the buffer's contents are reached through
+    /// it (indexing, dereferencing, [as_bytes](GmBuffer::as_bytes), and so on), and we panic with
+    /// the buffer's id if it's been overwritten. In release builds, use [check_canary](GmBuffer::check_canary).
+    ///
+    /// # Safety
+    /// The same invariants as [GmBuffer::new] apply.
+    ///
+    /// # Panics
+    /// Panics if `len` isn't long enough to hold the canary.
+    pub unsafe fn new_with_canary(gm_id: GmId, gm_ptr: GmPtr, len: usize) -> Self {
+        let size = core::mem::size_of::<T>();
+        let canary_len = CANARY.len().div_ceil(size);
+        assert!(
+            len >= canary_len,
+            "buffer of {} elements cannot fit a {} element canary",
+            len,
+            canary_len
+        );
+
+        let mut output = Self::new(gm_id, gm_ptr, len - canary_len);
+        output.canary_bytes = canary_len * size;
+
+        let canary = output.canary_ptr();
+        for i in 0..output.canary_bytes {
+            canary.add(i).write(CANARY[i % CANARY.len()]);
+        }
+
+        output
+    }
+
+    /// Returns `false` if this buffer has a canary and it's been overwritten, meaning that
+    /// GM has probably freed the buffer out from under us. Buffers made with [GmBuffer::new]
+    /// always return `true`.
+    pub fn check_canary(&self) -> bool {
+        let canary = self.canary_ptr();
+
+        (0..self.canary_bytes).all(|i| unsafe { canary.add(i).read() } == CANARY[i % CANARY.len()])
+    }
+
+    #[inline]
+    fn debug_check_canary(&self) {
+        #[cfg(debug_assertions)]
+        if !self.check_canary() {
+            panic!(
+                "canary in GmBuffer {:?} was overwritten. GM has probably freed this buffer",
+                self.id
+            );
+        }
+    }
+
+    fn canary_ptr(&self) -> *mut u8 {
+        unsafe { (self.buffer.as_ptr() as *mut u8).add(self.byte_len()) }
+    }
+
+    /// This destructs the Buffer, taking self, and returning the Id. Once we give up ownership
+    /// of the ID by exposing it, we assume that we cannot safely hold onto the buffer anymore (ie,
+    /// we assume that it will be destroyed), and therefore, this function takes `self`.
+    pub fn id(self) -> GmId {
+        self.id
+    }
+
+    /// Returns the size of the buffer in bytes.
+    pub fn byte_len(&self) -> usize {
+        core::mem::size_of_val(self.buffer)
+    }
+
+    /// Views the buffer as raw bytes.
+    ///
+    /// T should not have any padding bytes in it, or else you'll be reading uninitialized memory.
+    pub fn as_bytes(&self) -> &[u8] {
+        self.debug_check_canary();
+        unsafe { core::slice::from_raw_parts(self.buffer.as_ptr() as *const u8, self.byte_len()) }
+    }
+
+    /// Views the buffer as raw, mutable bytes.
+    ///
+    /// T should not have any padding bytes in it, or else you'll be reading uninitialized memory.
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.debug_check_canary();
+        let len = self.byte_len();
+        unsafe { core::slice::from_raw_parts_mut(self.buffer.as_mut_ptr() as *mut u8, len) }
+    }
+
+    /// Converts this buffer into a buffer of `U`, keeping the same id and backing memory.
+    ///
+    /// This checks that `U` is not zero sized, that the buffer is aligned for `U`, and that
+    /// the buffer's length in bytes is an exact multiple of `U`'s size. If any of those
+    /// fail, you get the original buffer back in the `Err`.
+    ///
+    /// # Safety
+    /// Whatever is currently in the buffer must be a valid `U`. In practice, this means `U`
+    /// must be valid for **any** bit pattern, like the number types or `repr(C)` structs of them.
+    pub unsafe fn reinterpret<U>(self) -> Result<GmBuffer<U>, Self> {
+        self.debug_check_canary();
+        let size = core::mem::size_of::<U>();
+        let ptr = self.buffer.as_mut_ptr();
+        let byte_len = self.byte_len();
+
+        if size == 0 || ptr.align_offset(core::mem::align_of::<U>()) != 0 || byte_len % size != 0 {
+            return Err(self);
+        }
+
+        Ok(GmBuffer {
+            id: self.id,
+            buffer: core::slice::from_raw_parts_mut(ptr as *mut U, byte_len / size),
+            canary_bytes: self.canary_bytes,
+            _not_send: core::marker::PhantomData,
+        })
+    }
+}
+
+impl<T> GmBuffer<T> {
+    /// Splits the buffer into two disjoint, mutable regions at `mid`.
+    ///
+    /// Unlike the GmBuffer itself, the regions are ordinary slices, so they can be handed to
+    /// different threads (for example, with [std::thread::scope]) as long as they're all
+    /// finished before the borrow ends.
+    ///
+    /// # Panics
+    /// Panics if `mid > len`.
+    pub fn split_at_mut(&mut self, mid: usize) -> (&mut [T], &mut [T]) {
+        self.debug_check_canary();
+        self.buffer.split_at_mut(mid)
+    }
+
+    /// Splits the buffer into disjoint, mutable regions of `chunk_size` elements each. The
+    /// last region is shorter if the length doesn't divide evenly. See [split_at_mut](GmBuffer::split_at_mut)
+    /// for handing these to other threads.
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is 0.
+    pub fn chunks_mut(&mut self, chunk_size: usize) -> core::slice::ChunksMut<'_, T> {
+        self.debug_check_canary();
+        self.buffer.chunks_mut(chunk_size)
+    }
+}
+
+impl<T: Copy> GmBuffer<T> {
+    /// Sets every element of the buffer to `value`.
+    pub fn fill(&mut self, value: T) {
+        self.debug_check_canary();
+        self.buffer.fill(value);
+    }
+
+    /// Sets every byte of the buffer to zero with a single memset. Since T must be zeroable
+    /// (see [GmBuffer::new]), this is always valid, and it's much faster than `fill` for big buffers.
+    pub fn zeroize(&mut self) {
+        self.debug_check_canary();
+        unsafe { core::ptr::write_bytes(self.buffer.as_mut_ptr(), 0, self.buffer.len()) };
+    }
+
+    /// Copies all of `src` into this buffer with a single memcpy.
+    ///
+    /// # Panics
+    /// Panics if the two buffers have different lengths.
+    pub fn copy_from(&mut self, src: &GmBuffer<T>) {
+        self.debug_check_canary();
+        src.debug_check_canary();
+        self.copy_from_slice(src.buffer);
+    }
+
+    /// Copies all of `src` into this buffer with a single memcpy.
+    ///
+    /// # Panics
+    /// Panics if `src` is a different length than this buffer.
+    pub fn copy_from_slice(&mut self, src: &[T]) {
+        self.debug_check_canary();
+        self.buffer.copy_from_slice(src);
     }
 }
 
-/// This is a Gm Real, which can be a resource, or any other stable resource.
-///
-/// Generally, you shouldn't be constructing this, but should be getting this from Gm.
-/// The one exception is in Unit Tests, where you can get access to a `new` method, or
-/// the `dummy` variant, which will give you an f64::MAX inside.
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-#[repr(transparent)]
-pub struct GmReal(pub f64);
+/// A [GmBuffer] which is allowed to cross threads, for filling a buffer from a background
+/// producer. It dereferences into the GmBuffer, and [into_inner](SendBuffer::into_inner) gives
+/// it back once you're back on the GM thread.
+#[derive(Debug)]
+pub struct SendBuffer<T: 'static>(GmBuffer<T>);
 
-impl GmReal {
-    /// Creates a new ID. You probably shouldn't be using this, but there are times
-    /// when you might be reconstructing it.
-    pub const fn new(id: f64) -> Self {
-        Self(id)
-    }
+unsafe impl<T: Send> Send for SendBuffer<T> {}
 
-    /// Returns the inner as a usize.
-    pub const fn as_usize(self) -> usize {
-        self.0 as usize
+impl<T> SendBuffer<T> {
+    /// Wraps a buffer so that it can be sent to another thread.
+    ///
+    /// # Safety
+    /// While any other thread has access to this buffer:
+    /// - GML must not read, write, resize, or free the buffer.
+    /// - The buffer must be handed back (or the other thread otherwise finished with it) before
+    ///   GML touches it again, for example by joining the thread or waiting on a channel before
+    ///   returning from the export that GML is waiting on.
+    pub unsafe fn new(buffer: GmBuffer<T>) -> Self {
+        Self(buffer)
     }
 
-    /// Returns the inner as an f64.
-    pub const fn as_f64(self) -> f64 {
+    /// Unwraps the buffer. Do this on the GM thread.
+    pub fn into_inner(self) -> GmBuffer<T> {
         self.0
     }
+}
 
-    /// Returns the inner f64.
-    pub const fn inner(self) -> f64 {
-        self.0
+impl<T> core::ops::Deref for SendBuffer<T> {
+    type Target = GmBuffer<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
     }
+}
 
-    /// Returns a dummy, with the f64::MAX inside it.
-    pub const fn dummy() -> Self {
-        Self(f64::MAX)
+impl<T> core::ops::DerefMut for SendBuffer<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
     }
 }
 
-/// Our basic GmBuffer. This holds anything you want.
+/// Borrows a GameMaker buffer for the length of `f` only, without pretending it's `'static`.
 ///
-/// # Safety
-/// We LIE to Rust and tell it that the buffer held within is `'static`.
-/// It is **not** static, but we're going to act like it is. Because GM is our
-/// allocator, a user could easily decide to deallocate a buffer.
+/// This is what you want for the common "GM hands us a buffer, we process it, and we're done"
+/// export. Since the slice can't escape the closure, there's no way to accidentally hold onto
+/// it after GM frees the buffer.
 ///
-/// We would very much so like if they don't do that, and will pretend like they cannot.
-/// If, however, they do, this entire data structure will be inadequate.
-#[derive(Debug)]
-pub struct GmBuffer<T: 'static> {
-    /// An Id for the GameMaker buffer to return when we want to destruct this.
-    id: GmId,
+/// ```
+/// # use gm_ffi::{with_gm_buffer, GmId, GmPtr, OutputCode};
+/// #[no_mangle]
+/// pub unsafe extern "C" fn double_all(id: GmId, ptr: GmPtr, len: f64) -> OutputCode {
+///     with_gm_buffer(id, ptr, len as usize, |buf: &mut [f32]| {
+///         for v in buf {
+///             *v *= 2.0;
+///         }
+///     });
+///
+///     OutputCode::SUCCESS
+/// }
+/// ```
+///
+/// # Safety
+/// The same invariants as [GmBuffer::new] apply, except the buffer only has to stay valid
+/// until `f` returns. The id isn't used, but is taken so call sites line up with [GmBuffer::new].
+pub unsafe fn with_gm_buffer<T, R>(
+    _gm_id: GmId,
+    gm_ptr: GmPtr,
+    len: usize,
+    f: impl FnOnce(&mut [T]) -> R,
+) -> R {
+    let buffer = core::slice::from_raw_parts_mut(gm_ptr.inner() as *mut T, len);
+
+    f(buffer)
+}
 
-    /// The actual vertex buffer that we write to.
-    pub buffer: &'static mut [T],
+impl GmBuffer<u8> {
+    /// Creates a [BufferCursor] for reading typed values out of this buffer.
+    pub fn cursor(&self) -> BufferCursor<'_> {
+        self.debug_check_canary();
+        BufferCursor::new(self.buffer)
+    }
 }
 
-impl<T> GmBuffer<T> {
-    /// Creates a new Gm Buffer.
-    ///
-    /// - `gm_id` is the id, in GameMaker, of the buffer we're trying to create.
-    /// - `gm_ptr` is the pointer provided to the buffer that GameMaker gives us.
-    /// - `len` is the number of T's that can be fit within the buffer, **not** the
-    /// number of bytes. For more information, see [from_raw_parts](core::slice::from_raw_parts_mut)
-    ///
-    /// # Safety
-    /// Buffer must be allocated BY GAMEMAKER, not by some Rust code. The following invariants, in particular
-    /// must be held in order for this type to be safe:
-    /// - The buffer must be valid until `GmBuffer` is dropped
-    /// - The buffer's `id` must be a valid `GmId` from GameMaker.
-    /// - T must be sized, non-zero sized, and **must be zeroable**. This means that an "all zeroes"
-    ///   representation of the buffer is valid.  
-    pub unsafe fn new(gm_id: GmId, gm_ptr: GmPtr, len: usize) -> Self {
-        let buffer = {
-            let buf = gm_ptr.inner() as *mut T;
+impl<T> core::ops::Deref for GmBuffer<T> {
+    type Target = [T];
 
-            core::slice::from_raw_parts_mut(buf, len)
-        };
+    fn deref(&self) -> &Self::Target {
+        self.debug_check_canary();
+        self.buffer
+    }
+}
 
-        Self { id: gm_id, buffer }
+impl<T> core::ops::DerefMut for GmBuffer<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.debug_check_canary();
+        self.buffer
     }
+}
 
-    /// This destructs the Buffer, taking self, and returning the Id. Once we give up ownership
-    /// of the ID by exposing it, we assume that we cannot safely hold onto the buffer anymore (ie,
-    /// we assume that it will be destroyed), and therefore, this function takes `self`.
-    pub fn id(self) -> GmId {
-        self.id
+impl<'a, T> IntoIterator for &'a GmBuffer<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut GmBuffer<T> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
     }
 }
 
@@ -208,12 +1244,14 @@ impl<T> core::ops::Index<usize> for GmBuffer<T> {
     type Output = T;
 
     fn index(&self, index: usize) -> &Self::Output {
+        self.debug_check_canary();
         &self.buffer[index]
     }
 }
 
 impl<T> core::ops::IndexMut<usize> for GmBuffer<T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        self.debug_check_canary();
         &mut self.buffer[index]
     }
 }
@@ -240,7 +1278,17 @@ impl Bridge {
         Self(buf)
     }
 
-    /// Creates a new [BridgeWriter] for this [GmBridge].
+    /// Creates a new [Bridge] based upon a [GmBuffer], or returns a [NeedsResize] if the buffer
+    /// is shorter than 256 elements. Use this over [Bridge::new] for buffers straight from GML.
+    pub fn try_new(buf: GmBuffer<u32>) -> Result<Self, NeedsResize> {
+        if buf.buffer.len() < 256 {
+            return Err(NeedsResize(256 * core::mem::size_of::<u32>()));
+        }
+
+        Ok(Self(buf))
+    }
+
+    /// Creates a new [BridgeWriter] for this [Bridge].
     pub fn writer(&mut self) -> BridgeWriter<'_> {
         BridgeWriter::new(self)
     }
@@ -254,6 +1302,11 @@ impl<'a> BridgeWriter<'a> {
         Self(bridge, 0)
     }
 
+    /// The number of u32s which can still be written.
+    pub fn remaining(&self) -> usize {
+        self.0 .0.len() - self.1
+    }
+
     /// Writes a u32 into the bridge at the [BridgeWriter]'s current position.
     pub fn write_u32(&mut self, value: u32) {
         self.0 .0[self.1] = value;
@@ -265,171 +1318,158 @@ impl<'a> BridgeWriter<'a> {
         self.0 .0[self.1] = value.to_bits();
         self.1 += 1;
     }
+
+    /// Writes a f64 into the bridge at the [BridgeWriter]'s current position, as two u32s,
+    /// low half first, so GML can read it back with `buffer_f64`.
+    pub fn write_f64(&mut self, value: f64) {
+        let bits = value.to_bits();
+        self.write_u32(bits as u32);
+        self.write_u32((bits >> 32) as u32);
+    }
+
+    /// Writes a string into the bridge at the [BridgeWriter]'s current position, NUL-terminated
+    /// and padded with zeroes to a whole number of u32s, so GML can read it back with
+    /// `buffer_string`. This writes [padded_str_len](BridgeWriter::padded_str_len) u32s.
+    pub fn write_padded_str(&mut self, value: &str) {
+        // the NUL, and the padding, come from the zeroed tail of the last chunk
+        let mut bytes = value.as_bytes().chunks_exact(4);
+        for chunk in bytes.by_ref() {
+            self.write_u32(u32::from_le_bytes(chunk.try_into().unwrap()));
+        }
+
+        let mut last = [0; 4];
+        last[..bytes.remainder().len()].copy_from_slice(bytes.remainder());
+        self.write_u32(u32::from_le_bytes(last));
+    }
+
+    /// The number of u32s [write_padded_str](BridgeWriter::write_padded_str) writes for `value`.
+    pub fn padded_str_len(value: &str) -> usize {
+        value.len() / 4 + 1
+    }
 }
 
 /// This is exactly like `println`, but works within NPC Studio DLLs. It's not ideal, but it does the job!
 #[macro_export]
 macro_rules! gm_println {
-    ($($arg:tt)*) => {
-        #[cfg(target_os = "macos")]
-        {
-            use std::io::Write;
+    ($($arg:tt)*) => {{
+        let line = ::std::format!($($arg)*);
+        $crate::panic::record_line(&line);
 
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        {
             let mut output = $crate::GmStdOut::stdout();
-            output.write_fmt(format_args!($($arg)*)).unwrap();
+            output.write_str(&line);
             output.write_str("\n");
         }
-
-        #[cfg(target_os = "windows")]
-        {
-            println!($($arg)*);
-        }
-    };
+    }};
 }
 
 /// This is exactly like `print`, but works within NPC Studio DLLs. It's not ideal, but it does the job!
 #[macro_export]
 macro_rules! gm_print {
-    ($($arg:tt)*) => {
-        #[cfg(target_os = "macos")]
-        {
-            use std::io::Write;
-            let mut output = $crate::GmStdOut::stdout();
-            output.write_fmt(format_args!($($arg)*)).unwrap();
-        }
+    ($($arg:tt)*) => {{
+        let text = ::std::format!($($arg)*);
+        $crate::panic::record_line(&text);
 
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
         {
-            print!($($arg)*);
+            $crate::GmStdOut::stdout().write_str(&text);
         }
-    };
-}
-
-#[cfg(target_os = "windows")]
-mod windows_stub_gm_std_out {
-    /// Names the DLL for easier debugging
-    pub fn setup_panic_hook(program_name: &'static str) {
-        let base_message = format!("panicked in `{}` at ", program_name);
-
-        std::panic::set_hook(Box::new(move |panic_info| {
-            print!("{}", base_message);
-
-            if let Some(message) = panic_info.payload().downcast_ref::<String>() {
-                print!("'{}', ", message);
-            } else if let Some(message) = panic_info.payload().downcast_ref::<&'static str>() {
-                print!("'{}', ", message);
-            }
-
-            if let Some(location) = panic_info.location() {
-                print!("{}", location);
-            }
-            println!();
-        }));
-    }
+    }};
 }
 
-#[cfg(target_os = "macos")]
-mod mac_os_gm_std_out {
-    use interprocess::local_socket::LocalSocketStream;
-    use once_cell::sync::Lazy;
-    use parking_lot::RwLock;
-    use std::io::Write;
-
-    /// This struct abstracts for our purposes to only `adam`. It's not very useful
-    /// to people outside NPC Studio (unless they also use `adam`), so it's kept internally.
-    #[derive(Debug)]
-    pub struct GmStdOut(LocalSocketStream);
-
-    static GM_STD_OUT: Lazy<RwLock<GmStdOut>> = Lazy::new(|| {
-        let socket_name =
-            std::env::var("ADAM_IPC_SOCKET").expect("could not find `ADAM_IPC_SOCKET`");
-
-        let socket_stream =
-            LocalSocketStream::connect(socket_name).expect("could not connect to socket name!");
-        RwLock::new(GmStdOut(socket_stream))
-    });
-
-    impl GmStdOut {
-        /// Gets a handle to stdout.
-        pub fn stdout() -> impl std::ops::DerefMut<Target = GmStdOut> {
-            GM_STD_OUT.write()
-        }
+/// This is exactly like `eprintln`, but works within NPC Studio DLLs. The output is tagged as
+/// stderr, so adam can highlight it.
+#[macro_export]
+macro_rules! gm_eprintln {
+    ($($arg:tt)*) => {{
+        let line = ::std::format!($($arg)*);
+        $crate::panic::record_line(&line);
 
-        /// Tries to write a string out, handling errors by not handling them at all.
-        pub fn write_str(&mut self, input: &str) {
-            let Ok(()) = self.0.write_all(&(input.len() as u64).to_le_bytes()) else {
-                return;
-            };
-            let Ok(()) = self.0.write_all(input.as_bytes()) else {
-                return;
-            };
-            let _ = self.0.flush();
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        {
+            let mut output = $crate::GmStdOut::stdout();
+            output.write_str_to($crate::Channel::Stderr, &line);
+            output.write_str_to($crate::Channel::Stderr, "\n");
         }
-    }
+    }};
+}
 
-    impl std::io::Write for GmStdOut {
-        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-            self.0.write(buf)
-        }
+/// This is exactly like `eprint`, but works within NPC Studio DLLs. The output is tagged as
+/// stderr, so adam can highlight it.
+#[macro_export]
+macro_rules! gm_eprint {
+    ($($arg:tt)*) => {{
+        let text = ::std::format!($($arg)*);
+        $crate::panic::record_line(&text);
 
-        fn flush(&mut self) -> std::io::Result<()> {
-            self.0.flush()
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        {
+            $crate::GmStdOut::stdout().write_str_to($crate::Channel::Stderr, &text);
         }
+    }};
+}
 
-        fn write_fmt(&mut self, fmt: std::fmt::Arguments<'_>) -> std::io::Result<()> {
-            // Create a shim which translates a Write to a fmt::Write and saves
-            // off I/O errors. instead of discarding them
-            struct Adapter<'a> {
-                inner: &'a mut GmStdOut,
-            }
-
-            impl std::fmt::Write for Adapter<'_> {
-                fn write_str(&mut self, s: &str) -> std::fmt::Result {
-                    self.inner.write_str(s);
-
-                    Ok(())
-                }
+/// This is exactly like `dbg`, but works within NPC Studio DLLs. It prints the file, line,
+/// expression, and its value with [gm_println], then returns the value.
+/// ```
+/// let hp = gm_ffi::gm_dbg!(10.0 * 2.0);
+/// assert_eq!(hp, 20.0);
+/// ```
+#[macro_export]
+macro_rules! gm_dbg {
+    () => {
+        $crate::gm_println!("[{}:{}:{}]", ::core::file!(), ::core::line!(), ::core::column!())
+    };
+    ($val:expr $(,)?) => {
+        match $val {
+            tmp => {
+                $crate::gm_println!(
+                    "[{}:{}:{}] {} = {:#?}",
+                    ::core::file!(),
+                    ::core::line!(),
+                    ::core::column!(),
+                    ::core::stringify!($val),
+                    &tmp
+                );
+                tmp
             }
-
-            let mut output = Adapter { inner: self };
-            let _ = std::fmt::write(&mut output, fmt);
-
-            Ok(())
         }
-    }
-
-    /// This sets up a fairly decent panic hook. Pass in the name for us to format to use to identify the DLL.
-    pub fn setup_panic_hook(project_name: &str) {
-        let base_message = format!("panicked in `{}` at ", project_name);
-
-        std::panic::set_hook(Box::new(move |panic_info| {
-            use std::fmt::Write;
-
-            let mut output = base_message.clone();
+    };
+    ($($val:expr),+ $(,)?) => {
+        ($($crate::gm_dbg!($val)),+,)
+    };
+}
 
-            if let Some(message) = panic_info.payload().downcast_ref::<String>() {
-                write!(output, "'{}', ", message).unwrap();
-            } else if let Some(message) = panic_info.payload().downcast_ref::<&'static str>() {
-                write!(output, "'{}', ", message).unwrap();
-            }
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+mod gm_std_out;
 
-            if let Some(location) = panic_info.location() {
-                write!(output, "{}", location).unwrap();
-            }
-            output.push('\n');
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub use gm_std_out::{setup_panic_hook, Channel, GmStdOut, OutputTarget};
 
-            GmStdOut::stdout().write_str(&output);
+/// A 1KiB [Bridge], along with the memory behind it, for tests which read back what gets
+/// written. The memory has to outlive the bridge.
+#[cfg(test)]
+pub(crate) fn test_bridge() -> (Vec<u32>, Bridge) {
+    let mut backing = vec![0u32; 256];
+    let bridge = unsafe {
+        Bridge::new(GmBuffer::new(
+            GmId::new(0.0),
+            GmPtr::new(backing.as_mut_ptr() as *const _),
+            backing.len(),
+        ))
+    };
 
-            std::process::exit(1);
-        }));
-    }
+    (backing, bridge)
 }
 
-#[cfg(target_os = "windows")]
-pub use windows_stub_gm_std_out::setup_panic_hook;
-
-#[cfg(target_os = "macos")]
-pub use mac_os_gm_std_out::{setup_panic_hook, GmStdOut};
+/// Serializes tests which check the sizes of process-wide queues, like
+/// [warnings](crate::warnings) and [callbacks](crate::callbacks), which every test shares.
+#[cfg(test)]
+pub(crate) fn lock_queues() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
 
 #[cfg(test)]
 mod tests {
@@ -438,12 +1478,12 @@ mod tests {
 
     #[test]
     fn make_string_ptr() {
-        GmPtr::new("Hello, world!\0".as_ptr() as *const c_char);
+        GmPtr::new(c"Hello, world!".as_ptr());
     }
 
     #[test]
     fn read_string_ptr() {
-        let ptr = GmPtr::new("Hello, world!\0".as_ptr() as *const c_char);
+        let ptr = GmPtr::new(c"Hello, world!".as_ptr());
         let out = ptr.to_str().unwrap();
         assert_eq!(out, "Hello, world!");
     }
@@ -469,4 +1509,326 @@ mod tests {
         assert_eq!(f32::from_bits(buf[0]), 44.3);
         assert_eq!(f32::from_bits(buf[1]), 22.2);
     }
+
+    #[test]
+    fn reinterpret() {
+        let mut buf = vec![0u32; 4];
+        let gm_ptr = GmPtr::new(buf.as_mut_ptr() as *const _);
+        let mut bytes: GmBuffer<u8> = unsafe { GmBuffer::new(GmId::new(0.0), gm_ptr, 16) };
+
+        bytes.as_bytes_mut()[0] = 1;
+        assert_eq!(bytes.as_bytes().len(), 16);
+
+        let bytes = unsafe { bytes.reinterpret::<[u8; 3]>() }.unwrap_err();
+        let mut floats = unsafe { bytes.reinterpret::<f32>() }.unwrap();
+        floats[1] = 1.0;
+
+        assert_eq!(floats.buffer.len(), 4);
+        assert_eq!(buf[0], 1);
+        assert_eq!(buf[1], 1.0f32.to_bits());
+    }
+
+    #[test]
+    fn scoped_buffer() {
+        let mut buf = vec![1.0f32, 2.0];
+        let gm_ptr = GmPtr::new(buf.as_mut_ptr() as *const _);
+
+        let sum = unsafe {
+            with_gm_buffer(GmId::new(0.0), gm_ptr, 2, |floats: &mut [f32]| {
+                floats[0] = 3.0;
+                floats.iter().sum::<f32>()
+            })
+        };
+
+        assert_eq!(sum, 5.0);
+        assert_eq!(buf[0], 3.0);
+    }
+
+    #[test]
+    fn canary() {
+        let mut buf = vec![0u32; 4];
+        let gm_ptr = GmPtr::new(buf.as_mut_ptr() as *const _);
+        let mut buffer: GmBuffer<u32> =
+            unsafe { GmBuffer::new_with_canary(GmId::new(0.0), gm_ptr, 4) };
+
+        assert_eq!(buffer.buffer.len(), 2);
+        buffer[1] = 7;
+        assert!(buffer.check_canary());
+
+        buf[3] = 0;
+        assert!(!buffer.check_canary());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn clobbered_canary_is_caught() {
+        let mut buf = vec![0u32; 4];
+        let gm_ptr = GmPtr::new(buf.as_mut_ptr() as *const _);
+        let mut buffer: GmBuffer<u32> =
+            unsafe { GmBuffer::new_with_canary(GmId::new(0.0), gm_ptr, 4) };
+        buf[3] = 0;
+
+        let caught = |f: &mut dyn FnMut()| {
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).is_err()
+        };
+        assert!(caught(&mut || {
+            let _ = buffer.snapshot();
+        }));
+        assert!(caught(&mut || {
+            let _ = buffer.snapshot_range(0..1);
+        }));
+        assert!(caught(&mut || {
+            let _ = buffer.view_2d(1, 1, 1);
+        }));
+    }
+
+    #[test]
+    fn needs_resize() {
+        let code = OutputCode::from(NeedsResize(4096));
+
+        assert_eq!(code, OutputCode::custom(-4096.0));
+        assert_eq!(code.required_len(), Some(4096));
+        assert_eq!(OutputCode::SUCCESS.required_len(), None);
+
+        let zero = OutputCode::from(NeedsResize(0));
+        assert!(!zero.is_failure());
+        assert_eq!(zero.required_len(), Some(1));
+        assert_eq!(OutputCode::custom(-0.0).required_len(), None);
+        assert_eq!(OutputCode::custom(-0.5).required_len(), None);
+        assert!(NeedsResize::gml_retry_wrapper("fill").contains("function fill_retry(_buffer) {"));
+    }
+
+    #[test]
+    fn fill_and_copy() {
+        let mut a = vec![0u32; 4];
+        let mut b = vec![0u32; 4];
+        let mut buf_a: GmBuffer<u32> =
+            unsafe { GmBuffer::new(GmId::new(0.0), GmPtr::new(a.as_mut_ptr() as *const _), 4) };
+        let mut buf_b: GmBuffer<u32> =
+            unsafe { GmBuffer::new(GmId::new(1.0), GmPtr::new(b.as_mut_ptr() as *const _), 4) };
+
+        buf_a.fill(3);
+        buf_b.copy_from(&buf_a);
+        buf_a.zeroize();
+        buf_a.copy_from_slice(&[1, 2, 3, 4]);
+
+        assert_eq!(a, [1, 2, 3, 4]);
+        assert_eq!(b, [3, 3, 3, 3]);
+    }
+
+    #[test]
+    fn slice_methods() {
+        let mut backing = vec![3u32, 1, 2, 4];
+        let mut buf: GmBuffer<u32> = unsafe {
+            GmBuffer::new(
+                GmId::new(0.0),
+                GmPtr::new(backing.as_mut_ptr() as *const _),
+                4,
+            )
+        };
+
+        buf.sort_unstable();
+        for v in &mut buf {
+            *v *= 2;
+        }
+
+        assert_eq!(buf.chunks_exact(2).count(), 2);
+        assert_eq!((&buf).into_iter().sum::<u32>(), 20);
+        assert_eq!(backing, [2, 4, 6, 8]);
+    }
+
+    #[test]
+    fn send_buffer() {
+        let mut backing = vec![0u32; 4];
+        let buf: GmBuffer<u32> = unsafe {
+            GmBuffer::new(
+                GmId::new(0.0),
+                GmPtr::new(backing.as_mut_ptr() as *const _),
+                4,
+            )
+        };
+        let mut buf = unsafe { SendBuffer::new(buf) };
+
+        let buf = std::thread::spawn(move || {
+            buf.fill(5);
+            buf
+        })
+        .join()
+        .unwrap()
+        .into_inner();
+
+        assert_eq!(buf.id(), GmId::new(0.0));
+        assert_eq!(backing, [5; 4]);
+    }
+
+    #[test]
+    fn disjoint_regions() {
+        let mut backing = vec![0u32; 6];
+        let mut buf: GmBuffer<u32> = unsafe {
+            GmBuffer::new(
+                GmId::new(0.0),
+                GmPtr::new(backing.as_mut_ptr() as *const _),
+                6,
+            )
+        };
+
+        let (audio, telemetry) = buf.split_at_mut(2);
+        std::thread::scope(|s| {
+            s.spawn(|| audio.fill(1));
+            s.spawn(|| telemetry.fill(2));
+        });
+
+        for (i, chunk) in buf.chunks_mut(4).enumerate() {
+            chunk[0] += i as u32 * 10;
+        }
+
+        assert_eq!(backing, [1, 1, 2, 2, 12, 2]);
+    }
+
+    #[test]
+    fn ptr_mut_writes() {
+        let mut backing = [0xffu8; 6];
+        let ptr = GmPtrMut::new(backing.as_mut_ptr() as *mut _);
+
+        assert_eq!(unsafe { ptr.write_cstr(6, "hello!") }, Err(NeedsResize(7)));
+        assert_eq!(backing, [0xff; 6]);
+
+        assert_eq!(unsafe { ptr.write_cstr(6, "hey") }, Ok(4));
+        assert_eq!(GmPtr::from(ptr).to_str(), Ok("hey"));
+
+        assert_eq!(unsafe { ptr.write_bytes(6, &[1, 0, 2]) }, Ok(()));
+        assert_eq!(backing, [1, 0, 2, 0, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn guarded_and_lossy_strings() {
+        let guard = CallGuard::new();
+        let ptr = GmPtr::new(c"hi".as_ptr());
+        assert_eq!(ptr.to_str_in(&guard), Ok("hi"));
+
+        let bad = [b'o', 0xff, b'k', 0];
+        let ptr = GmPtr::new(bad.as_ptr() as *const _);
+        assert!(ptr.to_str_in(&guard).is_err());
+        assert_eq!(ptr.to_str_lossy(), "o\u{fffd}k");
+    }
+
+    #[test]
+    fn wide_strings() {
+        let wide: Vec<u16> = "héllo\0".encode_utf16().collect();
+        let ptr = GmWidePtr::new(wide.as_ptr());
+        assert_eq!(ptr.as_units_in(&CallGuard::new()).len(), 5);
+        assert_eq!(ptr.to_string_utf16().unwrap(), "héllo");
+
+        let bad = [0x68, 0xd800, 0];
+        let ptr = GmWidePtr::new(bad.as_ptr());
+        assert!(ptr.to_string_utf16().is_err());
+        assert_eq!(ptr.to_string_utf16_lossy(), "h\u{fffd}");
+    }
+
+    #[test]
+    fn try_to_str() {
+        assert_eq!(GmPtr::null().try_to_str(), Err(GmStrError::Null));
+        assert_eq!(GmPtr::new(c"ok".as_ptr()).try_to_str(), Ok("ok"));
+
+        let ptr = GmPtr::new(c"hello".as_ptr());
+        assert_eq!(ptr.try_to_str_max(6), Ok("hello"));
+        assert!(ptr.try_to_str_max(0).is_err());
+        assert_eq!(
+            ptr.try_to_str_max(3),
+            Err(GmStrError::Unterminated { max_len: 3 })
+        );
+    }
+
+    #[test]
+    fn byte_views() {
+        let mut backing = [1u8, 0, 2, 0];
+        let ptr = GmPtrMut::new(backing.as_mut_ptr() as *mut _);
+
+        unsafe { ptr.to_bytes_mut(4)[3] = 3 };
+        assert_eq!(unsafe { GmPtr::from(ptr).to_bytes(4) }, &[1, 0, 2, 3]);
+        assert!(unsafe { GmPtr::null().to_bytes(0) }.is_empty());
+    }
+
+    #[test]
+    fn id_keys() {
+        use std::collections::{BTreeSet, HashSet};
+
+        let ids = [GmId::from_raw(2.0), GmId::new(1.0), GmId::from_raw(2.0)];
+        assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 2);
+        assert_eq!(
+            ids.into_iter().collect::<BTreeSet<_>>().first(),
+            Some(&GmId::new(1.0))
+        );
+
+        assert_eq!(GmId::from_raw(f64::NAN), GmId::from_raw(f64::NAN));
+        assert_ne!(GmId::from_raw(0.0), GmId::from_raw(-0.0));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trips() {
+        assert_eq!(serde_json::to_string(&GmId::new(5.0)).unwrap(), "5.0");
+        assert_eq!(serde_json::from_str::<GmReal>("2.5").unwrap(), GmReal(2.5));
+
+        let color = serde_json::to_string(&GmColor::rgb(1, 2, 3)).unwrap();
+        assert_eq!(color, r#"{"r":1,"g":2,"b":3,"a":255}"#);
+
+        assert!(serde_json::from_str::<GmInt53>("9007199254740993").is_err());
+    }
+
+    #[test]
+    fn optional_reals() {
+        assert!(GmReal::UNDEFINED.is_undefined());
+        assert!(!GmReal::new(0.0).is_undefined());
+
+        assert_eq!(OptionalReal::from(GmReal::UNDEFINED).get(), None);
+        assert_eq!(OptionalReal::from(Some(3.0)).unwrap_or(1.0), 3.0);
+        assert!(OptionalReal::NONE.is_none());
+    }
+
+    #[test]
+    fn output_code_predicates() {
+        assert!(OutputCode::from(true).is_success());
+        assert!(OutputCode::from(None::<u8>).is_failure());
+        assert!(OutputCode::from(Ok::<_, ()>(())).is_success());
+
+        let code = OutputCode::custom(12.0);
+        assert!(!code.is_success() && !code.is_failure());
+        assert_eq!(code.as_f64(), 12.0);
+    }
+
+    #[test]
+    fn packed_codes() {
+        let value = OutputCode::value(u32::MAX);
+        assert_eq!(value.kind(), CodeKind::Value);
+        assert_eq!(value.payload(), Some(u32::MAX));
+
+        let error = OutputCode::error(1);
+        assert_eq!(error.kind(), CodeKind::Error);
+        assert_eq!(error.payload(), Some(1));
+        assert!(!error.is_success());
+
+        assert_eq!(OutputCode::SUCCESS.kind(), CodeKind::Other);
+        assert_eq!(OutputCode::from(NeedsResize(8)).payload(), None);
+    }
+
+    #[test]
+    fn gm_dbg_returns_its_value() {
+        let name = String::from("obj_player");
+        let name = crate::gm_dbg!(name);
+        assert_eq!(name, "obj_player");
+        assert_eq!(crate::gm_dbg!(1, "two"), (1, "two"));
+
+        let line = crate::panic::recent_lines()
+            .into_iter()
+            .rev()
+            .find(|l| l.contains("= 1"));
+        assert!(line.is_some_and(|l| l.starts_with("[src/lib.rs:")));
+    }
+
+    #[test]
+    fn gm_eprintln_records_lines() {
+        crate::gm_eprintln!("couldn't load {}", "level_3");
+        assert!(crate::panic::recent_lines().contains(&"couldn't load level_3".to_string()));
+    }
 }
@@ -0,0 +1,110 @@
+//! A [log] backend which sends records through [gm_println](crate::gm_println), so output
+//! from crates used inside the DLL shows up in adam instead of vanishing.
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// A [Log] implementation which writes through [gm_println](crate::gm_println), with a level
+/// filter per module.
+///
+/// Install it once, usually next to `setup_panic_hook`:
+/// ```
+/// use gm_ffi::logger::GmLogger;
+/// use log::LevelFilter;
+///
+/// GmLogger::new(LevelFilter::Info)
+///     .module("reqwest", LevelFilter::Warn)
+///     .module("my_extension::ai", LevelFilter::Trace)
+///     .install()
+///     .ok();
+///
+/// log::info!("ready");
+/// ```
+#[derive(Debug, Clone)]
+pub struct GmLogger {
+    default_level: LevelFilter,
+    // sorted longest first, so the most specific module wins
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl GmLogger {
+    /// Creates a logger which lets through everything at `default_level` or above.
+    pub fn new(default_level: LevelFilter) -> Self {
+        Self {
+            default_level,
+            modules: Vec::new(),
+        }
+    }
+
+    /// Sets the level for `module` and everything inside it, like `"reqwest"` or
+    /// `"my_extension::net"`.
+    pub fn module(mut self, module: &str, level: LevelFilter) -> Self {
+        self.modules.retain(|(m, _)| m != module);
+        self.modules.push((module.to_string(), level));
+        self.modules
+            .sort_by_key(|(m, _)| core::cmp::Reverse(m.len()));
+        self
+    }
+
+    /// Returns the level which applies to `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .map_or(self.default_level, |(_, level)| *level)
+    }
+
+    /// Installs this as the global logger. This fails if a logger was already installed.
+    pub fn install(self) -> Result<(), log::SetLoggerError> {
+        let max_level = self
+            .modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.default_level, Ord::max);
+
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+
+        Ok(())
+    }
+}
+
+impl Log for GmLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            crate::gm_println!("[{} {}] {}", record.level(), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    #[test]
+    fn module_filters() {
+        let logger = GmLogger::new(LevelFilter::Info)
+            .module("net", LevelFilter::Warn)
+            .module("net::http", LevelFilter::Trace);
+
+        let enabled = |target, level| {
+            logger.enabled(&Metadata::builder().target(target).level(level).build())
+        };
+
+        assert!(enabled("game", Level::Info));
+        assert!(!enabled("game", Level::Debug));
+        assert!(!enabled("net::socket", Level::Info));
+        assert!(enabled("net::http::client", Level::Trace));
+        assert!(enabled("network", Level::Info));
+    }
+}
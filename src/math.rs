@@ -0,0 +1,174 @@
+//! Plain-old-data math types, laid out the way GM's vertex formats and matrices expect, so
+//! geometry can be written through typed `GmBuffer`s instead of raw `f32` indices.
+//!
+//! For example, a vertex format of `vertex_format_add_position_3d`, `vertex_format_add_color`,
+//! and `vertex_format_add_texcoord` matches:
+//! ```
+//! use gm_ffi::{GmColor, GmVec2, GmVec3};
+//!
+//! #[repr(C)]
+//! #[derive(Clone, Copy)]
+//! struct Vertex {
+//!     position: GmVec3,
+//!     color: GmColor,
+//!     uv: GmVec2,
+//! }
+//!
+//! assert_eq!(std::mem::size_of::<Vertex>(), 24);
+//! ```
+
+macro_rules! vector {
+    ($(#[$meta:meta])* $name:ident, $len:literal, $($field:ident),*) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Debug, Clone, Copy, PartialEq, Default)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $name {
+            $(
+                #[doc = concat!("The ", stringify!($field), " component.")]
+                pub $field: f32,
+            )*
+        }
+
+        impl $name {
+            /// All zeroes.
+            pub const ZERO: $name = $name { $($field: 0.0),* };
+
+            /// Creates a new vector.
+            pub const fn new($($field: f32),*) -> Self {
+                Self { $($field),* }
+            }
+
+            /// Returns the components as an array.
+            pub const fn to_array(self) -> [f32; $len] {
+                [$(self.$field),*]
+            }
+        }
+
+        impl From<[f32; $len]> for $name {
+            fn from([$($field),*]: [f32; $len]) -> Self {
+                Self { $($field),* }
+            }
+        }
+
+        impl From<$name> for [f32; $len] {
+            fn from(o: $name) -> Self {
+                o.to_array()
+            }
+        }
+
+        impl core::ops::Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                Self { $($field: self.$field + rhs.$field),* }
+            }
+        }
+
+        impl core::ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self { $($field: self.$field - rhs.$field),* }
+            }
+        }
+
+        impl core::ops::Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: f32) -> Self {
+                Self { $($field: self.$field * rhs),* }
+            }
+        }
+    };
+}
+
+vector!(
+    /// A 2D vector, matching `vertex_format_add_position` and `vertex_format_add_texcoord`.
+    GmVec2, 2, x, y
+);
+vector!(
+    /// A 3D vector, matching `vertex_format_add_position_3d` and `vertex_format_add_normal`.
+    GmVec3, 3, x, y, z
+);
+vector!(
+    /// A 4D vector, matching `vertex_format_add_custom(vertex_type_float4, ...)`.
+    GmVec4, 4, x, y, z, w
+);
+
+/// A 4x4 matrix, with its 16 elements in the same order as GML's matrix arrays
+/// (`matrix_build`, `matrix_get`, and so on).
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct GmMatrix4(pub [f32; 16]);
+
+impl GmMatrix4 {
+    /// The identity matrix, like `matrix_build_identity()`.
+    pub const IDENTITY: GmMatrix4 = GmMatrix4([
+        1.0, 0.0, 0.0, 0.0, //
+        0.0, 1.0, 0.0, 0.0, //
+        0.0, 0.0, 1.0, 0.0, //
+        0.0, 0.0, 0.0, 1.0, //
+    ]);
+
+    /// Converts from a GML matrix array, which arrives as 16 reals.
+    pub fn from_gm(values: &[f64; 16]) -> Self {
+        Self(values.map(|v| v as f32))
+    }
+
+    /// Converts into 16 reals, for handing back to GML.
+    pub fn to_gm(self) -> [f64; 16] {
+        self.0.map(f64::from)
+    }
+
+    /// Returns the element at `row` and `column`. The matrix is stored with each group
+    /// of four elements forming a row.
+    pub fn get(&self, row: usize, column: usize) -> f32 {
+        self.0[row * 4 + column]
+    }
+
+    /// Multiplies two matrices, like `matrix_multiply(self, rhs)`.
+    pub fn multiply(&self, rhs: &GmMatrix4) -> GmMatrix4 {
+        let mut out = [0.0; 16];
+        for row in 0..4 {
+            for column in 0..4 {
+                out[row * 4 + column] = (0..4).map(|i| self.get(row, i) * rhs.get(i, column)).sum();
+            }
+        }
+
+        GmMatrix4(out)
+    }
+}
+
+impl Default for GmMatrix4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layouts() {
+        assert_eq!(core::mem::size_of::<GmVec2>(), 8);
+        assert_eq!(core::mem::size_of::<GmVec3>(), 12);
+        assert_eq!(core::mem::size_of::<GmVec4>(), 16);
+        assert_eq!(core::mem::size_of::<GmMatrix4>(), 64);
+
+        let v = GmVec3::new(1.0, 2.0, 3.0) + GmVec3::from([1.0; 3]) * 2.0;
+        assert_eq!(v.to_array(), [3.0, 4.0, 5.0]);
+    }
+
+    #[test]
+    fn matrices() {
+        let mut translate = GmMatrix4::IDENTITY;
+        translate.0[12] = 5.0;
+
+        assert_eq!(translate.multiply(&GmMatrix4::IDENTITY), translate);
+        assert_eq!(GmMatrix4::from_gm(&translate.to_gm()), translate);
+    }
+}
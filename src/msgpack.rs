@@ -0,0 +1,213 @@
+//! Passing structured data between Rust and GML as MessagePack, for payloads too big to
+//! `json_stringify` every frame, or holding binary data JSON would mangle.
+//!
+//! Rust writes into a GM buffer with [write_msgpack], and GML reads it back with the
+//! functions from [gml_decoder], here generated with `gml_decoder("msgpack_decode")`:
+//! ```
+//! use gm_ffi::{msgpack, GmBuffer, GmId, GmPtr, OutputCode};
+//!
+//! #[derive(serde::Serialize)]
+//! struct Chunk {
+//!     x: i32,
+//!     y: i32,
+//!     tiles: Vec<u16>,
+//! }
+//!
+//! /// # Safety
+//! /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+//! #[no_mangle]
+//! pub unsafe extern "C" fn chunk_load(id: GmId, ptr: GmPtr, len: f64) -> OutputCode {
+//!     let mut buffer = unsafe { GmBuffer::new(id, ptr, len as usize) };
+//!     let chunk = Chunk { x: 0, y: 0, tiles: vec![0; 256] };
+//!     msgpack::write_msgpack(&chunk, &mut buffer)
+//! }
+//! ```
+//! ```gml
+//! if (chunk_load(global.bridge, buffer_get_address(global.bridge), buffer_get_size(global.bridge)) > 0) {
+//!     buffer_seek(global.bridge, buffer_seek_start, 0);
+//!     var _chunk = msgpack_decode(global.bridge);
+//!     show_debug_message(array_length(_chunk.tiles));
+//! }
+//! ```
+//! Structs are written as maps, so they come out in GML as structs with the same field names.
+
+use crate::{error::set_last_error, GmBuffer, GmError, NeedsResize, OutputCode};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodes `value` as MessagePack, with structs as maps, into the start of `buffer`, and
+/// returns the number of bytes written as an [OutputCode::custom].
+///
+/// If the buffer is too small, nothing is written, and this returns a [NeedsResize]. If
+/// `value` can't be encoded, this returns [OutputCode::FAILURE] and sets the
+/// [last error](crate::error::last_error).
+pub fn write_msgpack<T: Serialize + ?Sized>(value: &T, buffer: &mut GmBuffer<u8>) -> OutputCode {
+    let bytes = match to_msgpack(value) {
+        Ok(bytes) => bytes,
+        Err(e) => return set_last_error(e),
+    };
+
+    let Some(output) = buffer.as_bytes_mut().get_mut(..bytes.len()) else {
+        return NeedsResize(bytes.len()).into();
+    };
+    output.copy_from_slice(&bytes);
+
+    OutputCode::custom(bytes.len() as f64)
+}
+
+/// Encodes `value` as MessagePack, with structs as maps.
+pub fn to_msgpack<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, GmError> {
+    rmp_serde::to_vec_named(value)
+        .map_err(|e| GmError::custom(format_args!("couldn't encode msgpack: {}", e)))
+}
+
+/// Decodes a `T` from MessagePack in `bytes`, such as a [GmBuffer]. Trailing bytes are
+/// ignored, so this can be given a whole buffer.
+pub fn from_msgpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, GmError> {
+    rmp_serde::from_slice(bytes)
+        .map_err(|e| GmError::custom(format_args!("couldn't decode msgpack: {}", e)))
+}
+
+/// Generates GML functions for decoding MessagePack, named `<name>` and `<name>_*`. Paste them
+/// into a script in the project.
+///
+/// `<name>(buffer)` decodes one value from the buffer's current position, and leaves the
+/// position after it. Maps become structs (with non-string keys turned into strings), arrays
+/// become arrays, and binary data becomes a new buffer, which the caller has to delete.
+/// Integers which don't fit in a real come out as `int64`s. Extension types aren't
+/// supported.
+pub fn gml_decoder(name: &str) -> String {
+    format!(
+        r#"/// @param {{Id.Buffer}} _buffer
+function {name}(_buffer) {{
+    var _byte = buffer_read(_buffer, buffer_u8);
+    if (_byte <= 0x7f) return _byte;
+    if (_byte >= 0xe0) return _byte - 0x100;
+    if ((_byte & 0xf0) == 0x80) return {name}_map(_buffer, _byte & 0x0f);
+    if ((_byte & 0xf0) == 0x90) return {name}_array(_buffer, _byte & 0x0f);
+    if ((_byte & 0xe0) == 0xa0) return {name}_string(_buffer, _byte & 0x1f);
+
+    switch (_byte) {{
+        case 0xc0: return undefined;
+        case 0xc2: return false;
+        case 0xc3: return true;
+        case 0xc4: return {name}_bin(_buffer, {name}_uint(_buffer, 1));
+        case 0xc5: return {name}_bin(_buffer, {name}_uint(_buffer, 2));
+        case 0xc6: return {name}_bin(_buffer, {name}_uint(_buffer, 4));
+        case 0xca: return {name}_float(_buffer, 4);
+        case 0xcb: return {name}_float(_buffer, 8);
+        case 0xcc: return {name}_uint(_buffer, 1);
+        case 0xcd: return {name}_uint(_buffer, 2);
+        case 0xce: return {name}_uint(_buffer, 4);
+        case 0xcf: return {name}_uint(_buffer, 8);
+        case 0xd0: return {name}_int(_buffer, 1);
+        case 0xd1: return {name}_int(_buffer, 2);
+        case 0xd2: return {name}_int(_buffer, 4);
+        case 0xd3: return {name}_int(_buffer, 8);
+        case 0xd9: return {name}_string(_buffer, {name}_uint(_buffer, 1));
+        case 0xda: return {name}_string(_buffer, {name}_uint(_buffer, 2));
+        case 0xdb: return {name}_string(_buffer, {name}_uint(_buffer, 4));
+        case 0xdc: return {name}_array(_buffer, {name}_uint(_buffer, 2));
+        case 0xdd: return {name}_array(_buffer, {name}_uint(_buffer, 4));
+        case 0xde: return {name}_map(_buffer, {name}_uint(_buffer, 2));
+        case 0xdf: return {name}_map(_buffer, {name}_uint(_buffer, 4));
+    }}
+
+    show_error("{name}: unsupported msgpack type " + string(_byte), false);
+    return undefined;
+}}
+
+// big endian, unlike buffer_u16 and friends
+function {name}_uint(_buffer, _bytes) {{
+    var _value = (_bytes == 8) ? int64(0) : 0;
+    repeat (_bytes) _value = _value * 256 + buffer_read(_buffer, buffer_u8);
+    return _value;
+}}
+
+function {name}_int(_buffer, _bytes) {{
+    var _value = {name}_uint(_buffer, _bytes);
+    if (_bytes < 8 && _value >= power(2, _bytes * 8 - 1)) _value -= power(2, _bytes * 8);
+    return _value;
+}}
+
+function {name}_float(_buffer, _bytes) {{
+    static _scratch = buffer_create(8, buffer_fixed, 1);
+    for (var _i = _bytes - 1; _i >= 0; _i--) {{
+        buffer_poke(_scratch, _i, buffer_u8, buffer_read(_buffer, buffer_u8));
+    }}
+    return buffer_peek(_scratch, 0, (_bytes == 4) ? buffer_f32 : buffer_f64);
+}}
+
+function {name}_string(_buffer, _len) {{
+    var _scratch = buffer_create(_len + 1, buffer_fixed, 1);
+    buffer_copy(_buffer, buffer_tell(_buffer), _len, _scratch, 0);
+    buffer_poke(_scratch, _len, buffer_u8, 0);
+    var _string = buffer_peek(_scratch, 0, buffer_string);
+    buffer_delete(_scratch);
+    buffer_seek(_buffer, buffer_seek_relative, _len);
+    return _string;
+}}
+
+function {name}_bin(_buffer, _len) {{
+    var _bin = buffer_create(max(_len, 1), buffer_fixed, 1);
+    buffer_copy(_buffer, buffer_tell(_buffer), _len, _bin, 0);
+    buffer_seek(_buffer, buffer_seek_relative, _len);
+    return _bin;
+}}
+
+function {name}_array(_buffer, _len) {{
+    var _array = array_create(_len);
+    for (var _i = 0; _i < _len; _i++) _array[_i] = {name}(_buffer);
+    return _array;
+}}
+
+function {name}_map(_buffer, _len) {{
+    var _struct = {{}};
+    repeat (_len) {{
+        var _key = string({name}(_buffer));
+        _struct[$ _key] = {name}(_buffer);
+    }}
+    return _struct;
+}}
+"#,
+        name = name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GmId, GmPtr};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Chunk {
+        x: i32,
+        name: String,
+    }
+
+    #[test]
+    fn round_trips_through_buffers() {
+        let chunk = Chunk {
+            x: -3,
+            name: "cave".into(),
+        };
+        let mut backing = vec![0u8; 64];
+        let mut buffer = unsafe {
+            GmBuffer::new(
+                GmId::new(0.0),
+                GmPtr::new(backing.as_mut_ptr() as *const _),
+                backing.len(),
+            )
+        };
+
+        let code = write_msgpack(&chunk, &mut buffer);
+        // fixmap(2), fixstr "x", negative fixint, fixstr "name", fixstr "cave"
+        assert_eq!(code.as_f64(), 14.0);
+        assert_eq!(backing[..4], [0x82, 0xa1, b'x', 0xfd]);
+        assert_eq!(from_msgpack::<Chunk>(&backing).unwrap(), chunk);
+
+        let mut small =
+            unsafe { GmBuffer::new(GmId::new(0.0), GmPtr::new(backing.as_ptr() as *const _), 4) };
+        assert_eq!(write_msgpack(&chunk, &mut small).required_len(), Some(14));
+        assert!(gml_decoder("chunk_decode").contains("function chunk_decode_map(_buffer, _len) {"));
+    }
+}
@@ -0,0 +1,412 @@
+//! Handles to large result sets which GML can read a page at a time, instead of
+//! receiving everything in one call.
+//!
+//! Keep the sets in a static, hand GML a handle from [insert](ResultSets::insert), and let
+//! [export_result_sets](crate::export_result_sets) generate the exports which read pages:
+//! ```
+//! use gm_ffi::pagination::ResultSets;
+//! use once_cell::sync::Lazy;
+//! use std::sync::Mutex;
+//! use std::time::Duration;
+//!
+//! static RESULTS: Lazy<Mutex<ResultSets<String>>> =
+//!     Lazy::new(|| Mutex::new(ResultSets::new(Duration::from_secs(60))));
+//! gm_ffi::export_result_sets!(RESULTS, 100);
+//!
+//! #[no_mangle]
+//! pub extern "C" fn search(query: gm_ffi::GmPtr) -> gm_ffi::GmReal {
+//!     let query = query.to_str_lossy();
+//!     let hits = (0..50_000).map(|i| format!("{} {}", query, i)).collect();
+//!     RESULTS.lock().unwrap().insert(hits)
+//! }
+//! ```
+//! ```gml
+//! var _results = search("slime");
+//! result_page(_results, 0, buffer_get_address(global.bridge), buffer_get_size(global.bridge));
+//! var _page = gm_read_result_page(global.bridge);
+//! ```
+
+use crate::{
+    error::set_last_error, Bridge, BridgeWriter, GmError, GmReal, NeedsResize, OutputCode,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A store of result sets, each kept on the Rust side and handed to GML as a [GmReal] handle.
+///
+/// Every time a set is changed with [update](ResultSets::update), its version goes up, so
+/// GML can tell that pages it already fetched are stale. Sets which haven't been touched
+/// for longer than the store's time-to-live are dropped by [expire](ResultSets::expire).
+#[derive(Debug)]
+pub struct ResultSets<T> {
+    sets: HashMap<u64, ResultSet<T>>,
+    next_handle: u64,
+    ttl: Duration,
+}
+
+#[derive(Debug)]
+struct ResultSet<T> {
+    items: Vec<T>,
+    version: u32,
+    last_access: Instant,
+}
+
+/// A single page out of a result set.
+#[derive(Debug, PartialEq)]
+pub struct Page<'a, T> {
+    /// The items on this page. This is shorter than the page size on the last page.
+    pub items: &'a [T],
+    /// Which page this is, starting at 0.
+    pub index: usize,
+    /// The number of pages in the set, at this page size.
+    pub page_count: usize,
+    /// The total number of items in the set.
+    pub total: usize,
+    /// The version of the set this page was taken from.
+    pub version: u32,
+}
+
+impl<T> ResultSets<T> {
+    /// Creates a new store. Sets not accessed for `ttl` are removed by [expire](ResultSets::expire).
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            sets: HashMap::new(),
+            next_handle: 1,
+            ttl,
+        }
+    }
+
+    /// Stores a new result set, returning its handle.
+    pub fn insert(&mut self, items: Vec<T>) -> GmReal {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+
+        self.sets.insert(
+            handle,
+            ResultSet {
+                items,
+                version: 0,
+                last_access: Instant::now(),
+            },
+        );
+
+        GmReal::new(handle as f64)
+    }
+
+    /// Returns page `index` of the set, with `page_size` items per page. Returns `None` if
+    /// the handle is unknown or expired, `page_size` is 0, or the page is past the end.
+    ///
+    /// The first page of an empty set is always available, and is empty.
+    pub fn page(&mut self, handle: GmReal, index: usize, page_size: usize) -> Option<Page<'_, T>> {
+        let set = self.sets.get_mut(&Self::key(handle)?)?;
+        set.last_access = Instant::now();
+
+        if page_size == 0 {
+            return None;
+        }
+
+        let total = set.items.len();
+        let page_count = total.div_ceil(page_size);
+        if index >= page_count.max(1) {
+            return None;
+        }
+
+        let start = index * page_size;
+        let end = (start + page_size).min(total);
+
+        Some(Page {
+            items: &set.items[start..end],
+            index,
+            page_count,
+            total,
+            version: set.version,
+        })
+    }
+
+    /// Returns the number of items in the set.
+    pub fn total(&self, handle: GmReal) -> Option<usize> {
+        self.get(handle).map(|s| s.items.len())
+    }
+
+    /// Returns the current version of the set. Pages with an older version are stale.
+    pub fn version(&self, handle: GmReal) -> Option<u32> {
+        self.get(handle).map(|s| s.version)
+    }
+
+    /// Replaces the contents of a set, invalidating every page handed out so far.
+    /// Returns `false` if the handle is unknown.
+    pub fn update(&mut self, handle: GmReal, items: Vec<T>) -> bool {
+        let Some(set) = Self::key(handle).and_then(|k| self.sets.get_mut(&k)) else {
+            return false;
+        };
+
+        set.items = items;
+        set.version = set.version.wrapping_add(1);
+        set.last_access = Instant::now();
+
+        true
+    }
+
+    /// Removes a set. Returns `false` if the handle was unknown.
+    pub fn remove(&mut self, handle: GmReal) -> bool {
+        Self::key(handle)
+            .and_then(|k| self.sets.remove(&k))
+            .is_some()
+    }
+
+    /// Removes every set which hasn't been accessed within the time-to-live, returning
+    /// how many were removed. Call this once a step or so.
+    pub fn expire(&mut self) -> usize {
+        let before = self.sets.len();
+        let ttl = self.ttl;
+        self.sets.retain(|_, s| s.last_access.elapsed() < ttl);
+
+        before - self.sets.len()
+    }
+
+    /// The number of sets currently stored.
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns if there are no sets stored.
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    fn get(&self, handle: GmReal) -> Option<&ResultSet<T>> {
+        self.sets.get(&Self::key(handle)?)
+    }
+
+    fn key(handle: GmReal) -> Option<u64> {
+        let h = handle.as_f64();
+        if h >= 1.0 && h.fract() == 0.0 {
+            Some(h as u64)
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: AsRef<str>> ResultSets<T> {
+    /// Writes page `index` of the set into `bridge`, with `page_size` items per page, and
+    /// returns the number of items written.
+    ///
+    /// The layout is the set's version, the page's index, the number of pages, the total
+    /// number of items, and the number of items on this page, all as u32s. Then, for each item,
+    /// a u32 length in bytes and the item as a NUL-terminated string, padded to a multiple of 4
+    /// bytes. [GML_READER] reads it back.
+    ///
+    /// If the bridge is too small, nothing is written, and this returns a [NeedsResize]. If
+    /// there's no such page, this returns [OutputCode::FAILURE] and sets the
+    /// [last error](crate::error::last_error).
+    pub fn write_page(
+        &mut self,
+        bridge: &mut Bridge,
+        handle: GmReal,
+        index: usize,
+        page_size: usize,
+    ) -> OutputCode {
+        let Some(page) = self.page(handle, index, page_size) else {
+            return set_last_error(GmError::custom(format_args!(
+                "result set {} has no page {}",
+                handle.as_f64(),
+                index
+            )));
+        };
+
+        let units = 5 + page
+            .items
+            .iter()
+            .map(|item| 1 + BridgeWriter::padded_str_len(item.as_ref()))
+            .sum::<usize>();
+        let mut writer = bridge.writer();
+        if writer.remaining() < units {
+            return NeedsResize(units * 4).into();
+        }
+
+        writer.write_u32(page.version);
+        writer.write_u32(page.index as u32);
+        writer.write_u32(page.page_count as u32);
+        writer.write_u32(page.total as u32);
+        writer.write_u32(page.items.len() as u32);
+        for item in page.items {
+            writer.write_u32(item.as_ref().len() as u32);
+            writer.write_padded_str(item.as_ref());
+        }
+
+        OutputCode::custom(page.items.len() as f64)
+    }
+}
+
+/// A GML function which reads the page written by [ResultSets::write_page] into a
+/// `{ version, index, page_count, total, items }` struct, where `items` is an array of strings.
+pub const GML_READER: &str = r#"/// @param {Id.Buffer} _buffer
+function gm_read_result_page(_buffer) {
+    buffer_seek(_buffer, buffer_seek_start, 0);
+    var _page = {
+        version: buffer_read(_buffer, buffer_u32),
+        index: buffer_read(_buffer, buffer_u32),
+        page_count: buffer_read(_buffer, buffer_u32),
+        total: buffer_read(_buffer, buffer_u32),
+    };
+    var _count = buffer_read(_buffer, buffer_u32);
+    _page.items = array_create(_count);
+    for (var _i = 0; _i < _count; _i++) {
+        var _len = buffer_read(_buffer, buffer_u32);
+        var _start = buffer_tell(_buffer);
+        _page.items[_i] = buffer_read(_buffer, buffer_string);
+        buffer_seek(_buffer, buffer_seek_start, _start + (_len div 4 + 1) * 4);
+    }
+    return _page;
+}
+"#;
+
+/// Generates exports for [ResultSets](crate::pagination::ResultSets) of strings: one which
+/// writes a page of `page_size` items into a GM buffer with
+/// [write_page](crate::pagination::ResultSets::write_page), called `result_page` by default,
+/// one which returns the number of items in a set, called `result_total` by default, and one
+/// which removes a set, called `result_remove` by default. Each set of results needs its own
+/// names, as described in [Naming exports](crate#naming-exports). Writing a page first drops every set which has expired.
+///
+/// The sets must be a `static` `Mutex<ResultSets<T>>` (usually inside a `once_cell` `Lazy`),
+/// where `T` is a string type. In the extension editor, declare `result_page` as taking a
+/// handle, a page index, a buffer address, and the buffer's size in bytes, and the others as
+/// taking a handle. All return a real. `result_total` returns -1, and `result_remove` returns
+/// [OutputCode::FAILURE](crate::OutputCode::FAILURE), for an unknown handle.
+/// ```
+/// # use gm_ffi::pagination::ResultSets;
+/// # use once_cell::sync::Lazy;
+/// # use std::sync::Mutex;
+/// # use std::time::Duration;
+/// static RESULTS: Lazy<Mutex<ResultSets<&'static str>>> =
+///     Lazy::new(|| Mutex::new(ResultSets::new(Duration::from_secs(60))));
+/// gm_ffi::export_result_sets!(RESULTS, 2 => search_page, search_total, search_remove);
+///
+/// let handle = RESULTS.lock().unwrap().insert(vec!["a", "b", "c"]);
+/// let mut backing = vec![0u32; 256];
+/// let ptr = gm_ffi::GmPtr::new(backing.as_mut_ptr() as *const _);
+/// assert_eq!(unsafe { search_page(handle, 1.0, ptr, 1024.0) }.as_f64(), 1.0);
+/// assert_eq!(search_total(handle), 3.0);
+/// assert!(search_remove(handle).is_success());
+/// ```
+#[macro_export]
+macro_rules! export_result_sets {
+    ($sets:path, $page_size:expr) => {
+        $crate::export_result_sets!($sets, $page_size => result_page, result_total, result_remove);
+    };
+    ($sets:path, $page_size:expr => $page:ident, $total:ident, $remove:ident) => {
+        /// Writes a page of a result set into a GM buffer.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $page(
+            handle: $crate::GmReal,
+            index: f64,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                // the bridge is never handed back to GM, so its id doesn't matter
+                let buffer = unsafe { $crate::GmBuffer::new($crate::GmId::dummy(), ptr, len as usize / 4) };
+                let mut bridge = match $crate::Bridge::try_new(buffer) {
+                    Ok(bridge) => bridge,
+                    Err(e) => return e.into(),
+                };
+
+                let mut sets = $sets.lock().unwrap_or_else(|e| e.into_inner());
+                sets.expire();
+                sets.write_page(&mut bridge, handle, index as usize, $page_size)
+            })
+        }
+
+        /// Returns the number of items in a result set, or -1 if there's no such set.
+        #[no_mangle]
+        pub extern "C" fn $total(handle: $crate::GmReal) -> f64 {
+            $crate::catch_ffi!(-1.0 => {
+                $sets
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .total(handle)
+                    .map_or(-1.0, |total| total as f64)
+            })
+        }
+
+        /// Removes a result set.
+        #[no_mangle]
+        pub extern "C" fn $remove(handle: $crate::GmReal) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                $sets
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(handle)
+                    .into()
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paging() {
+        let mut sets = ResultSets::new(Duration::from_secs(60));
+        let handle = sets.insert((0..25).collect::<Vec<u32>>());
+
+        let page = sets.page(handle, 2, 10).unwrap();
+        assert_eq!(page.items, &[20, 21, 22, 23, 24]);
+        assert_eq!(page.page_count, 3);
+        assert_eq!(page.total, 25);
+        assert!(sets.page(handle, 3, 10).is_none());
+
+        assert!(sets.update(handle, vec![1]));
+        assert_eq!(sets.version(handle), Some(1));
+        assert!(sets.remove(handle));
+        assert!(sets.page(handle, 0, 10).is_none());
+    }
+
+    #[test]
+    fn writes_pages() {
+        let mut sets = ResultSets::new(Duration::from_secs(60));
+        let handle = sets.insert(vec!["slime", "bat", "orc"]);
+        let (backing, mut bridge) = crate::test_bridge();
+
+        assert_eq!(sets.write_page(&mut bridge, handle, 0, 2).as_f64(), 2.0);
+        assert_eq!(
+            backing[..11],
+            [
+                0,
+                0,
+                2,
+                3,
+                2,
+                5,
+                u32::from_le_bytes(*b"slim"),
+                u32::from(b'e'),
+                3,
+                u32::from_le_bytes(*b"bat\0"),
+                0
+            ]
+        );
+
+        assert!(sets.write_page(&mut bridge, handle, 2, 2).is_failure());
+        let mut long = ResultSets::new(Duration::from_secs(60));
+        let handle = long.insert(vec!["x".repeat(2000)]);
+        assert_eq!(
+            long.write_page(&mut bridge, handle, 0, 1).required_len(),
+            Some(2028)
+        );
+    }
+
+    #[test]
+    fn expiry() {
+        let mut sets = ResultSets::new(Duration::ZERO);
+        sets.insert(vec![1]);
+
+        assert_eq!(sets.expire(), 1);
+        assert!(sets.is_empty());
+    }
+}
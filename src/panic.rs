@@ -0,0 +1,257 @@
+//! Panic hooks for DLLs, which report panics somewhere a GM developer will actually see them.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// `PanicHookInfo` only has that name since 1.81, and is deprecated under this one after it
+#[allow(deprecated)]
+type PanicHookInfo<'a> = std::panic::PanicInfo<'a>;
+
+/// The number of recent [gm_println](crate::gm_println) lines kept for crash reports.
+pub const RECENT_LINES: usize = 32;
+
+static RECENT: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Remembers a line of output for crash reports. [gm_println](crate::gm_println) and
+/// [gm_print](crate::gm_print) call this for you.
+pub fn record_line(line: &str) {
+    let mut recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    if recent.len() == RECENT_LINES {
+        recent.pop_front();
+    }
+    recent.push_back(line.to_string());
+}
+
+/// Returns the most recently recorded lines of output, oldest first.
+pub fn recent_lines() -> Vec<String> {
+    let recent = RECENT.lock().unwrap_or_else(|e| e.into_inner());
+    recent.iter().cloned().collect()
+}
+
+/// What a [PanicHook] does once it's reported a panic.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PanicPolicy {
+    /// Exits the process with code 1, taking the game down with it.
+    Exit,
+    /// Lets the panic carry on unwinding. If the export was wrapped in
+    /// [catch_ffi](crate::catch_ffi), it returns [OutputCode::FAILURE](crate::OutputCode::FAILURE)
+    /// and the game keeps running. Otherwise, the unwind reaches GM, which usually aborts.
+    Continue,
+    /// Aborts the process immediately, without running any more code.
+    Abort,
+}
+
+impl Default for PanicPolicy {
+    /// [Exit](PanicPolicy::Exit) on macOS, and [Continue](PanicPolicy::Continue) elsewhere,
+    /// which is what `setup_panic_hook` has always done.
+    fn default() -> Self {
+        if cfg!(target_os = "macos") {
+            PanicPolicy::Exit
+        } else {
+            PanicPolicy::Continue
+        }
+    }
+}
+
+/// A configurable panic hook.
+///
+/// `setup_panic_hook` installs one of these with the defaults, but it can be built up by hand
+/// for more control:
+/// ```no_run
+/// # use gm_ffi::panic::{PanicHook, PanicPolicy};
+/// PanicHook::new("my_extension")
+///     .version(env!("CARGO_PKG_VERSION"))
+///     .policy(PanicPolicy::Continue)
+///     .crash_report_dir("crash_reports")
+///     .install();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PanicHook {
+    name: String,
+    version: Option<String>,
+    crash_report_dir: Option<PathBuf>,
+    policy: PanicPolicy,
+    chain_previous: bool,
+}
+
+impl PanicHook {
+    /// Creates a new hook. `name` identifies the DLL in the output.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            version: None,
+            crash_report_dir: None,
+            policy: PanicPolicy::default(),
+            chain_previous: true,
+        }
+    }
+
+    /// Sets the version of the DLL, to include in crash reports. Usually
+    /// `env!("CARGO_PKG_VERSION")`.
+    pub fn version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Writes a crash report into `dir` when a panic happens, with the panic message, a
+    /// backtrace, versions, the time, and the [recent_lines]. This is for shipped builds,
+    /// where there's no console or adam socket to report to.
+    pub fn crash_report_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_report_dir = Some(dir.into());
+        self
+    }
+
+    /// Sets what happens after the panic is reported. See [PanicPolicy].
+    pub fn policy(mut self, policy: PanicPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets whether the hook which was installed before this one still runs, after this hook
+    /// has reported the panic. This is on by default, so hooks from other DLLs or crates (like
+    /// `color-eyre`) keep working.
+    pub fn chain_previous(mut self, chain_previous: bool) -> Self {
+        self.chain_previous = chain_previous;
+        self
+    }
+
+    /// Installs the hook.
+    pub fn install(self) {
+        let previous = self.chain_previous.then(std::panic::take_hook);
+
+        std::panic::set_hook(Box::new(move |info| {
+            self.report(info);
+            if let Some(previous) = &previous {
+                previous(info);
+            }
+            self.finish();
+        }));
+    }
+
+    fn report(&self, info: &PanicHookInfo<'_>) {
+        let mut message = format!("panicked in `{}` at ", self.name);
+        message.push_str(&describe(info));
+        message.push('\n');
+        emit(&message);
+
+        if let Some(dir) = &self.crash_report_dir {
+            match self.write_crash_report(dir, info) {
+                Ok(path) => emit(&format!("crash report written to {}\n", path.display())),
+                Err(e) => emit(&format!("couldn't write crash report: {}\n", e)),
+            }
+        }
+    }
+
+    fn finish(&self) {
+        match self.policy {
+            PanicPolicy::Exit => std::process::exit(1),
+            PanicPolicy::Abort => std::process::abort(),
+            PanicPolicy::Continue => {}
+        }
+    }
+
+    fn write_crash_report(&self, dir: &Path, info: &PanicHookInfo<'_>) -> std::io::Result<PathBuf> {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut report = String::new();
+        let _ = writeln!(report, "`{}` panicked", self.name);
+        let _ = writeln!(
+            report,
+            "version: {}",
+            self.version.as_deref().unwrap_or("unknown")
+        );
+        let _ = writeln!(report, "gm-ffi version: {}", env!("CARGO_PKG_VERSION"));
+        let _ = writeln!(report, "time: {} (unix seconds)", time);
+        let _ = writeln!(report, "panic: {}", describe(info));
+        let _ = writeln!(
+            report,
+            "\nbacktrace:\n{}",
+            std::backtrace::Backtrace::force_capture()
+        );
+        let _ = writeln!(report, "recent output:");
+        for line in recent_lines() {
+            let _ = writeln!(report, "{}", line);
+        }
+
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}-crash-{}.txt", self.name, time));
+        std::fs::write(&path, report)?;
+
+        Ok(path)
+    }
+}
+
+/// Formats a panic as `'message', location`.
+fn describe(info: &PanicHookInfo<'_>) -> String {
+    let mut output = String::new();
+
+    if let Some(message) = info.payload().downcast_ref::<String>() {
+        let _ = write!(output, "'{}', ", message);
+    } else if let Some(message) = info.payload().downcast_ref::<&'static str>() {
+        let _ = write!(output, "'{}', ", message);
+    }
+
+    if let Some(location) = info.location() {
+        let _ = write!(output, "{}", location);
+    }
+
+    output
+}
+
+/// Writes panic output wherever this platform's `gm_println` goes.
+fn emit(output: &str) {
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    crate::GmStdOut::stdout().write_str(output);
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    print!("{}", output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_lines_are_capped() {
+        for i in 0..RECENT_LINES + 2 {
+            record_line(&i.to_string());
+        }
+
+        let lines = recent_lines();
+        assert_eq!(lines.len(), RECENT_LINES);
+        assert_eq!(lines.last().map(String::as_str), Some("33"));
+    }
+
+    #[test]
+    fn default_policy() {
+        let expected = if cfg!(target_os = "macos") {
+            PanicPolicy::Exit
+        } else {
+            PanicPolicy::Continue
+        };
+        assert_eq!(PanicHook::new("test").policy, expected);
+    }
+
+    #[test]
+    fn chains_previous_hook() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        static CALLED: AtomicBool = AtomicBool::new(false);
+
+        let original = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| CALLED.store(true, Ordering::SeqCst)));
+        PanicHook::new("test")
+            .policy(PanicPolicy::Continue)
+            .install();
+
+        let _ = std::panic::catch_unwind(|| panic!("chained"));
+        std::panic::set_hook(original);
+
+        assert!(CALLED.load(Ordering::SeqCst));
+    }
+}
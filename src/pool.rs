@@ -0,0 +1,266 @@
+//! A pool of GM buffers, registered up front and checked out as needed, so we don't
+//! need GM to create and destroy buffers every frame.
+
+use crate::{GmBuffer, GmId};
+use core::fmt;
+
+/// A pool of pre-registered [GmBuffer]s.
+///
+/// The protocol is:
+/// 1. At boot, GM creates N buffers and hands each to an export which calls [register](BufferPool::register).
+/// 2. When Rust needs a buffer, it calls [acquire](BufferPool::acquire), fills the buffer, and returns its
+///    id to GM.
+/// 3. When GM is done reading it, it calls an export which calls [release](BufferPool::release) with that id.
+///
+/// Since [GmBuffer]s stay on the GM thread, the pool usually lives in a thread local, and
+/// [export_pool](crate::export_pool) generates the exports for each step:
+/// ```
+/// # use gm_ffi::pool::BufferPool;
+/// # use std::cell::RefCell;
+/// thread_local! {
+///     static POOL: RefCell<BufferPool<u8>> = const { RefCell::new(BufferPool::new()) };
+/// }
+///
+/// gm_ffi::export_pool!(POOL);
+/// ```
+#[derive(Debug)]
+pub struct BufferPool<T: 'static> {
+    buffers: Vec<PoolEntry<T>>,
+}
+
+#[derive(Debug)]
+struct PoolEntry<T: 'static> {
+    buffer: GmBuffer<T>,
+    in_use: bool,
+}
+
+/// An error returned by [BufferPool::release].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReleaseError {
+    /// No buffer with that id was registered.
+    Unknown,
+    /// The buffer wasn't checked out.
+    NotAcquired,
+}
+
+impl fmt::Display for ReleaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReleaseError::Unknown => f.write_str("no buffer with that id is in the pool"),
+            ReleaseError::NotAcquired => f.write_str("the buffer wasn't checked out"),
+        }
+    }
+}
+
+impl std::error::Error for ReleaseError {}
+
+impl<T> BufferPool<T> {
+    /// Creates a new, empty pool.
+    pub const fn new() -> Self {
+        Self {
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Adds a buffer to the pool, ready to be acquired.
+    pub fn register(&mut self, buffer: GmBuffer<T>) {
+        self.buffers.push(PoolEntry {
+            buffer,
+            in_use: false,
+        });
+    }
+
+    /// Checks out a free buffer, returning its id and the buffer. Returns `None` if every
+    /// buffer is in use.
+    pub fn acquire(&mut self) -> Option<(GmId, &mut GmBuffer<T>)> {
+        let entry = self.buffers.iter_mut().find(|e| !e.in_use)?;
+        entry.in_use = true;
+
+        Some((entry.buffer.id, &mut entry.buffer))
+    }
+
+    /// Returns a checked out buffer.
+    pub fn get_mut(&mut self, id: GmId) -> Option<&mut GmBuffer<T>> {
+        self.buffers
+            .iter_mut()
+            .find(|e| e.in_use && e.buffer.id == id)
+            .map(|e| &mut e.buffer)
+    }
+
+    /// Returns a buffer to the pool.
+    pub fn release(&mut self, id: GmId) -> Result<(), ReleaseError> {
+        let entry = self
+            .buffers
+            .iter_mut()
+            .find(|e| e.buffer.id == id)
+            .ok_or(ReleaseError::Unknown)?;
+
+        if !entry.in_use {
+            return Err(ReleaseError::NotAcquired);
+        }
+        entry.in_use = false;
+
+        Ok(())
+    }
+
+    /// The number of buffers ready to be acquired.
+    pub fn available(&self) -> usize {
+        self.buffers.iter().filter(|e| !e.in_use).count()
+    }
+
+    /// The number of buffers currently checked out.
+    pub fn in_use(&self) -> usize {
+        self.buffers.len() - self.available()
+    }
+
+    /// The total number of buffers in the pool.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Returns if no buffers have been registered.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// Empties the pool, returning every buffer's id so GM can free them.
+    pub fn drain(&mut self) -> Vec<GmId> {
+        self.buffers.drain(..).map(|e| e.buffer.id()).collect()
+    }
+}
+
+impl<T> Default for BufferPool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a length in bytes into a length in `T`s, for [export_pool](crate::export_pool).
+#[doc(hidden)]
+pub fn element_len<T>(_pool: &BufferPool<T>, byte_len: f64) -> usize {
+    byte_len as usize / core::mem::size_of::<T>().max(1)
+}
+
+/// Generates the exports for a [BufferPool](crate::pool::BufferPool): one which registers a GM
+/// buffer with the pool, called `pool_register` by default, one which checks out a free buffer
+/// and returns its id, called `pool_acquire` by default, and one which returns a buffer to the
+/// pool, called `pool_release` by default. Each pool needs its own names, as described in
+/// [Naming exports](crate#naming-exports).
+///
+/// The pool must be a `thread_local` `RefCell<BufferPool<T>>`, since [GmBuffer](crate::GmBuffer)s
+/// stay on the GM thread. In the extension editor, declare `pool_register` as taking a buffer
+/// id, a buffer address, and the buffer's size in bytes, `pool_acquire` as taking no arguments,
+/// and `pool_release` as taking a buffer id. All return a real. `pool_acquire` returns -1 if
+/// every buffer is in use, and `pool_release` returns
+/// [OutputCode::FAILURE](crate::OutputCode::FAILURE) if the buffer wasn't checked out, both
+/// setting the [last error](crate::error::last_error).
+/// ```
+/// # use gm_ffi::pool::BufferPool;
+/// # use std::cell::RefCell;
+/// thread_local! {
+///     static POOL: RefCell<BufferPool<u32>> = const { RefCell::new(BufferPool::new()) };
+/// }
+/// gm_ffi::export_pool!(POOL => scratch_register, scratch_acquire, scratch_release);
+///
+/// let mut backing = [0u32; 4];
+/// let ptr = gm_ffi::GmPtr::new(backing.as_mut_ptr() as *const _);
+/// let id = gm_ffi::GmId::from_raw(3.0);
+/// assert!(unsafe { scratch_register(id, ptr, 16.0) }.is_success());
+///
+/// assert_eq!(scratch_acquire(), id);
+/// assert_eq!(scratch_acquire(), gm_ffi::GmId::from_raw(-1.0));
+/// assert!(scratch_release(id).is_success());
+/// assert!(scratch_release(id).is_failure());
+/// ```
+/// ```gml
+/// var _buffer = pool_acquire();
+/// if (_buffer != -1) {
+///     fill_particles(_buffer, buffer_get_address(_buffer), buffer_get_size(_buffer));
+///     // ...
+///     pool_release(_buffer);
+/// }
+/// ```
+#[macro_export]
+macro_rules! export_pool {
+    ($pool:path) => {
+        $crate::export_pool!($pool => pool_register, pool_acquire, pool_release);
+    };
+    ($pool:path => $register:ident, $acquire:ident, $release:ident) => {
+        /// Registers a GM buffer with the pool.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long, which stays
+        /// alive until it's drained from the pool.
+        #[no_mangle]
+        pub unsafe extern "C" fn $register(
+            id: $crate::GmId,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                $pool.with_borrow_mut(|pool| {
+                    let len = $crate::pool::element_len(pool, len);
+                    pool.register(unsafe { $crate::GmBuffer::new(id, ptr, len) });
+                });
+
+                $crate::OutputCode::SUCCESS
+            })
+        }
+
+        /// Checks out a free buffer from the pool, returning its id, or -1 if every buffer is
+        /// in use.
+        #[no_mangle]
+        pub extern "C" fn $acquire() -> $crate::GmId {
+            $crate::catch_ffi!($crate::GmId::from_raw(-1.0) => {
+                match $pool.with_borrow_mut(|pool| pool.acquire().map(|(id, _)| id)) {
+                    Some(id) => id,
+                    None => {
+                        $crate::error::set_last_error("every buffer in the pool is in use");
+                        $crate::GmId::from_raw(-1.0)
+                    }
+                }
+            })
+        }
+
+        /// Returns a buffer to the pool.
+        #[no_mangle]
+        pub extern "C" fn $release(id: $crate::GmId) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                match $pool.with_borrow_mut(|pool| pool.release(id)) {
+                    Ok(()) => $crate::OutputCode::SUCCESS,
+                    Err(e) => $crate::error::set_last_error(e),
+                }
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn acquire_release() {
+        let mut backing = vec![0u8; 8];
+        let ptr = GmPtr::new(backing.as_mut_ptr() as *const _);
+
+        let mut pool = BufferPool::<u8>::new();
+        pool.register(unsafe { GmBuffer::new(GmId::new(1.0), ptr, 4) });
+        pool.register(unsafe { GmBuffer::new(GmId::new(2.0), GmPtr::new(ptr.add(4)), 4) });
+
+        let (first, _) = pool.acquire().unwrap();
+        let (second, buf) = pool.acquire().unwrap();
+        buf[0] = 9;
+        assert!(pool.acquire().is_none());
+        assert_eq!(backing[4], 9);
+
+        assert_eq!(pool.release(first), Ok(()));
+        assert_eq!(pool.release(first), Err(ReleaseError::NotAcquired));
+        assert_eq!(pool.release(GmId::new(3.0)), Err(ReleaseError::Unknown));
+        assert_eq!(pool.available(), 1);
+
+        assert!(pool.get_mut(second).is_some());
+        assert_eq!(pool.drain().len(), 2);
+    }
+}
@@ -0,0 +1,151 @@
+//! A place to keep long-lived [GmBuffer]s, so exports which are only handed a [GmId]
+//! can find the buffer again.
+
+use crate::{GmBuffer, GmId};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+/// Stores [GmBuffer]s keyed by their [GmId].
+///
+/// Every buffer should eventually be taken back out with [remove](BufferRegistry::remove)
+/// before GM frees it. Anything still in here at shutdown shows up in [leak_report](BufferRegistry::leak_report).
+#[derive(Debug)]
+pub struct BufferRegistry<T: 'static> {
+    buffers: HashMap<GmId, Entry<T>>,
+}
+
+#[derive(Debug)]
+struct Entry<T: 'static> {
+    buffer: GmBuffer<T>,
+    registered_at: Instant,
+    #[cfg(debug_assertions)]
+    backtrace: std::backtrace::Backtrace,
+}
+
+impl<T> BufferRegistry<T> {
+    /// Creates a new, empty registry.
+    pub fn new() -> Self {
+        Self {
+            buffers: HashMap::new(),
+        }
+    }
+
+    /// Registers a buffer under its id. If a buffer was already registered with that id,
+    /// it's replaced and returned.
+    pub fn register(&mut self, buffer: GmBuffer<T>) -> Option<GmBuffer<T>> {
+        let entry = Entry {
+            buffer,
+            registered_at: Instant::now(),
+            #[cfg(debug_assertions)]
+            backtrace: std::backtrace::Backtrace::force_capture(),
+        };
+
+        self.buffers
+            .insert(entry.buffer.id, entry)
+            .map(|e| e.buffer)
+    }
+
+    /// Returns the buffer registered with `id`.
+    pub fn get(&self, id: GmId) -> Option<&GmBuffer<T>> {
+        self.buffers.get(&id).map(|e| &e.buffer)
+    }
+
+    /// Returns the buffer registered with `id`, mutably.
+    pub fn get_mut(&mut self, id: GmId) -> Option<&mut GmBuffer<T>> {
+        self.buffers.get_mut(&id).map(|e| &mut e.buffer)
+    }
+
+    /// Removes the buffer registered with `id`, giving up the buffer and returning its id
+    /// so it can be handed back to GM to free.
+    ///
+    /// In debug builds, this panics if the buffer had a canary and it's been overwritten,
+    /// since GM has probably freed the buffer while it was still registered.
+    pub fn remove(&mut self, id: GmId) -> Option<GmId> {
+        self.buffers.remove(&id).map(|e| {
+            e.buffer.debug_check_canary();
+            e.buffer.id()
+        })
+    }
+
+    /// Returns if a buffer is registered with `id`.
+    pub fn contains(&self, id: GmId) -> bool {
+        self.buffers.contains_key(&id)
+    }
+
+    /// The number of registered buffers.
+    pub fn len(&self) -> usize {
+        self.buffers.len()
+    }
+
+    /// Returns if there are no registered buffers.
+    pub fn is_empty(&self) -> bool {
+        self.buffers.is_empty()
+    }
+
+    /// Returns the id, length in bytes, and age of every buffer still registered.
+    pub fn leaks(&self) -> Vec<(GmId, usize, Duration)> {
+        let mut output: Vec<_> = self
+            .buffers
+            .values()
+            .map(|e| (e.buffer.id, e.buffer.byte_len(), e.registered_at.elapsed()))
+            .collect();
+        output.sort_by_key(|e| core::cmp::Reverse(e.2));
+
+        output
+    }
+
+    /// Returns a human readable report of every buffer still registered, oldest first. In
+    /// debug builds, this includes a backtrace of where each was registered. This is empty if
+    /// there's nothing registered.
+    pub fn leak_report(&self) -> String {
+        let mut output = String::new();
+
+        for (id, bytes, age) in self.leaks() {
+            writeln!(
+                output,
+                "buffer {:?}: {} bytes, registered {:.1}s ago",
+                id.0,
+                bytes,
+                age.as_secs_f64()
+            )
+            .unwrap();
+
+            #[cfg(debug_assertions)]
+            for line in self.buffers[&id].backtrace.to_string().lines() {
+                writeln!(output, "    {}", line).unwrap();
+            }
+        }
+
+        output
+    }
+}
+
+impl<T> Default for BufferRegistry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn register_and_remove() {
+        let mut backing = vec![0u8; 8];
+        let ptr = GmPtr::new(backing.as_mut_ptr() as *const _);
+
+        let mut registry = BufferRegistry::<u8>::new();
+        registry.register(unsafe { GmBuffer::new(GmId::new(3.0), ptr, 8) });
+
+        registry.get_mut(GmId::new(3.0)).unwrap()[0] = 1;
+        assert!(registry.leak_report().starts_with("buffer 3.0: 8 bytes"));
+
+        assert_eq!(registry.remove(GmId::new(3.0)), Some(GmId::new(3.0)));
+        assert!(registry.remove(GmId::new(3.0)).is_none());
+        assert!(registry.leak_report().is_empty());
+        assert_eq!(backing[0], 1);
+    }
+}
@@ -0,0 +1,121 @@
+//! Turning whatever an export body produces into a [Bridge] payload and an [OutputCode].
+
+use crate::{error::set_last_error, Bridge, BridgeWriter, GmError, OutputCode};
+
+/// Something an export can return. It writes its payload (if it has one) into a
+/// [BridgeWriter], and says which [OutputCode] GM should get.
+///
+/// Errors and `None`s return [OutputCode::FAILURE], and errors also set the
+/// [last error](crate::error::last_error).
+/// ```
+/// # use gm_ffi::{Bridge, GmBuffer, GmError, GmId, GmPtr, OutputCode};
+/// fn find_spawn(level: f64) -> Result<(f32, f32), GmError> {
+///     if level < 0.0 {
+///         return Err(GmError::custom("no such level"));
+///     }
+///     Ok((16.0, 32.0))
+/// }
+///
+/// #[no_mangle]
+/// pub unsafe extern "C" fn spawn_point(id: GmId, ptr: GmPtr, level: f64) -> OutputCode {
+///     let mut bridge = Bridge::new(GmBuffer::new(id, ptr, 256));
+///     bridge.write_return(find_spawn(level))
+/// }
+/// ```
+pub trait GmReturn {
+    /// Writes the payload and returns the code for GM.
+    fn write_to(self, writer: &mut BridgeWriter<'_>) -> OutputCode;
+}
+
+impl Bridge {
+    /// Writes `value` into the start of the bridge, returning its [OutputCode].
+    pub fn write_return(&mut self, value: impl GmReturn) -> OutputCode {
+        value.write_to(&mut self.writer())
+    }
+}
+
+impl GmReturn for () {
+    fn write_to(self, _: &mut BridgeWriter<'_>) -> OutputCode {
+        OutputCode::SUCCESS
+    }
+}
+
+impl GmReturn for OutputCode {
+    fn write_to(self, _: &mut BridgeWriter<'_>) -> OutputCode {
+        self
+    }
+}
+
+macro_rules! number_returns {
+    ($($ty:ty => |$writer:ident, $value:ident| $write:expr),* $(,)?) => {
+        $(
+            impl GmReturn for $ty {
+                fn write_to(self, $writer: &mut BridgeWriter<'_>) -> OutputCode {
+                    let $value = self;
+                    $write;
+                    OutputCode::SUCCESS
+                }
+            }
+        )*
+    };
+}
+
+number_returns!(
+    u8 => |w, v| w.write_u32(v.into()),
+    u16 => |w, v| w.write_u32(v.into()),
+    u32 => |w, v| w.write_u32(v),
+    i32 => |w, v| w.write_u32(v as u32),
+    bool => |w, v| w.write_u32(v.into()),
+    f32 => |w, v| w.write_f32(v),
+    f64 => |w, v| w.write_f64(v),
+);
+
+impl<A: GmReturn, B: GmReturn> GmReturn for (A, B) {
+    fn write_to(self, writer: &mut BridgeWriter<'_>) -> OutputCode {
+        let code = self.0.write_to(writer);
+        if !code.is_success() {
+            return code;
+        }
+
+        self.1.write_to(writer)
+    }
+}
+
+impl<T: GmReturn> GmReturn for Option<T> {
+    fn write_to(self, writer: &mut BridgeWriter<'_>) -> OutputCode {
+        match self {
+            Some(v) => v.write_to(writer),
+            None => OutputCode::FAILURE,
+        }
+    }
+}
+
+impl<T: GmReturn> GmReturn for Result<T, GmError> {
+    fn write_to(self, writer: &mut BridgeWriter<'_>) -> OutputCode {
+        match self {
+            Ok(v) => v.write_to(writer),
+            Err(e) => set_last_error(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::take_last_error, test_bridge};
+
+    #[test]
+    fn writes_payloads() {
+        let (backing, mut bridge) = test_bridge();
+
+        assert!(bridge.write_return(Some((7u32, 2.5f64))).is_success());
+        assert!(bridge.write_return(None::<u32>).is_failure());
+
+        let err: Result<(), GmError> = Err(GmError::NullPointer);
+        assert!(bridge.write_return(err).is_failure());
+        assert!(take_last_error().is_some());
+
+        let bits = 2.5f64.to_bits();
+        assert_eq!(backing[..3], [7, bits as u32, (bits >> 32) as u32]);
+    }
+}
@@ -0,0 +1,201 @@
+//! A single-producer, single-consumer ring buffer laid over a GM buffer, so Rust can
+//! stream data out and GML can read it whenever it gets around to it.
+
+use crate::{GmBuffer, GmId};
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// A ring buffer over a `GmBuffer<u8>`, where Rust produces `T`s and GML consumes them.
+///
+/// The buffer is laid out as:
+/// - bytes `0..4`: the head, a `buffer_u32`. This is the next slot Rust will write to,
+///   and is only ever written by Rust.
+/// - bytes `4..8`: the tail, a `buffer_u32`. This is the next slot GML will read from,
+///   and is only ever written by GML.
+/// - bytes `8..`: the slots, each `size_of::<T>()` bytes.
+///
+/// The ring is empty when `head == tail`, and one slot is always kept free so that
+/// a full ring can be told apart from an empty one. On the GML side, reading looks like:
+/// ```gml
+/// var _head = buffer_peek(ring, 0, buffer_u32);
+/// var _tail = buffer_peek(ring, 4, buffer_u32);
+/// while (_tail != _head) {
+///     var _sample = buffer_peek(ring, 8 + _tail * 4, buffer_f32);
+///     _tail = (_tail + 1) mod slot_count;
+/// }
+/// buffer_poke(ring, 4, buffer_u32, _tail);
+/// ```
+#[derive(Debug)]
+pub struct GmRingBuffer<T: Copy + 'static> {
+    buffer: GmBuffer<u8>,
+    slots: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy + 'static> GmRingBuffer<T> {
+    /// The size of the head and tail header at the start of the buffer, in bytes.
+    pub const HEADER_SIZE: usize = 8;
+
+    /// Creates a new ring buffer over `buffer`, resetting the head and tail to 0.
+    ///
+    /// Returns the buffer back if it isn't aligned to 4 bytes, for the atomic header, if `T`
+    /// is zero sized, or if it can't fit at least two slots. `T` itself can have any alignment.
+    pub fn new(mut buffer: GmBuffer<u8>) -> Result<Self, GmBuffer<u8>> {
+        let size = core::mem::size_of::<T>();
+
+        if size == 0 || buffer.as_bytes().as_ptr().align_offset(4) != 0 {
+            return Err(buffer);
+        }
+
+        let slots = buffer.byte_len().saturating_sub(Self::HEADER_SIZE) / size;
+        let Ok(slots) = u32::try_from(slots) else {
+            return Err(buffer);
+        };
+        if slots < 2 {
+            return Err(buffer);
+        }
+
+        buffer.as_bytes_mut()[..Self::HEADER_SIZE].fill(0);
+
+        Ok(Self {
+            buffer,
+            slots,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The total number of slots. One less than this can be filled at once.
+    pub fn slot_count(&self) -> u32 {
+        self.slots
+    }
+
+    /// The most elements the ring can hold at once.
+    pub fn capacity(&self) -> usize {
+        self.slots as usize - 1
+    }
+
+    /// The number of elements written which GML hasn't consumed yet.
+    ///
+    /// If GML has written a tail past the last slot, the ring is corrupt, and this is 0.
+    pub fn len(&self) -> usize {
+        let Some((head, tail)) = self.indices() else {
+            return 0;
+        };
+
+        ((head as u64 + self.slots as u64 - tail as u64) % self.slots as u64) as usize
+    }
+
+    /// Returns if GML has consumed everything we've written.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns if there's no room left to push.
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Pushes a value into the ring. Returns `false`, dropping the value, if GML
+    /// hasn't made room for it yet, or has written a tail past the last slot.
+    pub fn push(&mut self, value: T) -> bool {
+        let Some((head, tail)) = self.indices() else {
+            return false;
+        };
+        let next = ((head as u64 + 1) % self.slots as u64) as u32;
+        if next == tail {
+            return false;
+        }
+
+        unsafe {
+            let slot = self
+                .buffer
+                .as_bytes_mut()
+                .as_mut_ptr()
+                .add(Self::HEADER_SIZE + head as usize * core::mem::size_of::<T>());
+            // slots are only `size_of::<T>()` apart, after an 8 byte header, so a `T` with a
+            // bigger alignment than that can't be written in place
+            (slot as *mut T).write_unaligned(value);
+        }
+        self.head().store(next, Ordering::Release);
+
+        true
+    }
+
+    /// Pushes as many values from `values` as will fit, returning how many made it in.
+    pub fn push_slice(&mut self, values: &[T]) -> usize {
+        values.iter().take_while(|v| self.push(**v)).count()
+    }
+
+    /// Gives up the ring buffer, returning the id of the underlying buffer.
+    pub fn id(self) -> GmId {
+        self.buffer.id()
+    }
+
+    // GML owns the tail (and could scribble on the head), so neither is trusted
+    fn indices(&self) -> Option<(u32, u32)> {
+        let head = self.head().load(Ordering::Acquire);
+        let tail = self.tail().load(Ordering::Acquire);
+
+        (head < self.slots && tail < self.slots).then_some((head, tail))
+    }
+
+    fn head(&self) -> &AtomicU32 {
+        unsafe { &*(self.buffer.as_bytes().as_ptr() as *const AtomicU32) }
+    }
+
+    fn tail(&self) -> &AtomicU32 {
+        unsafe { &*(self.buffer.as_bytes().as_ptr().add(4) as *const AtomicU32) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn produce_and_consume() {
+        let mut backing = vec![0u32; 2 + 4];
+        let ptr = GmPtr::new(backing.as_mut_ptr() as *const _);
+        let buffer = unsafe { GmBuffer::new(GmId::new(0.0), ptr, 24) };
+
+        let mut ring = GmRingBuffer::<f32>::new(buffer).unwrap();
+        assert_eq!(ring.capacity(), 3);
+        assert_eq!(ring.push_slice(&[1.0, 2.0, 3.0, 4.0]), 3);
+        assert!(ring.is_full());
+
+        // pretend GML read two of them
+        unsafe { (backing.as_mut_ptr().add(1)).write(2) };
+        assert_eq!(ring.len(), 1);
+        assert!(ring.push(5.0));
+
+        assert_eq!(backing[0], 0);
+        assert_eq!(f32::from_bits(backing[2 + 3]), 5.0);
+        assert_eq!(f32::from_bits(backing[2 + 2]), 3.0);
+
+        // a tail past the end is treated as corrupt, rather than overflowing
+        unsafe { (backing.as_mut_ptr().add(1)).write(u32::MAX) };
+        assert_eq!(ring.len(), 0);
+        assert!(!ring.push(6.0));
+    }
+
+    #[test]
+    fn slots_can_be_unaligned() {
+        let mut backing = vec![0u32; 2 + 2 * 3 + 1];
+        // start 4 bytes off from 8 byte alignment, so the f64 slots are misaligned
+        let start = if backing.as_ptr().align_offset(8) == 0 {
+            1
+        } else {
+            0
+        };
+        let ptr = GmPtr::new(unsafe { backing.as_mut_ptr().add(start) } as *const _);
+        let buffer = unsafe { GmBuffer::new(GmId::new(0.0), ptr, 32) };
+
+        let mut ring = GmRingBuffer::<f64>::new(buffer).unwrap();
+        assert!(ring.push(1.5));
+        assert_eq!(
+            f64::from_bits(u64::from(backing[start + 2]) | u64::from(backing[start + 3]) << 32),
+            1.5
+        );
+    }
+}
@@ -0,0 +1,335 @@
+//! Raw bindings to the runner's extension interface, so DLLs can use the runner's own
+//! facilities, like async events and debug output, instead of routing everything through
+//! buffers.
+//!
+//! The runner hands these to a DLL in one of two ways:
+//! - Newer runners call `YYExtensionInitialise` with a [YYRunnerInterface], a table of
+//!   function pointers. [export_runner_init](crate::export_runner_init) generates it.
+//! - Every runner calls `RegisterCallbacks` with four function pointers for firing async
+//!   events with a `ds_map`. [export_register_callbacks](crate::export_register_callbacks)
+//!   generates it.
+//!
+//! Either one fills in the [callbacks] used by the rest of the crate.
+
+use core::ffi::{c_char, c_void};
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::RwLock;
+
+/// The async event GM fires for `CreateAsyncEventWithDSMap`s from extensions: Async - Social.
+pub const EVENT_OTHER_SOCIAL: i32 = 70;
+
+/// An opaque RValue, GM's dynamically typed value. Only ever used behind a pointer.
+#[repr(C)]
+pub struct RValue {
+    _private: [u8; 0],
+}
+
+/// An opaque instance. Only ever used behind a pointer.
+#[repr(C)]
+pub struct CInstance {
+    _private: [u8; 0],
+}
+
+/// The start of the runner's `YYRunnerInterface` table, from the extension SDK's
+/// `YYRunnerInterface.h`.
+///
+/// Only the fields up to `pHTTP_ID` are bound. Newer runners append more, so this is only ever
+/// read through the pointer the runner gives us, and never built or copied on our side. Fields
+/// whose signatures involve types we don't bind are left as plain pointers.
+#[repr(C)]
+#[allow(non_snake_case, missing_docs)]
+pub struct YYRunnerInterface {
+    pub DebugConsoleOutput: Option<unsafe extern "C" fn(fmt: *const c_char, ...)>,
+    pub ReleaseConsoleOutput: Option<unsafe extern "C" fn(fmt: *const c_char, ...)>,
+    pub ShowMessage: Option<unsafe extern "C" fn(msg: *const c_char)>,
+    pub YYError: Option<unsafe extern "C" fn(error: *const c_char, ...)>,
+
+    pub YYAlloc: Option<unsafe extern "C" fn(size: i32) -> *mut c_void>,
+    pub YYRealloc:
+        Option<unsafe extern "C" fn(original: *mut c_void, new_size: i32) -> *mut c_void>,
+    pub YYFree: Option<unsafe extern "C" fn(p: *const c_void)>,
+    pub YYStrDup: Option<unsafe extern "C" fn(s: *const c_char) -> *const c_char>,
+
+    pub YYGetBool: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> bool>,
+    pub YYGetFloat: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> f32>,
+    pub YYGetReal: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> f64>,
+    pub YYGetInt32: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> i32>,
+    pub YYGetUint32: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> u32>,
+    pub YYGetInt64: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> i64>,
+    pub YYGetPtr: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> *mut c_void>,
+    pub YYGetPtrOrInt: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> isize>,
+    pub YYGetString: Option<unsafe extern "C" fn(base: *const RValue, index: i32) -> *const c_char>,
+
+    pub BOOL_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> bool>,
+    pub REAL_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> f64>,
+    pub PTR_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> *mut c_void>,
+    pub INT64_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> i64>,
+    pub INT32_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> i32>,
+
+    pub HASH_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> i32>,
+
+    pub COPY_RValue: Option<unsafe extern "C" fn(dest: *mut RValue, source: *const RValue)>,
+    pub KIND_RValue: Option<unsafe extern "C" fn(value: *const RValue) -> i32>,
+    pub FREE_RValue: Option<unsafe extern "C" fn(value: *mut RValue)>,
+    pub YYCreateString: Option<unsafe extern "C" fn(value: *mut RValue, s: *const c_char)>,
+
+    pub YYCreateArray:
+        Option<unsafe extern "C" fn(value: *mut RValue, n_values: i32, values: *const f64)>,
+
+    pub Script_Find_Id: Option<unsafe extern "C" fn(name: *const c_char) -> i32>,
+    pub Script_Perform: Option<
+        unsafe extern "C" fn(
+            index: i32,
+            self_instance: *mut CInstance,
+            other_instance: *mut CInstance,
+            argc: i32,
+            result: *mut RValue,
+            args: *mut RValue,
+        ) -> bool,
+    >,
+
+    pub Code_Function_Find:
+        Option<unsafe extern "C" fn(name: *const c_char, index: *mut i32) -> bool>,
+
+    pub HTTP_Get: *const c_void,
+    pub HTTP_Post: *const c_void,
+    pub HTTP_Request: *const c_void,
+
+    pub ASYNCFunc_SpriteAdd: *const c_void,
+    pub ASYNCFunc_SpriteCleanup: *const c_void,
+    pub CreateSpriteAsync: *const c_void,
+
+    pub Timing_Time: Option<unsafe extern "C" fn() -> i64>,
+    pub Timing_Sleep: Option<unsafe extern "C" fn(sleep: i64, precise: bool)>,
+
+    pub YYMutexCreate: Option<unsafe extern "C" fn(name: *const c_char) -> *mut c_void>,
+    pub YYMutexDestroy: Option<unsafe extern "C" fn(mutex: *mut c_void)>,
+    pub YYMutexLock: Option<unsafe extern "C" fn(mutex: *mut c_void)>,
+    pub YYMutexUnlock: Option<unsafe extern "C" fn(mutex: *mut c_void)>,
+
+    pub CreateAsyncEventWithDSMap: Option<unsafe extern "C" fn(map: i32, event: i32)>,
+    pub CreateAsyncEventWithDSMapAndBuffer:
+        Option<unsafe extern "C" fn(map: i32, buffer: i32, event: i32)>,
+    pub CreateDsMap: Option<unsafe extern "C" fn(num: i32, ...) -> i32>,
+
+    pub DsMapAddDouble:
+        Option<unsafe extern "C" fn(map: i32, key: *const c_char, value: f64) -> bool>,
+    pub DsMapAddString:
+        Option<unsafe extern "C" fn(map: i32, key: *const c_char, value: *const c_char) -> bool>,
+    pub DsMapAddInt64:
+        Option<unsafe extern "C" fn(map: i32, key: *const c_char, value: i64) -> bool>,
+
+    pub BufferGetContent:
+        Option<unsafe extern "C" fn(index: i32, data: *mut *mut c_void, size: *mut i32) -> bool>,
+    pub BufferWriteContent: Option<
+        unsafe extern "C" fn(
+            index: i32,
+            dest_offset: i32,
+            source: *const c_void,
+            size: i32,
+            grow: bool,
+            wrap: bool,
+        ) -> i32,
+    >,
+    pub CreateBuffer: Option<unsafe extern "C" fn(size: i32, format: i32, alignment: i32) -> i32>,
+
+    pub pLiveConnection: *mut bool,
+    pub pHTTP_ID: *mut i32,
+}
+
+/// The functions for firing async events with a `ds_map`, which is everything the runner gives
+/// a DLL through `RegisterCallbacks`.
+#[derive(Debug, Clone, Copy)]
+pub struct Callbacks {
+    /// Fires an async event for a `ds_map`, which GM then destroys.
+    pub create_async_event_with_ds_map: unsafe extern "C" fn(map: i32, event: i32),
+    /// Creates a `ds_map`. Pass 0 for an empty one.
+    pub create_ds_map: unsafe extern "C" fn(num: i32, ...) -> i32,
+    /// Adds a real to a `ds_map`.
+    pub ds_map_add_double: unsafe extern "C" fn(map: i32, key: *const c_char, value: f64) -> bool,
+    /// Adds a string to a `ds_map`. GM copies it.
+    pub ds_map_add_string:
+        unsafe extern "C" fn(map: i32, key: *const c_char, value: *const c_char) -> bool,
+}
+
+static RUNNER: AtomicPtr<YYRunnerInterface> = AtomicPtr::new(core::ptr::null_mut());
+static CALLBACKS: RwLock<Option<Callbacks>> = RwLock::new(None);
+
+/// Stores the interface the runner passes to `YYExtensionInitialise`. This is what
+/// [export_runner_init](crate::export_runner_init) calls.
+///
+/// If `size` is smaller than the part of the interface we bind, it's an older runner, and the
+/// interface is ignored.
+///
+/// # Safety
+/// `functions` must be null, or the runner's interface, which lives for the rest of the game.
+pub unsafe fn init(functions: *const YYRunnerInterface, size: usize) {
+    if functions.is_null() || size < core::mem::size_of::<YYRunnerInterface>() {
+        return;
+    }
+
+    RUNNER.store(functions as *mut _, Ordering::Release);
+
+    let functions = unsafe { &*functions };
+    if let (Some(create_async), Some(create_ds_map), Some(add_double), Some(add_string)) = (
+        functions.CreateAsyncEventWithDSMap,
+        functions.CreateDsMap,
+        functions.DsMapAddDouble,
+        functions.DsMapAddString,
+    ) {
+        set_callbacks(Callbacks {
+            create_async_event_with_ds_map: create_async,
+            create_ds_map,
+            ds_map_add_double: add_double,
+            ds_map_add_string: add_string,
+        });
+    }
+}
+
+/// Stores the functions the runner passes to `RegisterCallbacks`. This is what
+/// [export_register_callbacks](crate::export_register_callbacks) calls.
+///
+/// # Safety
+/// The arguments must be the pointers the runner passes to `RegisterCallbacks`, in order.
+pub unsafe fn register_callbacks(
+    create_async_event_with_ds_map: *mut c_char,
+    create_ds_map: *mut c_char,
+    ds_map_add_double: *mut c_char,
+    ds_map_add_string: *mut c_char,
+) {
+    let pointers = [
+        create_async_event_with_ds_map,
+        create_ds_map,
+        ds_map_add_double,
+        ds_map_add_string,
+    ];
+    if pointers.iter().any(|p| p.is_null()) {
+        return;
+    }
+
+    type CreateAsync = unsafe extern "C" fn(i32, i32);
+    type CreateDsMap = unsafe extern "C" fn(i32, ...) -> i32;
+    type AddDouble = unsafe extern "C" fn(i32, *const c_char, f64) -> bool;
+    type AddString = unsafe extern "C" fn(i32, *const c_char, *const c_char) -> bool;
+
+    set_callbacks(unsafe {
+        Callbacks {
+            create_async_event_with_ds_map: core::mem::transmute::<*mut c_char, CreateAsync>(
+                pointers[0],
+            ),
+            create_ds_map: core::mem::transmute::<*mut c_char, CreateDsMap>(pointers[1]),
+            ds_map_add_double: core::mem::transmute::<*mut c_char, AddDouble>(pointers[2]),
+            ds_map_add_string: core::mem::transmute::<*mut c_char, AddString>(pointers[3]),
+        }
+    });
+}
+
+/// Replaces the async event [Callbacks]. The runner normally provides these, but this is
+/// useful for tests.
+pub fn set_callbacks(callbacks: Callbacks) {
+    *CALLBACKS.write().unwrap_or_else(|e| e.into_inner()) = Some(callbacks);
+}
+
+/// Returns the async event [Callbacks], if the runner has given them to us.
+pub fn callbacks() -> Option<Callbacks> {
+    *CALLBACKS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the runner's interface, if it's called `YYExtensionInitialise`.
+pub fn interface() -> Option<&'static YYRunnerInterface> {
+    let functions = RUNNER.load(Ordering::Acquire);
+    // safety: `init` only stores the runner's interface, which lives for the rest of the game
+    unsafe { functions.as_ref() }
+}
+
+/// Writes `message` to the runner's debug console, the same place `show_debug_message` goes.
+/// Returns `false` if the runner hasn't given us its interface.
+pub fn debug_output(message: &str) -> bool {
+    let Some(output) = interface().and_then(|functions| functions.DebugConsoleOutput) else {
+        return false;
+    };
+
+    let mut message = message.replace('\0', "");
+    message.push('\0');
+    unsafe { output(c"%s".as_ptr(), message.as_ptr() as *const c_char) };
+
+    true
+}
+
+/// Generates the `YYExtensionInitialise` export, which newer runners call with their
+/// [YYRunnerInterface], and stores it with [init]. The runner looks it up by name, so unlike
+/// other exports, this can't be renamed.
+/// ```
+/// gm_ffi::export_runner_init!();
+/// ```
+#[macro_export]
+macro_rules! export_runner_init {
+    () => {
+        /// Called by the runner with its extension interface.
+        ///
+        /// # Safety
+        /// Only the runner should call this.
+        #[no_mangle]
+        pub unsafe extern "C" fn YYExtensionInitialise(
+            functions: *const $crate::runner::YYRunnerInterface,
+            size: usize,
+        ) {
+            unsafe { $crate::runner::init(functions, size) }
+        }
+    };
+}
+
+/// Generates the `RegisterCallbacks` export, which the runner calls with the functions for
+/// firing async events, and stores them with [register_callbacks]. The runner looks it up by
+/// name, so unlike other exports, this can't be renamed.
+///
+/// In the extension editor, the function doesn't need to be declared.
+/// ```
+/// gm_ffi::export_register_callbacks!();
+/// ```
+#[macro_export]
+macro_rules! export_register_callbacks {
+    () => {
+        /// Called by the runner with the functions for firing async events.
+        ///
+        /// # Safety
+        /// Only the runner should call this.
+        #[no_mangle]
+        pub unsafe extern "C" fn RegisterCallbacks(
+            create_async_event_with_ds_map: *mut ::core::ffi::c_char,
+            create_ds_map: *mut ::core::ffi::c_char,
+            ds_map_add_double: *mut ::core::ffi::c_char,
+            ds_map_add_string: *mut ::core::ffi::c_char,
+        ) {
+            unsafe {
+                $crate::runner::register_callbacks(
+                    create_async_event_with_ds_map,
+                    create_ds_map,
+                    ds_map_add_double,
+                    ds_map_add_string,
+                )
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_missing_interfaces() {
+        unsafe {
+            init(core::ptr::null(), 4096);
+            register_callbacks(
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+                core::ptr::null_mut(),
+            );
+        }
+
+        assert!(interface().is_none());
+        assert!(!debug_output("nobody's listening"));
+    }
+}
@@ -0,0 +1,293 @@
+//! A fixed-tick simulation clock, so Rust systems and GML can agree on exactly
+//! how many simulation steps to run each frame, regardless of render rate.
+
+use parking_lot::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// The clock shared by every subsystem in the DLL. See [shared].
+static SHARED: Mutex<SimClock> = parking_lot::const_mutex(SimClock::with_tick_duration(
+    1.0 / SimClock::DEFAULT_TICK_RATE,
+));
+
+/// Returns a handle to the DLL-wide [SimClock]. Anything which needs to respect
+/// pause and time scale (tweens, physics) should read from this one, rather than
+/// keeping its own.
+///
+/// Work which should stop while the sim is paused can wait on it too: jobs started with
+/// [spawn_sim_job](crate::jobs::spawn_sim_job) don't start, and tasks queued with
+/// [enqueue_sim](crate::tick::enqueue_sim) don't run, until the clock is resumed. Plain
+/// [jobs](crate::jobs) and [tick](crate::tick) tasks ignore it.
+pub fn shared() -> MutexGuard<'static, SimClock> {
+    SHARED.lock()
+}
+
+/// Blocks the calling thread until the [shared] clock isn't paused. Returns immediately if it
+/// isn't paused already.
+///
+/// Never call this on GM's thread while the clock is paused, since nothing could resume it.
+pub fn wait_while_paused() {
+    while shared().is_paused() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+/// An accumulator-driven clock which turns variable frame times into a whole number
+/// of fixed-length simulation ticks.
+///
+/// Each frame, call [advance](SimClock::advance) with the frame's delta and run the sim
+/// that many times. Leftover time stays in the accumulator, and [alpha](SimClock::alpha)
+/// tells you how far between two ticks you are, for render interpolation.
+///
+/// [export_simclock](crate::export_simclock) generates exports which drive the
+/// [shared] clock from GML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimClock {
+    tick_duration: f64,
+    accumulator: f64,
+    scale: f64,
+    paused: bool,
+    max_ticks_per_frame: u32,
+    tick: u64,
+}
+
+impl SimClock {
+    /// The default for [set_max_ticks_per_frame](SimClock::set_max_ticks_per_frame).
+    pub const DEFAULT_MAX_TICKS_PER_FRAME: u32 = 8;
+
+    /// The tick rate of the [shared] clock, and what [new](SimClock::new) falls back to.
+    pub const DEFAULT_TICK_RATE: f64 = 30.0;
+
+    /// Creates a new clock which ticks `tick_rate` times a second.
+    ///
+    /// A `tick_rate` which isn't positive and finite falls back to
+    /// [DEFAULT_TICK_RATE](SimClock::DEFAULT_TICK_RATE).
+    pub fn new(tick_rate: f64) -> Self {
+        let tick_rate = if tick_rate.is_finite() && tick_rate > 0.0 {
+            tick_rate
+        } else {
+            Self::DEFAULT_TICK_RATE
+        };
+
+        Self::with_tick_duration(1.0 / tick_rate)
+    }
+
+    // float arithmetic isn't allowed in a `const fn` under our rust-version, so `SHARED` does
+    // the division itself
+    const fn with_tick_duration(tick_duration: f64) -> Self {
+        Self {
+            tick_duration,
+            accumulator: 0.0,
+            scale: 1.0,
+            paused: false,
+            max_ticks_per_frame: Self::DEFAULT_MAX_TICKS_PER_FRAME,
+            tick: 0,
+        }
+    }
+
+    /// Feeds `delta` seconds of real time into the clock, returning the number of
+    /// ticks to run this frame.
+    ///
+    /// While paused, this always returns 0 and the accumulator is left alone.
+    /// If we fall more than `max_ticks_per_frame` behind, the excess time is dropped
+    /// rather than letting the sim spiral trying to catch up.
+    pub fn advance(&mut self, delta: f64) -> u32 {
+        if self.paused || !delta.is_finite() || delta <= 0.0 {
+            return 0;
+        }
+
+        self.accumulator += delta * self.scale;
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_duration {
+            if ticks == self.max_ticks_per_frame {
+                self.accumulator %= self.tick_duration;
+                break;
+            }
+
+            self.accumulator -= self.tick_duration;
+            ticks += 1;
+        }
+
+        self.tick += u64::from(ticks);
+        ticks
+    }
+
+    /// How far we are between the last tick and the next one, in `0.0..1.0`.
+    /// Use this to interpolate rendered positions.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / self.tick_duration
+    }
+
+    /// The length of one tick, in seconds.
+    pub fn tick_duration(&self) -> f64 {
+        self.tick_duration
+    }
+
+    /// The total number of ticks this clock has produced.
+    pub fn tick_count(&self) -> u64 {
+        self.tick
+    }
+
+    /// Pauses the clock. [advance](SimClock::advance) will return 0 until resumed.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes the clock after a [pause](SimClock::pause).
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns if the clock is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Sets the time scale. `0.5` runs the sim at half speed, `2.0` at double speed.
+    /// Negative and non-finite scales are clamped to `0.0`.
+    pub fn set_scale(&mut self, scale: f64) {
+        self.scale = if scale.is_finite() {
+            scale.max(0.0)
+        } else {
+            0.0
+        };
+    }
+
+    /// Returns the current time scale.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Sets the most ticks a single [advance](SimClock::advance) can return.
+    pub fn set_max_ticks_per_frame(&mut self, max: u32) {
+        self.max_ticks_per_frame = max;
+    }
+
+    /// Returns the scaled, pause-aware length of time that `delta` seconds of real time
+    /// represents in the sim. Useful for systems which don't run on the fixed tick.
+    pub fn scaled_delta(&self, delta: f64) -> f64 {
+        if self.paused {
+            0.0
+        } else {
+            delta * self.scale
+        }
+    }
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TICK_RATE)
+    }
+}
+
+/// Generates exports which drive the [shared](crate::simclock::shared) clock: one which
+/// advances it, called `sim_advance` by default, one which returns its
+/// [alpha](crate::simclock::SimClock::alpha), called `sim_alpha` by default, one which pauses
+/// or resumes it, called `sim_set_paused` by default, and one which sets its time scale,
+/// called `sim_set_scale` by default. See [Naming exports](crate#naming-exports) for
+/// renaming them.
+///
+/// In the extension editor, declare `sim_advance` as taking a real, `sim_alpha` as taking no
+/// arguments, and `sim_set_paused` and `sim_set_scale` as taking a real. All return a real.
+/// `sim_advance` takes the frame's `delta_time`, in microseconds, and returns the number of
+/// ticks to run, `sim_set_paused` pauses when given `true`, and `sim_set_scale` returns the
+/// scale after clamping.
+/// ```
+/// gm_ffi::export_simclock!(clock_advance, clock_alpha, clock_set_paused, clock_set_scale);
+///
+/// assert_eq!(clock_set_scale(-1.0), 0.0);
+/// assert_eq!(clock_advance(1_000_000.0), 0.0);
+/// # clock_set_scale(1.0);
+/// ```
+/// ```gml
+/// repeat (sim_advance(delta_time)) {
+///     with (obj_unit) event_user(0);
+/// }
+/// ```
+#[macro_export]
+macro_rules! export_simclock {
+    () => {
+        $crate::export_simclock!(sim_advance, sim_alpha, sim_set_paused, sim_set_scale);
+    };
+    ($advance:ident, $alpha:ident, $set_paused:ident, $set_scale:ident) => {
+        /// Feeds `delta_us` microseconds of real time into the clock, and returns the number
+        /// of ticks to run this frame.
+        #[no_mangle]
+        pub extern "C" fn $advance(delta_us: f64) -> f64 {
+            $crate::catch_ffi!(0.0 => {
+                $crate::simclock::shared().advance(delta_us / 1_000_000.0) as f64
+            })
+        }
+
+        /// Returns how far the clock is between the last tick and the next one.
+        #[no_mangle]
+        pub extern "C" fn $alpha() -> f64 {
+            $crate::catch_ffi!(0.0 => {
+                $crate::simclock::shared().alpha()
+            })
+        }
+
+        /// Pauses the clock if `paused` is true, and resumes it otherwise.
+        #[no_mangle]
+        pub extern "C" fn $set_paused(paused: f64) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let mut clock = $crate::simclock::shared();
+                if paused >= 0.5 {
+                    clock.pause();
+                } else {
+                    clock.resume();
+                }
+
+                $crate::OutputCode::SUCCESS
+            })
+        }
+
+        /// Sets the clock's time scale, and returns it after clamping.
+        #[no_mangle]
+        pub extern "C" fn $set_scale(scale: f64) -> f64 {
+            $crate::catch_ffi!(f64::NAN => {
+                let mut clock = $crate::simclock::shared();
+                clock.set_scale(scale);
+                clock.scale()
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_ticks() {
+        let mut clock = SimClock::new(30.0);
+
+        assert_eq!(clock.advance(1.0 / 60.0), 0);
+        assert!((clock.alpha() - 0.5).abs() < 1e-9);
+        assert_eq!(clock.advance(1.0 / 60.0), 1);
+        assert_eq!(clock.advance(0.11), 3);
+        assert_eq!(clock.tick_count(), 4);
+    }
+
+    #[test]
+    fn pause_and_scale() {
+        let mut clock = SimClock::new(30.0);
+
+        clock.pause();
+        assert_eq!(clock.advance(1.0), 0);
+        clock.resume();
+
+        clock.set_scale(0.5);
+        assert_eq!(clock.advance(0.25), 3);
+
+        clock.set_scale(1.0);
+        assert_eq!(clock.advance(10.0), SimClock::DEFAULT_MAX_TICKS_PER_FRAME);
+    }
+
+    #[test]
+    fn rejects_bad_tick_rates() {
+        for tick_rate in [0.0, -30.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(SimClock::new(tick_rate), SimClock::default());
+        }
+    }
+}
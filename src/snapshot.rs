@@ -0,0 +1,153 @@
+//! Rust-owned copies of [GmBuffer] contents, for undoing operations on a buffer.
+
+use crate::GmBuffer;
+use core::ops::Range;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of bytes currently held by every live [OwnedSnapshot].
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how many bytes all of the currently alive [OwnedSnapshot]s are holding onto.
+/// Handy for spotting an undo stack that's grown out of hand.
+pub fn live_bytes() -> usize {
+    LIVE_BYTES.load(Ordering::Relaxed)
+}
+
+/// A Rust-owned copy of some or all of a [GmBuffer]. It remembers where in the buffer it
+/// came from, so it can be put back with [GmBuffer::restore_from].
+#[derive(Debug, PartialEq)]
+pub struct OwnedSnapshot<T> {
+    offset: usize,
+    data: Vec<T>,
+}
+
+impl<T> OwnedSnapshot<T> {
+    fn new(offset: usize, data: Vec<T>) -> Self {
+        LIVE_BYTES.fetch_add(core::mem::size_of_val(data.as_slice()), Ordering::Relaxed);
+
+        Self { offset, data }
+    }
+
+    /// The index in the original buffer where this snapshot starts.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The range of the original buffer this snapshot covers.
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.data.len()
+    }
+
+    /// The number of T's in the snapshot.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns if the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The number of bytes this snapshot is holding onto.
+    pub fn size_in_bytes(&self) -> usize {
+        core::mem::size_of_val(self.data.as_slice())
+    }
+
+    /// The snapshotted data.
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T: Clone> Clone for OwnedSnapshot<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.offset, self.data.clone())
+    }
+}
+
+impl<T> Drop for OwnedSnapshot<T> {
+    fn drop(&mut self) {
+        LIVE_BYTES.fetch_sub(self.size_in_bytes(), Ordering::Relaxed);
+    }
+}
+
+impl<T: Copy> GmBuffer<T> {
+    /// Copies the entire buffer into a Rust-owned [OwnedSnapshot].
+    pub fn snapshot(&self) -> OwnedSnapshot<T> {
+        self.debug_check_canary();
+        OwnedSnapshot::new(0, self.buffer.to_vec())
+    }
+
+    /// Copies part of the buffer into a Rust-owned [OwnedSnapshot]. Returns `None`
+    /// if the range is out of bounds.
+    pub fn snapshot_range(&self, range: Range<usize>) -> Option<OwnedSnapshot<T>> {
+        self.debug_check_canary();
+        let start = range.start;
+        let data = self.buffer.get(range)?.to_vec();
+
+        Some(OwnedSnapshot::new(start, data))
+    }
+
+    /// Writes a snapshot back into the buffer, at the place it was taken from.
+    ///
+    /// Returns `false` and leaves the buffer alone if the snapshot doesn't fit.
+    #[must_use]
+    pub fn restore_from(&mut self, snapshot: &OwnedSnapshot<T>) -> bool {
+        self.debug_check_canary();
+        let Some(dest) = self.buffer.get_mut(snapshot.range()) else {
+            return false;
+        };
+        dest.copy_from_slice(&snapshot.data);
+
+        true
+    }
+
+    /// Writes only `range` of a snapshot back into the buffer. `range` is in buffer indices,
+    /// not snapshot indices, and must lie within [OwnedSnapshot::range].
+    ///
+    /// Returns `false` and leaves the buffer alone if the range is outside the snapshot or the buffer.
+    #[must_use]
+    pub fn restore_range_from(&mut self, snapshot: &OwnedSnapshot<T>, range: Range<usize>) -> bool {
+        self.debug_check_canary();
+        let snap_range = snapshot.range();
+        if range.start < snap_range.start || range.end > snap_range.end || range.start > range.end {
+            return false;
+        }
+
+        let Some(dest) = self.buffer.get_mut(range.clone()) else {
+            return false;
+        };
+        dest.copy_from_slice(
+            &snapshot.data[range.start - snap_range.start..range.end - snap_range.start],
+        );
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{GmBuffer, GmId, GmPtr};
+
+    #[test]
+    fn snapshot_and_restore() {
+        let mut buf = vec![1u32, 2, 3, 4];
+        let gm_ptr = GmPtr::new(buf.as_mut_ptr() as *const _);
+        let mut buffer: GmBuffer<u32> = unsafe { GmBuffer::new(GmId::new(0.0), gm_ptr, 4) };
+
+        let whole = buffer.snapshot();
+        let tail = buffer.snapshot_range(2..4).unwrap();
+        assert_eq!(tail.size_in_bytes(), 8);
+        assert!(buffer.snapshot_range(2..5).is_none());
+
+        buffer.buffer.fill(0);
+        assert!(buffer.restore_from(&tail));
+        assert_eq!(buffer.buffer, &[0, 0, 3, 4]);
+
+        assert!(buffer.restore_range_from(&whole, 1..2));
+        assert_eq!(buffer.buffer, &[0, 2, 3, 4]);
+        assert!(!buffer.restore_range_from(&tail, 1..3));
+
+        assert_eq!(buf, [0, 2, 3, 4]);
+    }
+}
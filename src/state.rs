@@ -0,0 +1,187 @@
+//! Global extension state, created and dropped when GM says so.
+
+use std::sync::{Mutex, MutexGuard};
+
+/// A lazily created, mutex-protected global, usually declared with [gm_state](crate::gm_state).
+///
+/// The value is created the first time it's used, or when [init](GmState::init) is called, and
+/// dropped by [shutdown](GmState::shutdown). Using it after a shutdown creates it again.
+#[derive(Debug)]
+pub struct GmState<T> {
+    value: Mutex<Option<T>>,
+    create: fn() -> T,
+}
+
+impl<T> GmState<T> {
+    /// Creates a new, empty state, which is filled in with `create`.
+    pub const fn new(create: fn() -> T) -> Self {
+        Self {
+            value: Mutex::new(None),
+            create,
+        }
+    }
+
+    fn value(&self) -> MutexGuard<'_, Option<T>> {
+        self.value.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Creates the value, dropping the old one first if there was one.
+    pub fn init(&self) {
+        self.shutdown();
+
+        // likewise created without the lock held, in case `create` uses this state
+        let value = (self.create)();
+        let old = self.value().replace(value);
+        drop(old);
+    }
+
+    /// Drops the value, if there is one.
+    pub fn shutdown(&self) {
+        // taken out first, so the value isn't dropped with the lock held
+        let value = self.value().take();
+        drop(value);
+    }
+
+    /// Returns if the value currently exists.
+    pub fn is_initialized(&self) -> bool {
+        self.value().is_some()
+    }
+
+    /// Runs `f` with the value, creating it first if it doesn't exist.
+    pub fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let mut value = self.value();
+        while value.is_none() {
+            drop(value);
+
+            // created without the lock held, like in `init`
+            let created = (self.create)();
+            value = self.value();
+            if value.is_none() {
+                *value = Some(created);
+            } else {
+                // another thread got there first, so ours is dropped, also without the lock
+                drop(value);
+                drop(created);
+                value = self.value();
+            }
+        }
+
+        f(value.as_mut().unwrap())
+    }
+}
+
+/// Declares [GmState] globals, along with `gm_init` and `gm_shutdown` exports which create and
+/// drop them all, in order, so state is set up and torn down deterministically instead of
+/// whenever the DLL happens to be unloaded.
+///
+/// In the extension editor, declare both as taking no arguments and returning a real. Call
+/// `gm_init` at game start and `gm_shutdown` at game end. Both return
+/// [OutputCode::FAILURE](crate::OutputCode::FAILURE) if a constructor or destructor panics.
+/// ```
+/// struct World {
+///     entities: Vec<u32>,
+/// }
+///
+/// gm_ffi::gm_state! {
+///     pub WORLD: World = World { entities: Vec::new() };
+///     SEED: u64 = 42;
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn world_spawn(kind: f64) -> f64 {
+///     WORLD.with(|world| {
+///         world.entities.push(kind as u32);
+///         world.entities.len() as f64
+///     })
+/// }
+///
+/// assert!(gm_init().is_success());
+/// assert_eq!(world_spawn(3.0), 1.0);
+/// gm_shutdown();
+/// assert!(!WORLD.is_initialized());
+/// ```
+/// To rename the exports, as described in [Naming exports](crate#naming-exports), name them first:
+/// ```
+/// gm_ffi::gm_state! {
+///     init = my_extension_init, shutdown = my_extension_shutdown;
+///     CACHE: Vec<String> = Vec::new();
+/// }
+/// ```
+#[macro_export]
+macro_rules! gm_state {
+    (
+        init = $init:ident, shutdown = $shutdown:ident;
+        $($(#[$meta:meta])* $vis:vis $name:ident: $ty:ty = $create:expr);+ $(;)?
+    ) => {
+        $(
+            $(#[$meta])*
+            $vis static $name: $crate::state::GmState<$ty> = $crate::state::GmState::new(|| $create);
+        )+
+
+        /// Creates the extension's global state.
+        #[no_mangle]
+        pub extern "C" fn $init() -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                $($name.init();)+
+                $crate::OutputCode::SUCCESS
+            })
+        }
+
+        /// Drops the extension's global state.
+        #[no_mangle]
+        pub extern "C" fn $shutdown() -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                $($name.shutdown();)+
+                $crate::OutputCode::SUCCESS
+            })
+        }
+    };
+    ($($(#[$meta:meta])* $vis:vis $name:ident: $ty:ty = $create:expr);+ $(;)?) => {
+        $crate::gm_state! {
+            init = gm_init, shutdown = gm_shutdown;
+            $($(#[$meta])* $vis $name: $ty = $create);+
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lazy_init_and_shutdown() {
+        static COUNT: GmState<Vec<u32>> = GmState::new(|| vec![1]);
+
+        assert!(!COUNT.is_initialized());
+        COUNT.with(|count| count.push(2));
+        assert_eq!(COUNT.with(|count| count.clone()), [1, 2]);
+
+        COUNT.init();
+        assert_eq!(COUNT.with(|count| count.len()), 1);
+        COUNT.shutdown();
+        assert!(!COUNT.is_initialized());
+    }
+
+    #[test]
+    fn reentrant_drop() {
+        struct Reentrant;
+        impl Drop for Reentrant {
+            fn drop(&mut self) {
+                REENTRANT.is_initialized();
+            }
+        }
+        static REENTRANT: GmState<Reentrant> = GmState::new(|| Reentrant);
+
+        REENTRANT.init();
+        REENTRANT.init();
+        REENTRANT.shutdown();
+    }
+
+    #[test]
+    fn reentrant_create() {
+        static REENTRANT: GmState<bool> = GmState::new(|| REENTRANT.is_initialized());
+
+        assert!(!REENTRANT.with(|created_over_itself| *created_over_itself));
+        REENTRANT.shutdown();
+    }
+}
@@ -0,0 +1,164 @@
+//! Returning strings to GM.
+//!
+//! When an export is declared as returning a string, GM copies the string out of the pointer
+//! it's given as soon as the export returns. That means the pointer only needs to live until
+//! the *next* call to that export, but it does need to live past the end of the function,
+//! so it can't point into a local `String` or `CString`.
+
+use core::ffi::c_char;
+
+/// An owned, NUL-terminated string, intended to be kept around (in a static or thread local)
+/// per export, and returned to GM with [set](GmString::set).
+///
+/// The pointer returned is valid until the next call to [set](GmString::set), or until the
+/// GmString is dropped. Since GM copies the string on return, that's all we need, and the
+/// allocation gets reused between calls.
+/// ```
+/// # use gm_ffi::GmString;
+/// # use std::{cell::RefCell, ffi::c_char};
+/// thread_local! {
+///     static GREETING: RefCell<GmString> = const { RefCell::new(GmString::new()) };
+/// }
+///
+/// #[no_mangle]
+/// pub extern "C" fn greet(count: f64) -> *const c_char {
+///     GREETING.with_borrow_mut(|s| s.set(&format!("Hello x{}", count)))
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GmString {
+    // always ends with a NUL, unless it's empty
+    bytes: Vec<u8>,
+}
+
+impl GmString {
+    /// Creates a new, empty GmString. This doesn't allocate.
+    pub const fn new() -> Self {
+        Self { bytes: Vec::new() }
+    }
+
+    /// Replaces the contents with `value`, and returns a pointer to them for GM.
+    ///
+    /// C strings can't hold NULs, so if `value` contains one, it's cut off there.
+    pub fn set(&mut self, value: &str) -> *const c_char {
+        let value = value.as_bytes();
+        let len = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+
+        self.bytes.clear();
+        self.bytes.extend_from_slice(&value[..len]);
+        self.bytes.push(0);
+
+        self.as_ptr()
+    }
+
+    /// Returns a pointer to the current contents. This is an empty string if nothing has
+    /// been set yet.
+    pub fn as_ptr(&self) -> *const c_char {
+        if self.bytes.is_empty() {
+            c"".as_ptr()
+        } else {
+            self.bytes.as_ptr() as *const c_char
+        }
+    }
+
+    /// Returns the current contents, without the NUL.
+    pub fn as_str(&self) -> &str {
+        match self.bytes.split_last() {
+            // we only ever fill this from a `&str`, so this can't fail
+            Some((_, bytes)) => core::str::from_utf8(bytes).unwrap_or_default(),
+            None => "",
+        }
+    }
+}
+
+/// A fixed ring of [GmString]s. Each call to [return_str](StringPool::return_str) writes into
+/// the next slot, so a returned pointer stays valid for the next `N - 1` calls.
+///
+/// Most exports should just use the free function [return_str], which uses a per-thread pool.
+#[derive(Debug, Clone)]
+pub struct StringPool<const N: usize> {
+    slots: [GmString; N],
+    next: usize,
+}
+
+impl<const N: usize> StringPool<N> {
+    /// Creates a new pool. This doesn't allocate until strings are returned.
+    pub const fn new() -> Self {
+        assert!(N > 0, "a StringPool needs at least one slot");
+
+        Self {
+            slots: [const { GmString::new() }; N],
+            next: 0,
+        }
+    }
+
+    /// Copies `value` into the next slot and returns a pointer to it.
+    pub fn return_str(&mut self, value: &str) -> *const c_char {
+        let ptr = self.slots[self.next].set(value);
+        self.next = (self.next + 1) % N;
+
+        ptr
+    }
+}
+
+impl<const N: usize> Default for StringPool<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The number of slots in the pool used by [return_str].
+pub const RETURN_STR_SLOTS: usize = 16;
+
+thread_local! {
+    static RETURN_POOL: core::cell::RefCell<StringPool<RETURN_STR_SLOTS>> =
+        const { core::cell::RefCell::new(StringPool::new()) };
+}
+
+/// Returns a string to GM from an export declared as returning a string, without leaking or
+/// allocating on every call.
+///
+/// The pointer is valid until [RETURN_STR_SLOTS] more strings have been returned on this
+/// thread, which is plenty, since GM copies the string as soon as the export returns.
+/// ```
+/// # use std::ffi::c_char;
+/// #[no_mangle]
+/// pub extern "C" fn describe(hp: f64) -> *const c_char {
+///     gm_ffi::string::return_str(&format!("hp: {}", hp))
+/// }
+/// ```
+pub fn return_str(value: &str) -> *const c_char {
+    RETURN_POOL.with_borrow_mut(|pool| pool.return_str(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GmPtr;
+
+    #[test]
+    fn set_and_reuse() {
+        let mut s = GmString::new();
+        assert_eq!(GmPtr::new(s.as_ptr()).to_str(), Ok(""));
+
+        let ptr = s.set("hello");
+        assert_eq!(GmPtr::new(ptr).to_str(), Ok("hello"));
+
+        s.set("a\0b");
+        assert_eq!(s.as_str(), "a");
+        assert_eq!(GmPtr::new(s.as_ptr()).to_str(), Ok("a"));
+    }
+
+    #[test]
+    fn pool_rotates() {
+        let mut pool = StringPool::<2>::new();
+        let first = pool.return_str("one");
+        let second = pool.return_str("two");
+        assert_eq!(GmPtr::new(first).to_str(), Ok("one"));
+        assert_eq!(GmPtr::new(second).to_str(), Ok("two"));
+
+        pool.return_str("three");
+        assert_eq!(pool.slots[0].as_str(), "three");
+        assert_eq!(GmPtr::new(second).to_str(), Ok("two"));
+    }
+}
@@ -0,0 +1,130 @@
+//! A queue of work to run on GM's thread, so background threads in a DLL have a sanctioned way
+//! to touch GM-owned buffers and send results.
+//!
+//! Background threads [enqueue] closures, and GM calls the export generated by
+//! [export_tick](crate::export_tick) once a step, which runs them:
+//! ```
+//! gm_ffi::export_tick!();
+//!
+//! std::thread::spawn(|| {
+//!     let score = 100.0; // something slow
+//!     gm_ffi::tick::enqueue(move || {
+//!         gm_ffi::gm_println!("score is ready: {}", score);
+//!     });
+//! });
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+type Task = Box<dyn FnOnce() + Send>;
+
+static QUEUE: Mutex<VecDeque<Task>> = Mutex::new(VecDeque::new());
+static SIM_QUEUE: Mutex<VecDeque<Task>> = Mutex::new(VecDeque::new());
+
+/// Queues `task` to run on GM's thread during the next tick. This can be called from any thread.
+pub fn enqueue(task: impl FnOnce() + Send + 'static) {
+    QUEUE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push_back(Box::new(task));
+}
+
+/// Like [enqueue], but while the [shared](crate::simclock::shared) clock is paused, `task`
+/// stays queued, and runs on the first tick after it's resumed.
+pub fn enqueue_sim(task: impl FnOnce() + Send + 'static) {
+    SIM_QUEUE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push_back(Box::new(task));
+}
+
+/// The number of tasks waiting for the next tick, including those waiting for the
+/// [shared](crate::simclock::shared) clock to be resumed.
+pub fn queued() -> usize {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner()).len()
+        + SIM_QUEUE.lock().unwrap_or_else(|e| e.into_inner()).len()
+}
+
+/// Runs every task queued so far, in order, and returns how many ran. This is what the export
+/// generated by [export_tick](crate::export_tick) calls.
+///
+/// Tasks from [enqueue_sim] are left queued while the [shared](crate::simclock::shared) clock
+/// is paused. Tasks queued while this runs (including by the tasks themselves) wait for the next tick. A
+/// task which panics is caught, and its panic is recorded as the
+/// [last error](crate::error::last_error), so it can't unwind into GM.
+pub fn run_queued() -> usize {
+    let mut tasks = std::mem::take(&mut *QUEUE.lock().unwrap_or_else(|e| e.into_inner()));
+    if !crate::simclock::shared().is_paused() {
+        tasks.append(&mut SIM_QUEUE.lock().unwrap_or_else(|e| e.into_inner()));
+    }
+    let count = tasks.len();
+
+    for task in tasks {
+        crate::error::catch_unwind_or((), task);
+    }
+
+    count
+}
+
+/// Generates an export, called `gm_tick` by default, which runs queued tasks with
+/// [run_queued](crate::tick::run_queued) and returns how many ran. See
+/// [Naming exports](crate#naming-exports) for renaming it.
+///
+/// In the extension editor, declare it as taking no arguments and returning a real, and call it
+/// once a step, from a persistent controller object.
+/// ```
+/// gm_ffi::export_tick!(my_extension_tick);
+/// ```
+#[macro_export]
+macro_rules! export_tick {
+    () => {
+        $crate::export_tick!(gm_tick);
+    };
+    ($name:ident) => {
+        /// Runs the tasks queued for GM's thread.
+        #[no_mangle]
+        pub extern "C" fn $name() -> f64 {
+            $crate::catch_ffi!(0.0 => {
+                $crate::tick::run_queued() as f64
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[test]
+    fn runs_tasks_once() {
+        let ran = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..3 {
+            let ran = ran.clone();
+            std::thread::spawn(move || {
+                enqueue(move || {
+                    ran.fetch_add(1, Ordering::SeqCst);
+                })
+            })
+            .join()
+            .unwrap();
+        }
+        enqueue(|| enqueue(|| {}));
+
+        assert_eq!(run_queued(), 4);
+        assert_eq!(ran.load(Ordering::SeqCst), 3);
+        assert_eq!(queued(), 1);
+        assert_eq!(run_queued(), 1);
+
+        // in the same test, since the queues are shared
+        crate::simclock::shared().pause();
+        enqueue_sim(|| {});
+        assert_eq!(run_queued(), 0);
+        assert_eq!(queued(), 1);
+        crate::simclock::shared().resume();
+        assert_eq!(run_queued(), 1);
+    }
+}
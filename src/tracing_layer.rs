@@ -0,0 +1,152 @@
+//! A [tracing_subscriber] layer which sends events, and how long spans were open, through
+//! [gm_println](crate::gm_println).
+
+use std::fmt::{self, Write};
+use std::time::Instant;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+/// A [Layer] which formats events as `[LEVEL target outer:inner] message key=value`, and
+/// reports how long each span was open when it closes.
+/// ```
+/// use tracing_subscriber::prelude::*;
+///
+/// tracing_subscriber::registry()
+///     .with(gm_ffi::tracing_layer::GmLayer::new())
+///     .init();
+///
+/// let _span = tracing::info_span!("load_level", id = 3).entered();
+/// tracing::info!(enemies = 12, "spawned");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct GmLayer {
+    span_timings: bool,
+    write: fn(&str),
+}
+
+impl GmLayer {
+    /// Creates a new layer, which reports span timings.
+    pub fn new() -> Self {
+        Self {
+            span_timings: true,
+            write: |line| crate::gm_println!("{}", line),
+        }
+    }
+
+    /// Sets whether closing a span writes out how long it was open.
+    pub fn span_timings(mut self, span_timings: bool) -> Self {
+        self.span_timings = span_timings;
+        self
+    }
+}
+
+impl Default for GmLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct SpanStart(Instant);
+
+/// Writes out a message and fields as `message key=value key=value`.
+#[derive(Default)]
+struct FieldWriter(String);
+
+impl Visit for FieldWriter {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        } else {
+            let _ = write!(self.0, "{}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record_debug(field, &format_args!("{}", value));
+    }
+}
+
+impl<S> Layer<S> for GmLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanStart(Instant::now()));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut line = format!("[{} {}", metadata.level(), metadata.target());
+
+        if let Some(scope) = ctx.event_scope(event) {
+            let names: Vec<_> = scope.from_root().map(|span| span.name()).collect();
+            if !names.is_empty() {
+                let _ = write!(line, " {}", names.join(":"));
+            }
+        }
+        line.push_str("] ");
+
+        let mut fields = FieldWriter::default();
+        event.record(&mut fields);
+        line.push_str(&fields.0);
+
+        (self.write)(&line);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if !self.span_timings {
+            return;
+        }
+
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(elapsed) = span.extensions().get::<SpanStart>().map(|s| s.0.elapsed()) else {
+            return;
+        };
+
+        let metadata = span.metadata();
+        (self.write)(&format!(
+            "[{} {}] {} closed after {:.2?}",
+            metadata.level(),
+            metadata.target(),
+            span.name(),
+            elapsed
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tracing_subscriber::prelude::*;
+
+    static LINES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn formats_events_and_spans() {
+        let layer = GmLayer {
+            span_timings: true,
+            write: |line| LINES.lock().unwrap().push(line.to_string()),
+        };
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!(target: "game", "load").entered();
+            tracing::warn!(target: "game", count = 2, "spawned");
+        });
+
+        let lines = LINES.lock().unwrap();
+        assert_eq!(lines[0], "[WARN game load] spawned count=2");
+        assert!(lines[1].starts_with("[INFO game] load closed after "));
+    }
+}
@@ -0,0 +1,335 @@
+//! Declarative validation for JSON sent over from GML.
+//!
+//! Rather than letting a malformed config blow up deep inside parsing, declare the shape
+//! you expect up front and check incoming data against it:
+//!
+//! ```
+//! use gm_ffi::validate::{Field, Shape};
+//!
+//! let enemy = Shape::object([
+//!     Field::required("name", Shape::string()),
+//!     Field::required("hp", Shape::number().gt(0.0)),
+//!     Field::optional("speed", Shape::number().ge(0.0), 1.0),
+//! ]);
+//! let config = Shape::object([Field::required("enemies", Shape::array(enemy))]);
+//!
+//! let err = config
+//!     .validate_str(r#"{ "enemies": [{ "name": "slime", "hp": 0 }] }"#)
+//!     .unwrap_err();
+//! assert_eq!(err.to_string(), "enemies[0].hp must be > 0");
+//! ```
+
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// The expected shape of a JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Anything goes.
+    Any,
+    /// `true` or `false`.
+    Bool,
+    /// A number, optionally within a range.
+    Number(NumberShape),
+    /// A string.
+    String,
+    /// An array where every element has the given shape.
+    Array(Box<Shape>),
+    /// An object with the given fields. Fields not listed are left alone.
+    Object(Vec<Field>),
+}
+
+impl Shape {
+    /// A number with no limits on it. Use the methods on [NumberShape] to add some.
+    pub fn number() -> NumberShape {
+        NumberShape::default()
+    }
+
+    /// A string.
+    pub fn string() -> Shape {
+        Shape::String
+    }
+
+    /// A bool.
+    pub fn bool() -> Shape {
+        Shape::Bool
+    }
+
+    /// An array of `element`.
+    pub fn array(element: impl Into<Shape>) -> Shape {
+        Shape::Array(Box::new(element.into()))
+    }
+
+    /// An object with the given fields.
+    pub fn object(fields: impl IntoIterator<Item = Field>) -> Shape {
+        Shape::Object(fields.into_iter().collect())
+    }
+
+    /// Parses `input` as JSON and validates it, returning the value with any defaults filled in.
+    pub fn validate_str(&self, input: &str) -> Result<Value, ValidationError> {
+        self.validate_bytes(input.as_bytes())
+    }
+
+    /// Parses `input` as UTF-8 JSON and validates it, returning the value with any defaults filled in.
+    pub fn validate_bytes(&self, input: &[u8]) -> Result<Value, ValidationError> {
+        let mut value = serde_json::from_slice(input).map_err(|e| ValidationError {
+            path: String::new(),
+            message: format!("is not valid json ({})", e),
+        })?;
+        self.validate(&mut value)?;
+
+        Ok(value)
+    }
+
+    /// Validates `value` in place, inserting defaults for missing optional fields.
+    pub fn validate(&self, value: &mut Value) -> Result<(), ValidationError> {
+        self.validate_at(value, &mut String::new())
+    }
+
+    fn validate_at(&self, value: &mut Value, path: &mut String) -> Result<(), ValidationError> {
+        match (self, value) {
+            (Shape::Any, _) | (Shape::Bool, Value::Bool(_)) | (Shape::String, Value::String(_)) => {
+                Ok(())
+            }
+            (Shape::Number(shape), Value::Number(n)) => {
+                let n = n.as_f64().unwrap_or(f64::NAN);
+                shape
+                    .check(n)
+                    .map_err(|message| ValidationError::new(path, message))
+            }
+            (Shape::Array(element), Value::Array(values)) => {
+                for (i, v) in values.iter_mut().enumerate() {
+                    let len = path.len();
+                    path.push_str(&format!("[{}]", i));
+                    element.validate_at(v, path)?;
+                    path.truncate(len);
+                }
+
+                Ok(())
+            }
+            (Shape::Object(fields), Value::Object(map)) => {
+                for field in fields {
+                    let len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(&field.name);
+
+                    field.validate_in(map, path)?;
+                    path.truncate(len);
+                }
+
+                Ok(())
+            }
+            (shape, _) => Err(ValidationError::new(
+                path,
+                format!("must be {}", shape.description()),
+            )),
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Shape::Any => "anything",
+            Shape::Bool => "a bool",
+            Shape::Number(_) => "a number",
+            Shape::String => "a string",
+            Shape::Array(_) => "an array",
+            Shape::Object(_) => "an object",
+        }
+    }
+}
+
+/// A number, with optional limits. Get one with [Shape::number].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct NumberShape {
+    min: Option<(f64, bool)>,
+    max: Option<(f64, bool)>,
+    integer: bool,
+}
+
+impl NumberShape {
+    /// The number must be greater than `min`.
+    pub fn gt(mut self, min: f64) -> Self {
+        self.min = Some((min, false));
+        self
+    }
+
+    /// The number must be greater than or equal to `min`.
+    pub fn ge(mut self, min: f64) -> Self {
+        self.min = Some((min, true));
+        self
+    }
+
+    /// The number must be less than `max`.
+    pub fn lt(mut self, max: f64) -> Self {
+        self.max = Some((max, false));
+        self
+    }
+
+    /// The number must be less than or equal to `max`.
+    pub fn le(mut self, max: f64) -> Self {
+        self.max = Some((max, true));
+        self
+    }
+
+    /// The number must be a whole number.
+    pub fn integer(mut self) -> Self {
+        self.integer = true;
+        self
+    }
+
+    fn check(&self, n: f64) -> Result<(), String> {
+        if let Some((min, inclusive)) = self.min {
+            if inclusive && n < min {
+                return Err(format!("must be >= {}", min));
+            }
+            if !inclusive && n <= min {
+                return Err(format!("must be > {}", min));
+            }
+        }
+
+        if let Some((max, inclusive)) = self.max {
+            if inclusive && n > max {
+                return Err(format!("must be <= {}", max));
+            }
+            if !inclusive && n >= max {
+                return Err(format!("must be < {}", max));
+            }
+        }
+
+        if self.integer && n.fract() != 0.0 {
+            return Err("must be a whole number".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+impl From<NumberShape> for Shape {
+    fn from(o: NumberShape) -> Self {
+        Shape::Number(o)
+    }
+}
+
+/// A field within a [Shape::Object].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    name: String,
+    shape: Shape,
+    default: Option<Value>,
+    required: bool,
+}
+
+impl Field {
+    /// A field which must be present.
+    pub fn required(name: impl Into<String>, shape: impl Into<Shape>) -> Self {
+        Self {
+            name: name.into(),
+            shape: shape.into(),
+            default: None,
+            required: true,
+        }
+    }
+
+    /// A field which, if missing or `null`, is filled in with `default`.
+    pub fn optional(
+        name: impl Into<String>,
+        shape: impl Into<Shape>,
+        default: impl Into<Value>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            shape: shape.into(),
+            default: Some(default.into()),
+            required: false,
+        }
+    }
+
+    /// A field which may be missing entirely, and isn't filled in when it is.
+    pub fn maybe(name: impl Into<String>, shape: impl Into<Shape>) -> Self {
+        Self {
+            name: name.into(),
+            shape: shape.into(),
+            default: None,
+            required: false,
+        }
+    }
+
+    fn validate_in(
+        &self,
+        map: &mut Map<String, Value>,
+        path: &mut String,
+    ) -> Result<(), ValidationError> {
+        match map.get_mut(&self.name) {
+            Some(Value::Null) | None => {
+                if let Some(default) = &self.default {
+                    map.insert(self.name.clone(), default.clone());
+                    Ok(())
+                } else if self.required {
+                    Err(ValidationError::new(path, "is required".to_string()))
+                } else {
+                    Ok(())
+                }
+            }
+            Some(value) => self.shape.validate_at(value, path),
+        }
+    }
+}
+
+/// An error produced when a value doesn't match its [Shape].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The path to the bad value, like `enemies[3].hp`. Empty for the root.
+    pub path: String,
+    /// What was wrong with it, like `must be > 0`.
+    pub message: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, message: String) -> Self {
+        Self {
+            path: path.to_string(),
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "value {}", self.message)
+        } else {
+            write!(f, "{} {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_and_errors() {
+        let shape = Shape::object([
+            Field::required("name", Shape::string()),
+            Field::optional("count", Shape::number().integer().ge(0.0), 3),
+        ]);
+
+        let value = shape.validate_str(r#"{ "name": "a" }"#).unwrap();
+        assert_eq!(value["count"], 3);
+
+        let err = shape.validate_str(r#"{ "count": 1 }"#).unwrap_err();
+        assert_eq!(err.to_string(), "name is required");
+
+        let err = shape
+            .validate_str(r#"{ "name": "a", "count": 1.5 }"#)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "count must be a whole number");
+
+        let err = shape.validate_str("[]").unwrap_err();
+        assert_eq!(err.to_string(), "value must be an object");
+    }
+}
@@ -0,0 +1,141 @@
+//! Strided 2D views over a [GmBuffer], for surface and pixel data.
+
+use crate::GmBuffer;
+
+/// A 2D view over a flat buffer, where each row is `stride` elements apart and
+/// only the first `width` elements of each row are part of the image.
+///
+/// Get one with [GmBuffer::view_2d].
+#[derive(Debug)]
+pub struct View2d<'a, T> {
+    data: &'a mut [T],
+    width: usize,
+    height: usize,
+    stride: usize,
+}
+
+impl<'a, T> View2d<'a, T> {
+    /// Creates a new view over `data`. Returns `None` if `width` is larger than `stride`,
+    /// or if `data` is too small to hold `height` rows.
+    pub fn new(data: &'a mut [T], width: usize, height: usize, stride: usize) -> Option<Self> {
+        if width > stride {
+            return None;
+        }
+
+        if height > 0 {
+            let needed = (height - 1).checked_mul(stride)?.checked_add(width)?;
+            if needed > data.len() {
+                return None;
+            }
+        }
+
+        Some(Self {
+            data,
+            width,
+            height,
+            stride,
+        })
+    }
+
+    /// The width of the view, in elements.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the view, in rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The distance between the start of two rows, in elements.
+    pub fn stride(&self) -> usize {
+        self.stride
+    }
+
+    /// Returns row `y`, `width` elements long.
+    ///
+    /// # Panics
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: usize) -> &[T] {
+        assert!(y < self.height, "row {} out of bounds ({})", y, self.height);
+        let start = y * self.stride;
+
+        &self.data[start..start + self.width]
+    }
+
+    /// Returns row `y` mutably, `width` elements long.
+    ///
+    /// # Panics
+    /// Panics if `y` is out of bounds.
+    pub fn row_mut(&mut self, y: usize) -> &mut [T] {
+        assert!(y < self.height, "row {} out of bounds ({})", y, self.height);
+        let start = y * self.stride;
+
+        &mut self.data[start..start + self.width]
+    }
+
+    /// Returns the element at `(x, y)`.
+    ///
+    /// # Panics
+    /// Panics if `x` or `y` are out of bounds.
+    pub fn pixel(&self, x: usize, y: usize) -> &T {
+        &self.row(y)[x]
+    }
+
+    /// Returns the element at `(x, y)` mutably.
+    ///
+    /// # Panics
+    /// Panics if `x` or `y` are out of bounds.
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut T {
+        &mut self.row_mut(y)[x]
+    }
+
+    /// Returns the element at `(x, y)`, or `None` if it's out of bounds.
+    pub fn get(&self, x: usize, y: usize) -> Option<&T> {
+        if x < self.width && y < self.height {
+            Some(self.pixel(x, y))
+        } else {
+            None
+        }
+    }
+
+    /// Returns an iterator over every row, mutably.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [T]> + '_ {
+        let width = self.width;
+
+        self.data
+            .chunks_mut(self.stride.max(1))
+            .take(self.height)
+            .map(move |row| &mut row[..width])
+    }
+}
+
+impl<T> GmBuffer<T> {
+    /// Views the buffer as a 2D image, `width` by `height`, with rows `stride` elements apart.
+    /// For a surface from `buffer_get_surface`, that's usually a `GmBuffer<u32>` where `stride == width`.
+    ///
+    /// Returns `None` if the buffer is too small, or `width > stride`.
+    pub fn view_2d(&mut self, width: usize, height: usize, stride: usize) -> Option<View2d<'_, T>> {
+        self.debug_check_canary();
+        View2d::new(self.buffer, width, height, stride)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strided_rows() {
+        let mut data = [0u8; 11];
+        let mut view = View2d::new(&mut data, 3, 3, 4).unwrap();
+
+        *view.pixel_mut(2, 1) = 7;
+        view.row_mut(2).fill(1);
+        assert_eq!(view.get(3, 0), None);
+        assert_eq!(view.rows_mut().count(), 3);
+
+        assert_eq!(data, [0, 0, 0, 0, 0, 0, 7, 0, 1, 1, 1]);
+        assert!(View2d::new(&mut data, 3, 4, 4).is_none());
+    }
+}
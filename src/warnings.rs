@@ -0,0 +1,183 @@
+//! A queue of non-fatal problems, which GM drains once a step so they show up somewhere
+//! visible instead of disappearing.
+
+use crate::{Bridge, BridgeWriter};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::Mutex;
+
+/// How serious a queued message is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    /// Something odd happened, but things carried on as normal.
+    Warning = 0,
+    /// Something failed.
+    Error = 1,
+}
+
+/// The most messages kept at once. Past this, the oldest are dropped.
+pub const MAX_QUEUED: usize = 256;
+
+static QUEUE: Mutex<VecDeque<(Severity, String)>> = Mutex::new(VecDeque::new());
+
+/// Queues a warning for GM. This can be called from any thread.
+pub fn push_gm_warning(message: impl fmt::Display) {
+    push(Severity::Warning, message.to_string());
+}
+
+/// Queues an error for GM. This can be called from any thread.
+pub fn push_gm_error(message: impl fmt::Display) {
+    push(Severity::Error, message.to_string());
+}
+
+fn push(severity: Severity, message: String) {
+    let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    if queue.len() == MAX_QUEUED {
+        queue.pop_front();
+    }
+    queue.push_back((severity, message));
+}
+
+/// The number of messages waiting to be drained.
+pub fn queued() -> usize {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner()).len()
+}
+
+/// Writes as many queued messages as fit into `bridge`, oldest first, and returns how many
+/// were written. Anything which doesn't fit stays queued for next time.
+///
+/// The layout is a u32 count, then for each message a u32 [Severity], a u32 length in bytes,
+/// and the message as a NUL-terminated string, padded to a multiple of 4 bytes.
+/// [GML_READER] reads it back.
+pub fn drain_into(bridge: &mut Bridge) -> usize {
+    let mut writer = bridge.writer();
+    // reserve the count
+    writer.write_u32(0);
+
+    let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    let mut count = 0;
+    while let Some((severity, message)) = queue.front_mut() {
+        if count == 0 {
+            // a message too big for an empty bridge would block the queue forever
+            truncate_to(
+                message,
+                (writer.remaining().saturating_sub(2) * 4).saturating_sub(1),
+            );
+        }
+
+        let units = BridgeWriter::padded_str_len(message);
+        if writer.remaining() < 2 + units {
+            break;
+        }
+
+        writer.write_u32(*severity as u32);
+        writer.write_u32(message.len() as u32);
+        writer.write_padded_str(message);
+
+        queue.pop_front();
+        count += 1;
+    }
+    drop(queue);
+
+    bridge.writer().write_u32(count as u32);
+
+    count
+}
+
+fn truncate_to(message: &mut String, max_len: usize) {
+    if message.len() <= max_len {
+        return;
+    }
+
+    let mut len = max_len;
+    while !message.is_char_boundary(len) {
+        len -= 1;
+    }
+    message.truncate(len);
+}
+
+/// A GML function which reads the messages written by [drain_into] into an array of
+/// `{ severity, message }` structs.
+pub const GML_READER: &str = r#"/// @param {Id.Buffer} _buffer
+function gm_read_drained_errors(_buffer) {
+    buffer_seek(_buffer, buffer_seek_start, 0);
+    var _count = buffer_read(_buffer, buffer_u32);
+    var _out = array_create(_count);
+    for (var _i = 0; _i < _count; _i++) {
+        var _severity = buffer_read(_buffer, buffer_u32);
+        var _len = buffer_read(_buffer, buffer_u32);
+        var _start = buffer_tell(_buffer);
+        var _message = buffer_read(_buffer, buffer_string);
+        buffer_seek(_buffer, buffer_seek_start, _start + (_len div 4 + 1) * 4);
+        _out[_i] = { severity: _severity, message: _message };
+    }
+    return _out;
+}
+"#;
+
+/// Generates an export, called `gm_drain_errors` by default, which drains queued messages into
+/// a GM buffer with [drain_into](crate::warnings::drain_into), returning how many were written.
+///
+/// In the extension editor, declare it as taking a buffer id, a buffer address, and the buffer's
+/// size in bytes, and call it once a step:
+/// ```gml
+/// var _count = gm_drain_errors(global.errors, buffer_get_address(global.errors), buffer_get_size(global.errors));
+/// if (_count > 0) {
+///     array_foreach(gm_read_drained_errors(global.errors), function(_e) {
+///         show_debug_message(_e.message);
+///     });
+/// }
+/// ```
+/// The buffer must be at least 1KiB.
+#[macro_export]
+macro_rules! export_drain_errors {
+    () => {
+        $crate::export_drain_errors!(gm_drain_errors);
+    };
+    ($name:ident) => {
+        /// Drains queued warnings and errors into a GM buffer.
+        ///
+        /// # Safety
+        /// `ptr` must be the address of a GM buffer at least `len` bytes long.
+        #[no_mangle]
+        pub unsafe extern "C" fn $name(
+            id: $crate::GmId,
+            ptr: $crate::GmPtr,
+            len: f64,
+        ) -> $crate::OutputCode {
+            $crate::catch_ffi!({
+                let buffer = unsafe { $crate::GmBuffer::new(id, ptr, len as usize / 4) };
+                let mut bridge = match $crate::Bridge::try_new(buffer) {
+                    Ok(bridge) => bridge,
+                    Err(e) => return e.into(),
+                };
+                let count = $crate::warnings::drain_into(&mut bridge);
+
+                $crate::OutputCode::custom(count as f64)
+            })
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_bridge;
+
+    #[test]
+    fn drains_what_fits() {
+        let _lock = crate::lock_queues();
+        let (backing, mut bridge) = test_bridge();
+
+        push_gm_warning("abcd");
+        push_gm_error(format_args!("{}", "x".repeat(1100)));
+        assert_eq!(drain_into(&mut bridge), 1);
+        assert_eq!(backing[..5], [1, 0, 4, u32::from_le_bytes(*b"abcd"), 0]);
+        assert_eq!(queued(), 1);
+
+        // the long message gets cut down to fit
+        assert_eq!(drain_into(&mut bridge), 1);
+        assert_eq!(backing[2], 1011);
+        assert_eq!(queued(), 0);
+    }
+}